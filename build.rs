@@ -0,0 +1,23 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Build facts served by GET /version
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    // embed_migrations! reads the directory, which Cargo can't see by itself
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+
+    // Images are usually built without .git, so the commit can be passed in
+    let commit = std::env::var("GIT_COMMIT").ok().or_else(|| {
+        Command::new("git").args(["rev-parse", "--short=12", "HEAD"]).output().ok()
+            .filter(|o| o.status.success())
+            .and_then(|o| String::from_utf8(o.stdout).ok())
+            .map(|s| s.trim().to_string())
+    });
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit.unwrap_or("unknown".to_string()));
+
+    let built_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", built_at);
+}
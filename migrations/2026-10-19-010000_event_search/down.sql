@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX "teams_event_id_idx";
+DROP INDEX "events_started_at_idx";
+DROP INDEX "events_desc_trgm_idx";
+DROP INDEX "events_name_trgm_idx";
+
+ALTER TABLE "teams" DROP COLUMN "capacity";
+
+ALTER TABLE "events" DROP COLUMN "ended_at";
+ALTER TABLE "events" DROP COLUMN "started_at";
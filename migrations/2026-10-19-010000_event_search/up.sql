@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+CREATE EXTENSION IF NOT EXISTS "pg_trgm";
+
+ALTER TABLE "events" ADD COLUMN "started_at" timestamp;
+ALTER TABLE "events" ADD COLUMN "ended_at" timestamp;
+
+ALTER TABLE "teams" ADD COLUMN "capacity" integer NOT NULL DEFAULT 5;
+
+CREATE INDEX "events_name_trgm_idx" ON "events" USING gin ("name" gin_trgm_ops);
+CREATE INDEX "events_desc_trgm_idx" ON "events" USING gin ("desc" gin_trgm_ops);
+CREATE INDEX "events_started_at_idx" ON "events" ("started_at");
+CREATE INDEX "teams_event_id_idx" ON "teams" ("event_id");
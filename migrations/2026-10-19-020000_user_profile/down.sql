@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS "set_updated_at" ON "requests";
+DROP TRIGGER IF EXISTS "set_updated_at" ON "joins";
+DROP TRIGGER IF EXISTS "set_updated_at" ON "teams";
+DROP TRIGGER IF EXISTS "set_updated_at" ON "solos";
+DROP TRIGGER IF EXISTS "set_updated_at" ON "events";
+DROP TRIGGER IF EXISTS "set_updated_at" ON "users";
+
+ALTER TABLE "users" DROP COLUMN "links";
+ALTER TABLE "users" DROP COLUMN "display_name";
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+ALTER TABLE "users" ADD COLUMN "display_name" varchar(100);
+ALTER TABLE "users" ADD COLUMN "links" varchar(400)[] NOT NULL DEFAULT '{}';
+
+-- Keep updated_at current on every UPDATE
+SELECT diesel_manage_updated_at('users');
+SELECT diesel_manage_updated_at('events');
+SELECT diesel_manage_updated_at('solos');
+SELECT diesel_manage_updated_at('teams');
+SELECT diesel_manage_updated_at('joins');
+SELECT diesel_manage_updated_at('requests');
@@ -0,0 +1,52 @@
+-- Your SQL goes here
+
+-- kind is either 'skill' or 'interest'.
+-- category is the team role a skill counts toward (frontend, backend, ...).
+CREATE TABLE "skills" (
+  "id" Uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "name" varchar(50) NOT NULL UNIQUE,
+  "kind" varchar(20) NOT NULL DEFAULT 'skill',
+  "category" varchar(50),
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+-- level is the self-assessed proficiency from 1 (beginner) to 5 (expert)
+CREATE TABLE "user_skills" (
+  "user_id" Uuid,
+  "skill_id" Uuid,
+  "level" integer NOT NULL DEFAULT 1 CHECK ("level" BETWEEN 1 AND 5),
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now()),
+  PRIMARY KEY ("user_id", "skill_id")
+);
+
+ALTER TABLE "user_skills" ADD FOREIGN KEY ("user_id") REFERENCES "users" ("id");
+
+ALTER TABLE "user_skills" ADD FOREIGN KEY ("skill_id") REFERENCES "skills" ("id");
+
+SELECT diesel_manage_updated_at('skills');
+SELECT diesel_manage_updated_at('user_skills');
+
+INSERT INTO "skills" ("name", "kind", "category") VALUES
+  ('TypeScript', 'skill', 'frontend'),
+  ('React', 'skill', 'frontend'),
+  ('Vue.js', 'skill', 'frontend'),
+  ('Rust', 'skill', 'backend'),
+  ('Go', 'skill', 'backend'),
+  ('Python', 'skill', 'backend'),
+  ('PostgreSQL', 'skill', 'backend'),
+  ('Swift', 'skill', 'mobile'),
+  ('Kotlin', 'skill', 'mobile'),
+  ('Figma', 'skill', 'designer'),
+  ('UI/UX Design', 'skill', 'designer'),
+  ('PyTorch', 'skill', 'ml'),
+  ('Data Analysis', 'skill', 'ml'),
+  ('Docker', 'skill', 'infra'),
+  ('AWS', 'skill', 'infra'),
+  ('Web', 'interest', NULL),
+  ('Game', 'interest', NULL),
+  ('IoT', 'interest', NULL),
+  ('AI', 'interest', NULL),
+  ('Security', 'interest', NULL),
+  ('Education', 'interest', NULL);
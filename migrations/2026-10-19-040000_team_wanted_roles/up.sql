@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- role matches skills.category, so members whose skills fall into the
+-- category fill the slot when they join.
+CREATE TABLE "team_wanted_roles" (
+  "team_id" Uuid,
+  "role" varchar(50),
+  "count" integer NOT NULL DEFAULT 1 CHECK ("count" > 0),
+  "filled" integer NOT NULL DEFAULT 0 CHECK ("filled" >= 0),
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now()),
+  PRIMARY KEY ("team_id", "role")
+);
+
+ALTER TABLE "team_wanted_roles" ADD FOREIGN KEY ("team_id") REFERENCES "teams" ("id");
+
+SELECT diesel_manage_updated_at('team_wanted_roles');
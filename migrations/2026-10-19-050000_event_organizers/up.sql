@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "event_organizers" (
+  "event_id" Uuid,
+  "user_id" Uuid,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now()),
+  PRIMARY KEY ("event_id", "user_id")
+);
+
+ALTER TABLE "event_organizers" ADD FOREIGN KEY ("event_id") REFERENCES "events" ("id");
+
+ALTER TABLE "event_organizers" ADD FOREIGN KEY ("user_id") REFERENCES "users" ("id");
+
+SELECT diesel_manage_updated_at('event_organizers');
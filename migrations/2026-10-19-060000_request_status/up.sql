@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+-- status is one of 'pending', 'accepted' or 'declined'
+ALTER TABLE "requests" ADD COLUMN "status" varchar(20) NOT NULL DEFAULT 'pending';
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+-- user_id receives the notification, actor_id is who it is about
+CREATE TABLE "notifications" (
+  "id" Uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "user_id" Uuid NOT NULL,
+  "kind" varchar(30) NOT NULL,
+  "team_id" Uuid NOT NULL,
+  "actor_id" Uuid NOT NULL,
+  "read_at" timestamp,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+ALTER TABLE "notifications" ADD FOREIGN KEY ("user_id") REFERENCES "users" ("id");
+
+CREATE INDEX "notifications_user_id_created_at_idx" ON "notifications" ("user_id", "created_at");
+CREATE INDEX "notifications_unread_idx" ON "notifications" ("user_id") WHERE "read_at" IS NULL;
+
+SELECT diesel_manage_updated_at('notifications');
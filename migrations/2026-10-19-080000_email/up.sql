@@ -0,0 +1,35 @@
+-- Your SQL goes here
+
+-- One row per user who has set up email; the flags follow NoticeKind groups
+CREATE TABLE "email_preferences" (
+  "user_id" Uuid PRIMARY KEY,
+  "email" varchar(255) NOT NULL,
+  "locale" varchar(10) NOT NULL DEFAULT 'en',
+  "on_request" boolean NOT NULL DEFAULT true,
+  "on_join" boolean NOT NULL DEFAULT true,
+  "on_team" boolean NOT NULL DEFAULT true,
+  "unsubscribe_token" Uuid NOT NULL UNIQUE DEFAULT (uuid_generate_v4()),
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+ALTER TABLE "email_preferences" ADD FOREIGN KEY ("user_id") REFERENCES "users" ("id");
+
+-- Rendered messages waiting for the mail worker
+CREATE TABLE "email_queue" (
+  "id" Uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "recipient" varchar(255) NOT NULL,
+  "subject" varchar(255) NOT NULL,
+  "body" text NOT NULL,
+  "attempts" int4 NOT NULL DEFAULT 0,
+  "next_attempt_at" timestamp NOT NULL DEFAULT (now()),
+  "sent_at" timestamp,
+  "last_error" text,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+CREATE INDEX "email_queue_pending_idx" ON "email_queue" ("next_attempt_at") WHERE "sent_at" IS NULL;
+
+SELECT diesel_manage_updated_at('email_preferences');
+SELECT diesel_manage_updated_at('email_queue');
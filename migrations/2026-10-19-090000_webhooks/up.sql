@@ -0,0 +1,36 @@
+-- Your SQL goes here
+
+CREATE TABLE "webhook_subscriptions" (
+  "id" Uuid PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  "event_id" Uuid NOT NULL,
+  "url" varchar(400) NOT NULL,
+  "secret" varchar(100) NOT NULL,
+  "event_types" varchar(50)[] NOT NULL,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+-- Every payload sent to a subscription, kept as the delivery log
+CREATE TABLE "webhook_deliveries" (
+  "id" Uuid PRIMARY KEY,
+  "subscription_id" Uuid NOT NULL,
+  "event_type" varchar(50) NOT NULL,
+  "payload" text NOT NULL,
+  "attempts" int4 NOT NULL DEFAULT 0,
+  "next_attempt_at" timestamp NOT NULL DEFAULT (now()),
+  "delivered_at" timestamp,
+  "response_status" int4,
+  "last_error" text,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+ALTER TABLE "webhook_subscriptions" ADD FOREIGN KEY ("event_id") REFERENCES "events" ("id");
+ALTER TABLE "webhook_deliveries" ADD FOREIGN KEY ("subscription_id") REFERENCES "webhook_subscriptions" ("id");
+
+CREATE INDEX "webhook_subscriptions_event_id_idx" ON "webhook_subscriptions" ("event_id");
+CREATE INDEX "webhook_deliveries_subscription_id_created_at_idx" ON "webhook_deliveries" ("subscription_id", "created_at");
+CREATE INDEX "webhook_deliveries_pending_idx" ON "webhook_deliveries" ("next_attempt_at") WHERE "delivered_at" IS NULL;
+
+SELECT diesel_manage_updated_at('webhook_subscriptions');
+SELECT diesel_manage_updated_at('webhook_deliveries');
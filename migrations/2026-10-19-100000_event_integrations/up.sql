@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- Incoming-webhook of the chat channel an event announces into
+CREATE TABLE "event_integrations" (
+  "event_id" Uuid PRIMARY KEY,
+  "platform" varchar(10) NOT NULL,
+  "webhook_url" varchar(400) NOT NULL,
+  "notify_recruiting" boolean NOT NULL DEFAULT true,
+  "notify_full" boolean NOT NULL DEFAULT true,
+  "notify_solos" boolean NOT NULL DEFAULT true,
+  "created_at" timestamp NOT NULL DEFAULT (now()),
+  "updated_at" timestamp NOT NULL DEFAULT (now())
+);
+
+ALTER TABLE "event_integrations" ADD FOREIGN KEY ("event_id") REFERENCES "events" ("id");
+
+SELECT diesel_manage_updated_at('event_integrations');
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- The wanted role the member filled when joining, so leaving frees it again.
+-- Members who joined before this have none and free nothing.
+ALTER TABLE "joins" ADD COLUMN "filled_role" varchar(50);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "email_preferences" DROP COLUMN "verified_at";
+ALTER TABLE "email_preferences" DROP COLUMN "verification_token";
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Nothing is mailed to an address until its owner opens the link sent to it.
+-- Addresses saved before this start unverified; saving the preference again
+-- sends the link.
+ALTER TABLE "email_preferences" ADD COLUMN "verification_token" Uuid NOT NULL UNIQUE DEFAULT (uuid_generate_v4());
+ALTER TABLE "email_preferences" ADD COLUMN "verified_at" timestamp;
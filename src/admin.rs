@@ -0,0 +1,151 @@
+use std::fs;
+
+use anyhow::{anyhow, Context};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::cli::{AdminCommand, EventCommand, OrganizerCommand, TeamCommand, UserCommand};
+use crate::cruds::{self, EventFilter};
+use crate::models::{Event, Request, TeamDetail, User, UserDetail};
+use crate::pagination::{Order, PageParams, SortKey, MAX_LIMIT};
+
+#[derive(Serialize)]
+struct EventExport {
+    event: Event,
+    organizers: Vec<User>,
+    teams: Vec<TeamExport>,
+    solos: Vec<UserDetail>,
+    requests: Vec<Request>,
+}
+
+#[derive(Serialize)]
+struct TeamExport {
+    #[serde(flatten)]
+    team: TeamDetail,
+    // Leader first
+    members: Vec<UserDetail>,
+}
+
+pub fn run(command: AdminCommand) -> anyhow::Result<()> {
+    match command {
+        AdminCommand::Events(c) => events(c),
+        AdminCommand::Organizers(c) => organizers(c),
+        AdminCommand::Users(c) => users(c),
+        AdminCommand::Teams(c) => teams(c),
+    }
+}
+
+fn events(command: EventCommand) -> anyhow::Result<()> {
+    match command {
+        EventCommand::List{q} => {
+            let filter = EventFilter{q, ..EventFilter::default()};
+            let mut page = PageParams{limit: MAX_LIMIT, offset: 0, sort: SortKey::CreatedAt, order: Order::Asc};
+            loop {
+                let events = cruds::get_event_list(&filter, &page)?;
+                for e in &events.items {
+                    let started_at = e.started_at.map(|t| t.to_string()).unwrap_or("-".to_string());
+                    println!("{}\t{}\t{}", e.id, started_at, e.name);
+                }
+                if events.next_cursor.is_none() {
+                    return Ok(());
+                }
+                page.offset += page.limit;
+            }
+        },
+        EventCommand::Create{name, organizer, desc, url, started_at, ended_at} => {
+            let organizer = find_user(&organizer)?;
+            let event = cruds::create_event(&name, &desc, &url, started_at.as_ref(), ended_at.as_ref(), &organizer.id.to_string())?;
+            println!("{}", event.id);
+            Ok(())
+        },
+        EventCommand::Delete{event_id, yes} => {
+            let event = cruds::get_event_info_by_id(&event_id)
+                .with_context(|| format!("No event {}", event_id))?;
+            if !yes {
+                return Err(anyhow!("This deletes {} with all its teams; pass --yes to go ahead", event.name));
+            }
+            cruds::delete_event_by_id(&event_id)?;
+            println!("Deleted {}", event.name);
+            Ok(())
+        },
+        EventCommand::Export{event_id, output} => {
+            let export = EventExport{
+                event: cruds::get_event_info_by_id(&event_id)
+                    .with_context(|| format!("No event {}", event_id))?,
+                organizers: cruds::get_event_organizers(&event_id)?,
+                teams: cruds::get_event_teams_with_members(&event_id)?
+                    .into_iter()
+                    .map(|(team, members)| TeamExport{team, members})
+                    .collect(),
+                solos: cruds::get_event_solo_details(&event_id)?,
+                requests: cruds::get_event_requests(&event_id)?,
+            };
+            let json = serde_json::to_string_pretty(&export)?;
+            match output {
+                Some(path) => fs::write(&path, json)
+                    .with_context(|| format!("Failed to write {}", path.display()))?,
+                None => println!("{}", json),
+            }
+            Ok(())
+        },
+    }
+}
+
+fn organizers(command: OrganizerCommand) -> anyhow::Result<()> {
+    match command {
+        OrganizerCommand::Grant{event_id, user} => {
+            let user = find_user(&user)?;
+            if cruds::add_event_organizer(&event_id, &user.id)? {
+                println!("{} now organizes {}", user.name, event_id);
+            } else {
+                println!("{} already organizes {}", user.name, event_id);
+            }
+            Ok(())
+        },
+        OrganizerCommand::Revoke{event_id, user} => {
+            let user = find_user(&user)?;
+            if cruds::delete_event_organizer(&event_id, &user.id)? {
+                println!("{} no longer organizes {}", user.name, event_id);
+            } else {
+                println!("{} didn't organize {}", user.name, event_id);
+            }
+            Ok(())
+        },
+    }
+}
+
+fn users(command: UserCommand) -> anyhow::Result<()> {
+    match command {
+        UserCommand::Merge{duplicate, into, yes} => {
+            let duplicate = find_user(&duplicate)?;
+            let into = find_user(&into)?;
+            if !yes {
+                return Err(anyhow!("This deletes {} ({}) after moving everything to {} ({}); pass --yes to go ahead",
+                    duplicate.name, duplicate.id, into.name, into.id));
+            }
+            cruds::merge_users(&duplicate.id, &into.id)?;
+            println!("Merged {} into {}", duplicate.id, into.id);
+            Ok(())
+        },
+    }
+}
+
+fn teams(command: TeamCommand) -> anyhow::Result<()> {
+    match command {
+        TeamCommand::Move{user, team_id} => {
+            let user = find_user(&user)?;
+            let from = cruds::move_member(&user.id, &team_id)?;
+            println!("Moved {} from {} to {}", user.name, from.name, team_id);
+            Ok(())
+        },
+    }
+}
+
+// A user by ID, or by GitHub login for anything that isn't one
+fn find_user(reference: &String) -> anyhow::Result<User> {
+    let user = match Uuid::parse_str(reference) {
+        Ok(_) => cruds::get_user_info_by_id(reference),
+        Err(_) => cruds::get_user_info_by_name(reference),
+    };
+    user.with_context(|| format!("No user {}", reference))
+}
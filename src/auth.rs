@@ -1,9 +1,21 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
 use actix_web::HttpRequest;
 use reqwest::header;
 use serde::Deserialize;
 use anyhow::{Result, Ok};
 
-#[derive(Debug, Deserialize)]
+use crate::{config, metrics};
+
+// Tokens the mock identity provider accepts are this followed by a login
+pub const MOCK_TOKEN_PREFIX: &str = "mock_";
+
+// Verified tokens, so every request doesn't cost a round trip to GitHub
+static TOKEN_CACHE: OnceLock<Mutex<HashMap<String, (Instant, GithubUserData)>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct GithubUserData {
     pub login: String,
     pub avatar_url: String,
@@ -13,28 +25,82 @@ pub fn parse_token(req: HttpRequest) -> Option<String> {
   match req.headers().get("Authorization") {
     Some(auth_header) => {
       let auth_str = auth_header.to_str().unwrap();
-      if auth_str.starts_with("Bearer ") {
-        return Some(auth_str[7..].to_string())
-      } else {
-        return None
+      match auth_str.strip_prefix("Bearer ") {
+        Some(token) => return Some(token.to_string()),
+        None => return None
       }
     },
     None => return None
   };
 }
 
+pub fn mock_token(login: &str) -> String {
+  format!("{}{}", MOCK_TOKEN_PREFIX, login)
+}
+
+// GitHub's identicon, so mock users look like fresh GitHub accounts
+pub fn mock_avatar_url(login: &str) -> String {
+  format!("https://github.com/identicons/{}.png", login)
+}
+
+// The user a mock token stands for, when mock tokens are turned on
+fn mock_user(token: &str) -> Option<GithubUserData> {
+  if !config::get().github.mock_tokens {
+    return None;
+  }
+  match token.strip_prefix(MOCK_TOKEN_PREFIX) {
+    Some(login) if !login.is_empty() => return Some(GithubUserData{login: login.to_string(), avatar_url: mock_avatar_url(login)}),
+    _ => return None
+  };
+}
+
+fn cached(token: &str, ttl: Duration) -> Option<GithubUserData> {
+  let cache = TOKEN_CACHE.get_or_init(Default::default).lock().unwrap();
+  match cache.get(token) {
+    Some((at, user_data)) if at.elapsed() < ttl => return Some(user_data.clone()),
+    _ => return None
+  };
+}
+
+fn remember(token: String, user_data: &GithubUserData, ttl: Duration) {
+  let mut cache = TOKEN_CACHE.get_or_init(Default::default).lock().unwrap();
+  cache.retain(|_, (at, _)| at.elapsed() < ttl);
+  cache.insert(token, (Instant::now(), user_data.clone()));
+}
+
+#[tracing::instrument(skip_all, fields(login, cached))]
 pub async fn verification(token: String) -> Result<GithubUserData> {
-  let url = "https://api.github.com/user";
+  if let Some(user_data) = mock_user(&token) {
+    tracing::Span::current().record("login", &user_data.login);
+    return Ok(user_data);
+  }
+  let ttl = Duration::from_secs(config::get().cache.token_ttl_secs);
+  if !ttl.is_zero() {
+    let hit = cached(&token, ttl);
+    metrics::count_token_cache(hit.is_some());
+    tracing::Span::current().record("cached", hit.is_some());
+    if let Some(user_data) = hit {
+      tracing::Span::current().record("login", &user_data.login);
+      return Ok(user_data);
+    }
+  }
+  let url = format!("{}/user", config::get().github.api_url.trim_end_matches('/'));
   let mut custom_headers = header::HeaderMap::new();
   custom_headers.insert(header::USER_AGENT, "HotchPotch".parse()?);
   custom_headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse()?);
 
   let client = reqwest::Client::new();
+  let started = Instant::now();
   let res = client.get(url)
     .headers(custom_headers)
-    .send().await?
-    .text().await?;
-  
-  let user_data = serde_json::from_str(&res)?;
+    .send().await;
+  metrics::observe_github_verification(started.elapsed());
+  let res = res?.text().await?;
+
+  let user_data: GithubUserData = serde_json::from_str(&res)?;
+  tracing::Span::current().record("login", &user_data.login);
+  if !ttl.is_zero() {
+    remember(token, &user_data, ttl);
+  }
   Ok(user_data)
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::Notification;
+
+// Notices not yet picked up by a slow subscriber before it starts lagging
+const CAPACITY: usize = 256;
+// Comment line sent on idle streams so proxies don't close them
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+static SENDER: OnceLock<broadcast::Sender<Notification>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NoticeKind {
+    RequestCreated,
+    RequestAccepted,
+    RequestDeclined,
+    MemberJoined,
+    MemberLeft,
+    TeamDisbanded,
+}
+
+impl NoticeKind {
+    // Value stored in notifications.kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoticeKind::RequestCreated => "request_created",
+            NoticeKind::RequestAccepted => "request_accepted",
+            NoticeKind::RequestDeclined => "request_declined",
+            NoticeKind::MemberJoined => "member_joined",
+            NoticeKind::MemberLeft => "member_left",
+            NoticeKind::TeamDisbanded => "team_disbanded",
+        }
+    }
+}
+
+fn sender() -> &'static broadcast::Sender<Notification> {
+    SENDER.get_or_init(|| broadcast::channel(CAPACITY).0)
+}
+
+/// Deliver a stored notification to everyone currently subscribed.
+/// Having no subscribers is not an error.
+pub fn publish(notification: Notification) {
+    let _ = sender().send(notification);
+}
+
+pub fn subscribe() -> broadcast::Receiver<Notification> {
+    sender().subscribe()
+}
+
+/// Server-Sent Events stream of the notifications addressed to `user_id`
+pub fn sse_stream(user_id: Uuid) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    futures_util::stream::unfold(subscribe(), move |mut rx| async move {
+        loop {
+            match tokio::time::timeout(KEEP_ALIVE, rx.recv()).await {
+                Ok(Ok(notice)) => {
+                    if notice.user_id != user_id {
+                        continue;
+                    }
+                    let data = match serde_json::to_string(&notice) {
+                        Ok(d) => d,
+                        Err(_) => continue,
+                    };
+                    return Some((Ok(Bytes::from(format!("data: {}\n\n", data))), rx));
+                },
+                // A client that falls behind just misses the oldest notices
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+                Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), rx)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod bus_tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn notification(user_id: Uuid, kind: NoticeKind, team_id: Uuid) -> Notification {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        Notification{
+            id: Uuid::new_v4(),
+            user_id,
+            kind: kind.as_str().to_string(),
+            team_id,
+            actor_id: user_id,
+            read_at: None,
+            created_at: at,
+            updated_at: at,
+        }
+    }
+
+    #[actix_web::test]
+    async fn stream_only_delivers_own_notices() {
+        let me = Uuid::new_v4();
+        let someone = Uuid::new_v4();
+        let team = Uuid::new_v4();
+        let stream = sse_stream(me);
+        futures_util::pin_mut!(stream);
+        publish(notification(someone, NoticeKind::MemberJoined, team));
+        publish(notification(me, NoticeKind::RequestAccepted, team));
+        let chunk = stream.next().await.unwrap().unwrap();
+        let text = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(text.starts_with("data: "));
+        assert!(text.contains("\"kind\":\"request_accepted\""));
+        assert!(text.contains(&team.to_string()));
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use serde_json::json;
+
+use crate::models::EventIntegration;
+use crate::outbound;
+
+pub const PLATFORMS: [&str; 2] = ["discord", "slack"];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Longest Retry-After we are willing to sleep through
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+// Discord embed colours
+const RECRUITING_COLOR: u32 = 0x57f287;
+const FULL_COLOR: u32 = 0xed4245;
+const SOLOS_COLOR: u32 = 0xfee75c;
+
+static SENDER: OnceLock<mpsc::Sender<Outgoing>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Platform {
+    Discord,
+    Slack,
+}
+
+impl Platform {
+    pub fn parse(s: &str) -> Option<Platform> {
+        match s {
+            "discord" => Some(Platform::Discord),
+            "slack" => Some(Platform::Slack),
+            _ => None,
+        }
+    }
+
+    // Posts allowed per window on a single incoming webhook
+    fn rate(&self) -> (usize, Duration) {
+        match self {
+            Platform::Discord => (5, Duration::from_secs(2)),
+            Platform::Slack => (1, Duration::from_secs(1)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChatMessage {
+    TeamRecruiting {
+        event_name: String,
+        team_name: String,
+        desc: Option<String>,
+        size: usize,
+        capacity: i32,
+    },
+    TeamFull {
+        event_name: String,
+        team_name: String,
+        capacity: i32,
+    },
+    SolosLooking {
+        event_name: String,
+        count: i64,
+    },
+}
+
+// A message on its way to one channel
+#[derive(Clone, PartialEq, Debug)]
+pub struct Outgoing {
+    pub platform: Platform,
+    pub url: String,
+    pub message: ChatMessage,
+}
+
+/// Whether the organizer turned on this kind of message
+pub fn wants(integration: &EventIntegration, message: &ChatMessage) -> bool {
+    match message {
+        ChatMessage::TeamRecruiting{..} => integration.notify_recruiting,
+        ChatMessage::TeamFull{..} => integration.notify_full,
+        ChatMessage::SolosLooking{..} => integration.notify_solos,
+    }
+}
+
+fn title_and_text(message: &ChatMessage) -> (String, String, u32) {
+    match message {
+        ChatMessage::TeamRecruiting{event_name, team_name, desc, size, capacity} => {
+            let mut text = format!("{} of {} members so far, {} spots open.", size, capacity, (*capacity as usize).saturating_sub(*size));
+            if let Some(desc) = desc.as_ref().filter(|d| !d.trim().is_empty()) {
+                text = format!("{}\n{}", desc, text);
+            }
+            (format!("New team recruiting for {}: {}", event_name, team_name), text, RECRUITING_COLOR)
+        },
+        ChatMessage::TeamFull{event_name, team_name, capacity} => (
+            format!("{} is full", team_name),
+            format!("{} reached {} members and stopped recruiting for {}.", team_name, capacity, event_name),
+            FULL_COLOR,
+        ),
+        ChatMessage::SolosLooking{event_name, count} => (
+            format!("Solos still looking for a team in {}", event_name),
+            format!("{} participant(s) are still looking for a team. Leaders, go say hi!", count),
+            SOLOS_COLOR,
+        ),
+    }
+}
+
+// Slack reads &, < and > in mrkdwn as entities and links, so team names
+// and descriptions could otherwise post <!channel> pings or disguised links
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Body of the incoming-webhook post in the platform's own format
+pub fn format(platform: Platform, message: &ChatMessage) -> serde_json::Value {
+    let (title, text, color) = title_and_text(message);
+    match platform {
+        Platform::Discord => json!({
+            "content": null,
+            "embeds": [{"title": title, "description": text, "color": color}],
+            "allowed_mentions": {"parse": []},
+        }),
+        Platform::Slack => json!({
+            // Shown in notifications and by clients that can't render blocks
+            "text": escape_slack(&format!("{}: {}", title, text)),
+            "blocks": [
+                {"type": "header", "text": {"type": "plain_text", "text": title}},
+                {"type": "section", "text": {"type": "mrkdwn", "text": escape_slack(&text)}},
+            ],
+        }),
+    }
+}
+
+/// Sliding-window limit of posts per incoming webhook
+#[derive(Default)]
+pub struct RateLimiter {
+    sent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    /// How long to wait before posting to `url` at `now`
+    pub fn wait(&mut self, url: &str, limit: usize, window: Duration, now: Instant) -> Duration {
+        let sent = self.sent.entry(url.to_string()).or_default();
+        while sent.front().is_some_and(|t| now.duration_since(*t) >= window) {
+            sent.pop_front();
+        }
+        if sent.len() < limit {
+            return Duration::ZERO;
+        }
+        window - now.duration_since(sent[0])
+    }
+
+    pub fn record(&mut self, url: &str, at: Instant) {
+        self.sent.entry(url.to_string()).or_default().push_back(at);
+    }
+}
+
+// Only the latest solo count matters, so older ones for the same channel
+// are dropped when posts back up
+fn coalesce(batch: Vec<Outgoing>) -> Vec<Outgoing> {
+    let mut result: Vec<Outgoing> = vec![];
+    for out in batch {
+        if let ChatMessage::SolosLooking{..} = out.message {
+            result.retain(|o| !(o.url == out.url && matches!(o.message, ChatMessage::SolosLooking{..})));
+        }
+        result.push(out);
+    }
+    result
+}
+
+// The URL is checked again before each post, since its host may resolve
+// somewhere else by now
+fn post(out: &Outgoing) -> anyhow::Result<()> {
+    let client = outbound::client(&out.url, REQUEST_TIMEOUT)?;
+    for _ in 0..2 {
+        let res = client.post(&out.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(format(out.platform, &out.message).to_string())
+            .send()?;
+        if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = res.headers().get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(Duration::from_secs_f64)
+                .unwrap_or(Duration::from_secs(1));
+            std::thread::sleep(retry_after.min(MAX_RETRY_AFTER));
+            continue;
+        }
+        if !res.status().is_success() {
+            return Err(anyhow!("Chat webhook responded {}", res.status()));
+        }
+        return Ok(());
+    }
+    Err(anyhow!("Chat webhook kept rate limiting"))
+}
+
+/// Hand messages to the sender thread. They are dropped when it isn't
+/// running; a lost announcement is not worth failing a request over.
+pub fn send_all(outgoing: Vec<Outgoing>) {
+    if let Some(sender) = SENDER.get() {
+        for out in outgoing {
+            let _ = sender.send(out);
+        }
+    }
+}
+
+pub fn spawn_worker() {
+    let (tx, rx) = mpsc::channel::<Outgoing>();
+    if SENDER.set(tx).is_err() {
+        return;
+    }
+    std::thread::spawn(move || {
+        let mut limiter = RateLimiter::default();
+        while let Ok(first) = rx.recv() {
+            let mut batch = vec![first];
+            batch.extend(rx.try_iter());
+            for out in coalesce(batch) {
+                let (limit, window) = out.platform.rate();
+                let wait = limiter.wait(&out.url, limit, window, Instant::now());
+                std::thread::sleep(wait);
+                limiter.record(&out.url, Instant::now());
+                if let Err(e) = post(&out) {
+                    tracing::warn!(error = format!("{:#}", e), "Chat post failed");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod chat_tests {
+    use super::*;
+
+    fn recruiting() -> ChatMessage {
+        ChatMessage::TeamRecruiting{
+            event_name: "Hack Day".to_string(),
+            team_name: "Crabs".to_string(),
+            desc: Some("We build in Rust".to_string()),
+            size: 1,
+            capacity: 4,
+        }
+    }
+
+    #[test]
+    fn formats_for_each_platform() {
+        let discord = format(Platform::Discord, &recruiting());
+        assert_eq!(discord["embeds"][0]["title"], "New team recruiting for Hack Day: Crabs");
+        assert_eq!(discord["embeds"][0]["description"], "We build in Rust\n1 of 4 members so far, 3 spots open.");
+        let slack = format(Platform::Slack, &recruiting());
+        assert_eq!(slack["blocks"][0]["text"]["text"], "New team recruiting for Hack Day: Crabs");
+        assert!(slack["text"].as_str().unwrap().starts_with("New team recruiting"));
+    }
+
+    #[test]
+    fn escapes_slack_markup() {
+        let message = ChatMessage::TeamRecruiting{
+            event_name: "Hack Day".to_string(),
+            team_name: "<!channel> & co".to_string(),
+            desc: Some("<https://evil.example|docs>".to_string()),
+            size: 1,
+            capacity: 4,
+        };
+        let slack = format(Platform::Slack, &message);
+        assert_eq!(slack["blocks"][0]["text"]["text"], "New team recruiting for Hack Day: <!channel> & co");
+        assert!(slack["blocks"][1]["text"]["text"].as_str().unwrap().starts_with("&lt;https://evil.example|docs&gt;\n"));
+        assert!(slack["text"].as_str().unwrap().contains("&lt;!channel&gt; &amp; co"));
+    }
+
+    #[test]
+    fn limits_posts_per_window() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        let window = Duration::from_secs(2);
+        for _ in 0..2 {
+            assert_eq!(limiter.wait("a", 2, window, start), Duration::ZERO);
+            limiter.record("a", start);
+        }
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.wait("a", 2, window, later), Duration::from_millis(1500));
+        // Other channels have their own budget
+        assert_eq!(limiter.wait("b", 2, window, later), Duration::ZERO);
+        assert_eq!(limiter.wait("a", 2, window, start + window), Duration::ZERO);
+    }
+
+    #[test]
+    fn keeps_only_latest_solo_count() {
+        let solos = |count| Outgoing{
+            platform: Platform::Slack,
+            url: "u".to_string(),
+            message: ChatMessage::SolosLooking{event_name: "Hack Day".to_string(), count},
+        };
+        let team = Outgoing{platform: Platform::Slack, url: "u".to_string(), message: recruiting()};
+        let result = coalesce(vec![solos(1), team.clone(), solos(2)]);
+        assert_eq!(result, vec![team, solos(2)]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use clap::{Args, Parser, Subcommand};
+
+/// Backend of hotchpotch, the team matching service for hackathons
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Run pending database migrations before serving
+    #[arg(long, global = true, env = "MIGRATE_ON_START")]
+    pub migrate_on_start: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Serve the API (the default)
+    Serve,
+    /// Run pending database migrations and exit
+    Migrate,
+    /// Fill the database with demo data and write mock tokens for its users
+    Seed(SeedArgs),
+    #[command(flatten)]
+    Admin(AdminCommand),
+}
+
+#[derive(Args, Debug)]
+pub struct SeedArgs {
+    /// Random seed; the same seed gives the same data
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+    #[arg(long, default_value_t = 3)]
+    pub events: usize,
+    #[arg(long, default_value_t = 40)]
+    pub users: usize,
+    /// Where to write the mock token of every seeded user
+    #[arg(long, default_value = "seed-tokens.json")]
+    pub tokens: PathBuf,
+}
+
+// Operations organizers used to ask for raw SQL for. Users are given by
+// ID or GitHub login.
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// List, create, delete or export events
+    #[command(subcommand)]
+    Events(EventCommand),
+    /// Grant or revoke the organizer role of an event
+    #[command(subcommand)]
+    Organizers(OrganizerCommand),
+    /// Fix up user accounts
+    #[command(subcommand)]
+    Users(UserCommand),
+    /// Fix up team memberships
+    #[command(subcommand)]
+    Teams(TeamCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EventCommand {
+    /// List events, oldest first
+    List {
+        /// Only events whose name or description contains this
+        #[arg(long)]
+        q: Option<String>,
+    },
+    /// Create an event organized by a user
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        organizer: String,
+        #[arg(long, default_value = "")]
+        desc: String,
+        #[arg(long, default_value = "")]
+        url: String,
+        /// Start time, as in 2026-11-01T09:00:00
+        #[arg(long)]
+        started_at: Option<NaiveDateTime>,
+        /// End time, as in 2026-11-02T18:00:00
+        #[arg(long)]
+        ended_at: Option<NaiveDateTime>,
+    },
+    /// Delete an event with its teams, solos and requests
+    Delete {
+        event_id: String,
+        /// Confirm the deletion
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Export an event with its organizers, teams, solos and requests as JSON
+    Export {
+        event_id: String,
+        /// File to write instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OrganizerCommand {
+    /// Make a user an organizer of an event
+    Grant { event_id: String, user: String },
+    /// Take the organizer role of an event away from a user
+    Revoke { event_id: String, user: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// Move everything of a duplicate account over to another one and delete it
+    Merge {
+        duplicate: String,
+        into: String,
+        /// Confirm the merge
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TeamCommand {
+    /// Move a member to another team of the same event
+    Move { user: String, team_id: String },
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn parses_admin_commands() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["hotchpotch", "users", "merge", "octo-old", "octo", "--yes"]).unwrap();
+        match cli.command {
+            Some(Command::Admin(AdminCommand::Users(UserCommand::Merge{duplicate, into, yes}))) => {
+                assert_eq!((duplicate.as_str(), into.as_str(), yes), ("octo-old", "octo", true));
+            },
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        assert!(Cli::try_parse_from(["hotchpotch", "events", "create", "--name", "x", "--organizer", "octo", "--started-at", "tomorrow"]).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+use anyhow::{anyhow, Context};
+use dotenv::dotenv;
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+use crate::{cors, telemetry};
+
+// Read when HOTCHPOTCH_CONFIG doesn't name another file
+const DEFAULT_FILE: &str = "hotchpotch.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Settings read once at startup. Values come from the TOML file first and
+/// are then overridden by environment variables.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Filter directives such as `info` or `info,hotchpotch_web_backend=debug`
+    pub log_level: String,
+    // `text` for people, `json` for log collectors
+    pub log_format: String,
+    pub server: ServerConfig,
+    pub cors: CorsConfig,
+    pub database: DatabaseConfig,
+    pub github: GithubConfig,
+    pub cache: CacheConfig,
+    pub health: HealthConfig,
+    pub features: FeatureConfig,
+    pub outbound: OutboundConfig,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    // Origins the browser frontend is served from. `https://*.example.com`
+    // matches any subdomain.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_secs: usize,
+    // Accept any origin; only for running the frontend locally
+    pub dev_mode: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_idle: Option<u32>,
+    pub connect_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GithubConfig {
+    // Overridden for GitHub Enterprise or a stub in development
+    pub api_url: String,
+    // Development only: accept `mock_<login>` as the token of <login>
+    pub mock_tokens: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    // How long a verified access token is trusted before asking GitHub again; 0 turns it off
+    pub token_ttl_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    // Also require GitHub to answer before reporting ready
+    pub check_github: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    pub email: bool,
+    pub webhooks: bool,
+    pub chat: bool,
+    pub api_docs: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboundConfig {
+    // Let webhook and chat URLs point at loopback and private addresses; only
+    // for receivers running next to the server in development
+    pub allow_private_networks: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config{
+            log_level: "info".to_string(),
+            log_format: "text".to_string(),
+            server: ServerConfig::default(),
+            cors: CorsConfig::default(),
+            database: DatabaseConfig::default(),
+            github: GithubConfig::default(),
+            cache: CacheConfig::default(),
+            health: HealthConfig::default(),
+            features: FeatureConfig::default(),
+            outbound: OutboundConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig{
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> CorsConfig {
+        CorsConfig{
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["Authorization", "Accept", "Content-Type"].map(String::from).to_vec(),
+            max_age_secs: 3600,
+            dev_mode: false,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> DatabaseConfig {
+        DatabaseConfig{
+            url: String::new(),
+            max_connections: 10,
+            min_idle: None,
+            connect_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for GithubConfig {
+    fn default() -> GithubConfig {
+        GithubConfig{api_url: "https://api.github.com".to_string(), mock_tokens: false}
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig{token_ttl_secs: 60}
+    }
+}
+
+impl Default for FeatureConfig {
+    fn default() -> FeatureConfig {
+        FeatureConfig{email: true, webhooks: true, chat: true, api_docs: true}
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn override_with<T: FromStr>(target: &mut T, name: &str) -> anyhow::Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Ok(value) = env::var(name) {
+        *target = value.parse().map_err(|e| anyhow!("{} is invalid: {}", name, e))?;
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Parse a TOML document; missing keys keep their defaults
+    pub fn from_toml(text: &str) -> anyhow::Result<Config> {
+        Ok(toml::from_str(text)?)
+    }
+
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        override_with(&mut self.log_level, "LOG_LEVEL")?;
+        override_with(&mut self.log_format, "LOG_FORMAT")?;
+        override_with(&mut self.server.host, "HOST")?;
+        override_with(&mut self.server.port, "PORT")?;
+        if let Ok(origins) = env::var("ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = split_list(&origins);
+        }
+        if let Ok(methods) = env::var("CORS_ALLOWED_METHODS") {
+            self.cors.allowed_methods = split_list(&methods);
+        }
+        if let Ok(headers) = env::var("CORS_ALLOWED_HEADERS") {
+            self.cors.allowed_headers = split_list(&headers);
+        }
+        override_with(&mut self.cors.max_age_secs, "CORS_MAX_AGE_SECS")?;
+        override_with(&mut self.cors.dev_mode, "CORS_DEV_MODE")?;
+        override_with(&mut self.database.url, "DATABASE_URL")?;
+        override_with(&mut self.database.max_connections, "DATABASE_MAX_CONNECTIONS")?;
+        if let Ok(min_idle) = env::var("DATABASE_MIN_IDLE") {
+            self.database.min_idle = Some(min_idle.parse().map_err(|e| anyhow!("DATABASE_MIN_IDLE is invalid: {}", e))?);
+        }
+        override_with(&mut self.database.connect_timeout_secs, "DATABASE_CONNECT_TIMEOUT_SECS")?;
+        override_with(&mut self.github.api_url, "GITHUB_API_URL")?;
+        override_with(&mut self.github.mock_tokens, "GITHUB_MOCK_TOKENS")?;
+        override_with(&mut self.cache.token_ttl_secs, "TOKEN_CACHE_TTL_SECS")?;
+        override_with(&mut self.health.check_github, "HEALTH_CHECK_GITHUB")?;
+        override_with(&mut self.features.email, "FEATURE_EMAIL")?;
+        override_with(&mut self.features.webhooks, "FEATURE_WEBHOOKS")?;
+        override_with(&mut self.features.chat, "FEATURE_CHAT")?;
+        override_with(&mut self.features.api_docs, "FEATURE_API_DOCS")?;
+        override_with(&mut self.outbound.allow_private_networks, "OUTBOUND_ALLOW_PRIVATE_NETWORKS")?;
+        Ok(())
+    }
+
+    /// Every problem at once, so a broken deploy is fixed in one go
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if EnvFilter::try_new(&self.log_level).is_err() {
+            errors.push(format!("log_level: {} is not a valid filter", self.log_level));
+        }
+        if !telemetry::LOG_FORMATS.contains(&self.log_format.as_str()) {
+            errors.push(format!("log_format must be one of {}", telemetry::LOG_FORMATS.join(", ")));
+        }
+        if self.database.url.is_empty() {
+            errors.push("database.url (DATABASE_URL) must be set".to_string());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.min_idle.is_some_and(|m| m > self.database.max_connections) {
+            errors.push("database.min_idle must not exceed database.max_connections".to_string());
+        }
+        if self.server.host.trim().is_empty() {
+            errors.push("server.host must not be empty".to_string());
+        }
+        for origin in &self.cors.allowed_origins {
+            if let Err(e) = cors::check_origin_pattern(origin) {
+                errors.push(format!("cors.allowed_origins: {} {}", origin, e));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                errors.push(format!("cors.allowed_methods: {} is not an HTTP method", method));
+            }
+        }
+        for header in &self.cors.allowed_headers {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                errors.push(format!("cors.allowed_headers: {} is not a header name", header));
+            }
+        }
+        if !(self.github.api_url.starts_with("http://") || self.github.api_url.starts_with("https://")) {
+            errors.push("github.api_url must start with http:// or https://".to_string());
+        }
+        errors
+    }
+
+    /// Defaults, then the TOML file, then the environment
+    pub fn load() -> anyhow::Result<Config> {
+        dotenv().ok();
+        let path = env::var("HOTCHPOTCH_CONFIG").ok()
+            .or_else(|| Path::new(DEFAULT_FILE).exists().then(|| DEFAULT_FILE.to_string()));
+        let mut config = match &path {
+            Some(path) => {
+                let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+                Config::from_toml(&text).with_context(|| format!("Failed to parse {}", path))?
+            },
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        let errors = config.validate();
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid configuration:\n  {}", errors.join("\n  ")));
+        }
+        Ok(config)
+    }
+}
+
+/// Load the configuration for the process. Call once, before anything reads it.
+pub fn init() -> anyhow::Result<&'static Config> {
+    let config = Config::load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The loaded configuration. Loads it on first use where `init` wasn't
+/// called, as in tests.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load().unwrap_or_else(|e| panic!("{:#}", e)))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn file_overrides_defaults() {
+        let config = Config::from_toml(r#"
+            log_level = "info"
+
+            [server]
+            port = 9000
+
+            [cors]
+            allowed_origins = ["https://hotchpotch.example"]
+
+            [features]
+            chat = false
+        "#).unwrap();
+        assert_eq!(config.log_level, "info");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.cors.allowed_origins, vec!["https://hotchpotch.example"]);
+        assert!(!config.features.chat);
+        assert!(config.features.email);
+        assert!(!config.outbound.allow_private_networks);
+        assert!(Config::from_toml("[server]\nprot = 1").is_err());
+        assert!(Config::from_toml(include_str!("../hotchpotch.toml.example")).is_ok());
+    }
+
+    #[test]
+    fn reports_every_invalid_setting() {
+        let mut config = Config::from_toml(r#"
+            [cors]
+            allowed_origins = ["localhost:3000"]
+
+            [database]
+            max_connections = 2
+            min_idle = 5
+        "#).unwrap();
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("database.url"));
+        config.database.url = "postgres://localhost/hotchpotch".to_string();
+        config.database.min_idle = None;
+        config.cors.allowed_origins = vec![];
+        assert!(config.validate().is_empty());
+    }
+}
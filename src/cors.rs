@@ -0,0 +1,131 @@
+use actix_cors::Cors;
+
+use crate::config::CorsConfig;
+
+fn split_scheme(pattern: &str) -> Option<(&str, &str)> {
+    ["https://", "http://"].iter()
+        .find(|scheme| pattern.starts_with(*scheme))
+        .map(|scheme| pattern.split_at(scheme.len()))
+}
+
+/// Why `pattern` can't be used as an allowed origin, if it can't
+pub fn check_origin_pattern(pattern: &str) -> Result<(), String> {
+    let (_, host) = match split_scheme(pattern) {
+        Some(parts) => parts,
+        None => return Err("must start with http:// or https://".to_string()),
+    };
+    if host.is_empty() || host.contains('/') {
+        return Err("must be a scheme and host without a path".to_string());
+    }
+    if host.contains('*') && !(host.starts_with("*.") && host.matches('*').count() == 1) {
+        return Err("may only use * as the whole first label, as in https://*.example.com".to_string());
+    }
+    Ok(())
+}
+
+/// Whether `origin` is allowed by `pattern`. `https://*.example.com` takes
+/// any subdomain of example.com over https, but not example.com itself.
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let (scheme, host) = match split_scheme(pattern) {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let suffix = match host.strip_prefix('*') {
+        Some(suffix) => suffix,
+        None => return pattern.eq_ignore_ascii_case(origin),
+    };
+    let origin = origin.to_ascii_lowercase();
+    let subdomain = match origin.strip_prefix(scheme).and_then(|o| o.strip_suffix(&suffix.to_ascii_lowercase())) {
+        Some(s) => s,
+        None => return false,
+    };
+    !subdomain.is_empty()
+        && !subdomain.starts_with('.')
+        && !subdomain.ends_with('.')
+        && subdomain.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// CORS middleware for the configured policy
+pub fn build(config: &CorsConfig) -> Cors {
+    let cors = if config.dev_mode {
+        Cors::default().allowed_origin_fn(|_, _| true)
+    } else {
+        let patterns = config.allowed_origins.clone();
+        Cors::default().allowed_origin_fn(move |origin, _| {
+            origin.to_str().is_ok_and(|o| patterns.iter().any(|p| origin_matches(p, o)))
+        })
+    };
+    cors.allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .supports_credentials()
+        .max_age(config.max_age_secs)
+}
+
+#[cfg(test)]
+mod cors_tests {
+    use super::*;
+    use actix_web::http::{header, Method, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    fn config() -> CorsConfig {
+        CorsConfig{
+            allowed_origins: vec!["https://hotchpotch.example".to_string(), "https://*.staging.example".to_string()],
+            ..CorsConfig::default()
+        }
+    }
+
+    #[test]
+    fn matches_exact_and_wildcard_origins() {
+        assert!(origin_matches("https://hotchpotch.example", "https://hotchpotch.example"));
+        assert!(!origin_matches("https://hotchpotch.example", "http://hotchpotch.example"));
+        assert!(origin_matches("https://*.staging.example", "https://pr-12.staging.example"));
+        assert!(origin_matches("https://*.staging.example", "https://a.b.staging.example"));
+        assert!(!origin_matches("https://*.staging.example", "https://staging.example"));
+        assert!(!origin_matches("https://*.staging.example", "https://evil.example/.staging.example"));
+        assert!(!origin_matches("https://*.staging.example", "https://x.staging.example.evil"));
+        assert!(check_origin_pattern("https://*.staging.example").is_ok());
+        assert!(check_origin_pattern("https://pr-*.staging.example").is_err());
+        assert!(check_origin_pattern("localhost:3000").is_err());
+    }
+
+    async fn preflight(config: &CorsConfig, origin: &str, method: &str) -> actix_web::dev::ServiceResponse {
+        let app = init_service(
+            App::new()
+                .wrap(build(config))
+                .route("/api/v1/users/me", web::patch().to(HttpResponse::Ok))
+        ).await;
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/v1/users/me")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization, content-type"))
+            .to_request();
+        call_service(&app, req).await.map_into_boxed_body()
+    }
+
+    #[actix_web::test]
+    async fn preflight_allows_configured_origins() {
+        for origin in ["https://hotchpotch.example", "https://pr-12.staging.example"] {
+            let res = preflight(&config(), origin, "PATCH").await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), origin);
+            assert_eq!(res.headers().get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "3600");
+        }
+    }
+
+    #[actix_web::test]
+    async fn preflight_rejects_other_origins_and_methods() {
+        let res = preflight(&config(), "https://evil.example", "PATCH").await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        let res = preflight(&config(), "https://hotchpotch.example", "TRACE").await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        // Dev mode lets a local frontend on any port through
+        let dev = CorsConfig{dev_mode: true, ..config()};
+        let res = preflight(&dev, "http://localhost:5173", "PATCH").await;
+        assert_eq!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "http://localhost:5173");
+    }
+}
@@ -1,29 +1,107 @@
 use anyhow::{anyhow, Context};
-use diesel::dsl::any;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::dsl::{now, sql, IntervalDsl};
 use diesel::insert_into;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::sql_types::Bool;
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use std::collections::HashMap;
+use std::fmt;
 use uuid::Uuid;
-use uuid::uuid;
 
+use crate::bus::{self, NoticeKind};
+use crate::config;
+use crate::chat::{self, ChatMessage, Outgoing, Platform};
 use crate::db::establish_connection;
-use crate::models::{self, Event, Join, Request, User, Solo, Team, NewEvent, NewJoin, NewRequest, NewUser, NewSolo, NewTeam};
+use crate::formation::PlannedTeam;
+use crate::mail::{self, MailContext};
+use crate::webhook::{self, WebhookEvent};
+use crate::models::{
+    EmailPreference, EventIntegration, QueuedEmail, WebhookDelivery, WebhookSubscription,
+    Event, Notification, Request, Skill, User, UserDetail, UserSkillDetail, Team, TeamDetail, WantedRole,
+    NewEmailPreference, NewEventIntegration, NewQueuedEmail, NewWebhookDelivery, NewWebhookSubscription,
+    NewEvent, NewEventOrganizer, NewJoin, NewNotification, NewRequest, NewSkill, NewUser, NewUserSkill, NewSolo, NewTeam, NewWantedRole,
+    UpdateUser,
+};
+use crate::pagination::{Order, Page, PageParams, SortKey};
 use crate::schema::*;
+use crate::validation::FieldErrors;
+
+#[derive(Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EventStatus {
+    Upcoming,
+    Ongoing,
+    Finished,
+}
+
+#[derive(Default, Debug)]
+pub struct EventFilter {
+    pub q: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub status: Option<EventStatus>,
+    pub has_open_teams: Option<bool>,
+}
+
+/// A failure caused by the request rather than the server. Handlers find it
+/// by downcasting and answer 404, 409 or 422 with the message.
+#[derive(Debug)]
+pub enum CrudError {
+    NotFound(String),
+    Conflict(String),
+    // Checks that need the stored rows, reported like the body validation
+    Invalid(FieldErrors),
+}
+
+impl fmt::Display for CrudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrudError::NotFound(message) | CrudError::Conflict(message) => write!(f, "{}", message),
+            CrudError::Invalid(errors) => write!(f, "Invalid fields: {:?}", errors.errors),
+        }
+    }
+}
+
+impl std::error::Error for CrudError {}
 
 // Create
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_event (
     name: &String,
     desc: &String,
-    url:  &String
-) -> anyhow::Result<()> {
+    url:  &String,
+    started_at: Option<&NaiveDateTime>,
+    ended_at: Option<&NaiveDateTime>,
+    organizer_id: &String
+) -> anyhow::Result<Event> {
+    let binding = conv_string_to_uuid(organizer_id);
+    let organizer_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
     let conn = &mut establish_connection()?;
-    let new_event = NewEvent{name, desc, url};
-    insert_into(events::dsl::events)
-        .values(&new_event)
-        .execute(conn)
-        .with_context(|| "Failed to insert new_event")?;
-    Ok(())
+    let new_event = NewEvent{name, desc, url, started_at, ended_at};
+    conn.transaction(|conn| {
+        let event = insert_into(events::dsl::events)
+            .values(&new_event)
+            .get_result::<Event>(conn)
+            .with_context(|| "Failed to insert new_event")?;
+        // The creator organizes the event
+        let new_organizer = NewEventOrganizer{event_id: &event.id, user_id: organizer_id};
+        insert_into(event_organizers::dsl::event_organizers)
+            .values(&new_organizer)
+            .execute(conn)
+            .with_context(|| "Failed to insert new_organizer")?;
+        Ok(event)
+    })
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_join (
     team_id: &String,
     user_id: &String
@@ -40,13 +118,24 @@ pub fn create_join (
     };
     let conn = &mut establish_connection()?;
     let new_join = NewJoin{team_id, user_id};
-    insert_into(joins::dsl::joins)
-        .values(&new_join)
-        .execute(conn)
-        .with_context(|| "Failed to insert new_join")?;
+    let (notifications, outgoing) = conn.transaction(|conn| {
+        ensure_team_has_room(conn, team_id)?;
+        insert_into(joins::dsl::joins)
+            .values(&new_join)
+            .execute(conn)
+            .with_context(|| "Failed to insert new_join")?;
+        fill_wanted_role(conn, team_id, user_id)?;
+        queue_team_webhooks(conn, team_id, WebhookEvent::JoinCreated, json!({"team_id": team_id, "user_id": user_id}))?;
+        let members = others(get_team_member_ids(conn, team_id)?, user_id);
+        let notifications = notify(conn, &members, NoticeKind::MemberJoined, team_id, user_id)?;
+        Ok::<_, anyhow::Error>((notifications, chat_if_team_full(conn, team_id)?))
+    })?;
+    publish_all(notifications);
+    chat::send_all(outgoing);
     Ok(())
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_request (
     team_id: &String,
     user_id: &String,
@@ -65,27 +154,70 @@ pub fn create_request (
   
     let conn = &mut establish_connection()?;
     let new_request = NewRequest{team_id, user_id, message};
-    insert_into(requests::dsl::requests)
-        .values(&new_request)
-        .execute(conn)
-        .with_context(|| "Failed to insert new_request")?;
+    let notifications = conn.transaction(|conn| {
+        // Locking the team keeps it from being deleted under the new request
+        let leader_id: Uuid = match teams::dsl::teams
+            .find(team_id)
+            .select(teams::dsl::reader_id)
+            .for_update()
+            .first(conn)
+            .optional()
+            .with_context(|| "Failed to load team")? {
+            Some(id) => id,
+            None => return Err(CrudError::NotFound("No such team".to_string()).into()),
+        };
+        // A declined applicant, or a member who has since left, may ask again;
+        // a pending request or a current membership stands
+        let status: Option<String> = requests::dsl::requests
+            .find((team_id, user_id))
+            .select(requests::dsl::status)
+            .for_update()
+            .first(conn)
+            .optional()
+            .with_context(|| "Failed to get request")?;
+        let joined: bool = diesel::select(diesel::dsl::exists(joins::dsl::joins
+                .filter(joins::dsl::team_id.eq(team_id))
+                .filter(joins::dsl::user_id.eq(user_id))))
+            .get_result(conn)
+            .with_context(|| "Failed to get join")?;
+        match status.as_deref() {
+            None => {
+                insert_into(requests::dsl::requests)
+                    .values(&new_request)
+                    .execute(conn)
+                    .with_context(|| "Failed to insert new_request")?;
+            },
+            Some(status) if status == "declined" || (status == "accepted" && !joined) => {
+                diesel::update(requests::dsl::requests.find((team_id, user_id)))
+                    .set((requests::dsl::status.eq("pending"), requests::dsl::message.eq(message)))
+                    .execute(conn)
+                    .with_context(|| "Failed to renew request")?;
+            },
+            Some(_) => return Err(CrudError::Conflict("You already asked to join this team".to_string()).into()),
+        }
+        let data = json!({"team_id": team_id, "user_id": user_id, "message": message});
+        queue_team_webhooks(conn, team_id, WebhookEvent::RequestCreated, data)?;
+        notify(conn, &[leader_id], NoticeKind::RequestCreated, team_id, user_id)
+    })?;
+    publish_all(notifications);
     Ok(())
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_user (
     name: &String,
     icon_url: &String,
     profile: &String,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<User> {
     let conn = &mut establish_connection()?;
     let new_user = NewUser{name, icon_url, profile};
     insert_into(users::dsl::users)
         .values(&new_user)
-        .execute(conn)
-        .with_context(|| "Failed to insert new_user")?;
-    Ok(())
+        .get_result::<User>(conn)
+        .with_context(|| "Failed to insert new_user")
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_solo (
     event_id: &String,
     user_id: &String
@@ -102,20 +234,33 @@ pub fn create_solo (
     };
     let conn = &mut establish_connection()?;
     let new_solo = NewSolo{event_id, user_id};
-    insert_into(solos::dsl::solos)
-
-        .values(&new_solo)
-        .execute(conn)
-        .with_context(|| "Failed to insert new_solo")?;
+    let outgoing = conn.transaction(|conn| {
+        insert_into(solos::dsl::solos)
+            .values(&new_solo)
+            .execute(conn)
+            .with_context(|| "Failed to insert new_solo")?;
+        queue_webhooks(conn, event_id, WebhookEvent::SoloCreated, json!({"event_id": event_id, "user_id": user_id}))?;
+        chat_for_event(conn, event_id, |conn, event_name| {
+            let count: i64 = solos::dsl::solos
+                .filter(solos::dsl::event_id.eq(event_id))
+                .count()
+                .get_result(conn)
+                .with_context(|| "Failed to count solos")?;
+            Ok(ChatMessage::SolosLooking{event_name, count})
+        })
+    })?;
+    chat::send_all(outgoing);
     Ok(())
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn create_team (
     event_id: &String,
     reader_id: &String,
     name: &String,
-    desc: &String
-) -> anyhow::Result<()> {
+    desc: &String,
+    capacity: Option<&i32>
+) -> anyhow::Result<Team> {
     let binding = conv_string_to_uuid(event_id);
     let event_id = match &binding {
         Ok(u) => u,
@@ -127,130 +272,1466 @@ pub fn create_team (
         Err(e) => return Err(anyhow!("{}", e)),
     };
     let conn = &mut establish_connection()?;
-    let new_team = NewTeam{event_id, reader_id, name, desc};
-    insert_into(teams::dsl::teams)
-        .values(&new_team)
+    let new_team = NewTeam{event_id, reader_id, name, desc, capacity};
+    let (team, outgoing) = conn.transaction(|conn| {
+        let team = insert_into(teams::dsl::teams)
+            .values(&new_team)
+            .get_result::<Team>(conn)
+            .with_context(|| "Failed to insert new_team")?;
+        queue_webhooks(conn, event_id, WebhookEvent::TeamCreated, serde_json::to_value(&team)?)?;
+        chat_for_event(conn, event_id, |_, event_name| {
+            Ok(ChatMessage::TeamRecruiting{
+                event_name,
+                team_name: team.name.clone(),
+                desc: team.desc.clone(),
+                size: 1,
+                capacity: team.capacity,
+            })
+        })
+        .map(|outgoing| (team, outgoing))
+    })?;
+    chat::send_all(outgoing);
+    Ok(team)
+}
+
+// Turn a formation plan into teams in one go: each planned team is
+// created with its leader, the other members join it and everyone
+// leaves the event's solos.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn create_planned_teams (
+    event_id: &String,
+    planned: &[PlannedTeam],
+    team_size: &i32
+) -> anyhow::Result<Vec<Team>> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let (created, outgoing) = conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let mut created = vec![];
+        let mut outgoing = vec![];
+        for p in planned {
+            let desc = String::new();
+            let capacity = (*team_size).max(p.members.len() as i32);
+            let new_team = NewTeam{
+                event_id,
+                reader_id: &p.leader_id,
+                name: &p.name,
+                desc: &desc,
+                capacity: Some(&capacity),
+            };
+            let team = insert_into(teams::dsl::teams)
+                .values(&new_team)
+                .get_result::<Team>(conn)
+                .with_context(|| "Failed to insert planned team")?;
+            queue_webhooks(conn, event_id, WebhookEvent::TeamCreated, serde_json::to_value(&team)?)?;
+            for m in p.members.iter().filter(|m| m.user.id != p.leader_id) {
+                let new_join = NewJoin{team_id: &team.id, user_id: &m.user.id};
+                insert_into(joins::dsl::joins)
+                    .values(&new_join)
+                    .execute(conn)
+                    .with_context(|| "Failed to insert planned join")?;
+                fill_wanted_role(conn, &team.id, &m.user.id)?;
+                queue_webhooks(conn, event_id, WebhookEvent::JoinCreated, json!({"team_id": team.id, "user_id": m.user.id}))?;
+            }
+            let member_ids: Vec<Uuid> = p.members.iter().map(|m| m.user.id).collect();
+            let removed = diesel::delete(solos::dsl::solos
+                    .filter(solos::dsl::event_id.eq(event_id))
+                    .filter(solos::dsl::user_id.eq_any(&member_ids)))
+                .execute(conn)
+                .with_context(|| "Failed to delete planned solos")?;
+            if removed != member_ids.len() {
+                return Err(CrudError::Conflict("Solos changed since the preview; preview again".to_string()).into());
+            }
+            // Announced like a team made by hand, or as full when the plan filled it
+            if p.members.len() < team.capacity as usize {
+                outgoing.extend(chat_for_event(conn, event_id, |_, event_name| {
+                    Ok(ChatMessage::TeamRecruiting{
+                        event_name,
+                        team_name: team.name.clone(),
+                        desc: team.desc.clone(),
+                        size: p.members.len(),
+                        capacity: team.capacity,
+                    })
+                })?);
+            } else {
+                outgoing.extend(chat_if_team_full(conn, &team.id)?);
+            }
+            created.push(team);
+        }
+        Ok((created, outgoing))
+    })?;
+    chat::send_all(outgoing);
+    Ok(created)
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn create_webhook_subscription (
+    event_id: &String,
+    url: &String,
+    secret: &String,
+    event_types: &Vec<Option<String>>
+) -> anyhow::Result<WebhookSubscription> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let new_subscription = NewWebhookSubscription{event_id, url, secret, event_types};
+    insert_into(webhook_subscriptions::dsl::webhook_subscriptions)
+        .values(&new_subscription)
+        .get_result::<WebhookSubscription>(conn)
+        .with_context(|| "Failed to insert new_subscription")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn create_skill (
+    name: &String,
+    kind: &String,
+    category: Option<&String>
+) -> anyhow::Result<Skill> {
+    let conn = &mut establish_connection()?;
+    let new_skill = NewSkill{name, kind, category};
+    // In a transaction of its own so a violation doesn't spoil the caller's
+    let inserted = conn.transaction(|conn| {
+        insert_into(skills::dsl::skills)
+            .values(&new_skill)
+            .get_result::<Skill>(conn)
+    });
+    match inserted {
+        Ok(skill) => Ok(skill),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            Err(CrudError::Conflict(format!("The skill {} already exists", name)).into())
+        },
+        Err(e) => Err(anyhow::Error::new(e).context("Failed to insert new_skill")),
+    }
+}
+
+// Returns false when the user already organizes the event
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn add_event_organizer(event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let new_organizer = NewEventOrganizer{event_id, user_id};
+    let inserted = insert_into(event_organizers::dsl::event_organizers)
+        .values(&new_organizer)
+        .on_conflict_do_nothing()
         .execute(conn)
-        .with_context(|| "Failed to insert new_team")?;
-  
-    Ok(())
+        .with_context(|| "Failed to insert new_organizer")?;
+    Ok(inserted > 0)
 }
 
 // Read
 
-pub fn get_event_list() -> anyhow::Result<Vec<Event>> {
-    use crate::schema::events::dsl::events;
-    let conn = &mut establish_connection().unwrap();
-    match events.select(Event::as_select()).load(conn) {
-        Ok(v) => return Ok(v),
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_list(filter: &EventFilter, page: &PageParams) -> anyhow::Result<Page<Event>> {
+    use crate::schema::events::dsl::*;
+    let conn = &mut establish_connection()?;
+    let total: i64 = match filter_events(filter).count().get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let query = filter_events(filter);
+    let query = match (page.sort, page.order) {
+        (SortKey::CreatedAt, Order::Asc) => query.order((created_at.asc(), id.asc())),
+        (SortKey::CreatedAt, Order::Desc) => query.order((created_at.desc(), id.desc())),
+        (SortKey::Name, Order::Asc) => query.order((name.asc(), id.asc())),
+        (SortKey::Name, Order::Desc) => query.order((name.desc(), id.desc())),
+    };
+    match query
+        .offset(page.offset)
+        .limit(page.limit)
+        .select(Event::as_select())
+        .load(conn) {
+        Ok(v) => return Ok(Page::new(v, total, page)),
         Err(e) => return Err(anyhow!("{}", e)),
     }
 }
-pub fn get_requests_from_user_id(user_id: &String) -> anyhow::Result<Vec<Request>> {
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_requests_from_user_id(user_id: &String, page: &PageParams) -> anyhow::Result<Page<Request>> {
     let conn = &mut establish_connection()?;
     let user_id: Uuid = match Uuid::parse_str(user_id) {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
+    let total: i64 = match requests::dsl::requests
+        .filter(requests::dsl::user_id.eq(user_id))
+        .count()
+        .get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    // Requests have no name, so they can only be ordered by creation time
+    let query = match page.order {
+        Order::Asc => requests::dsl::requests
+            .order((requests::dsl::created_at.asc(), requests::dsl::team_id.asc()))
+            .into_boxed(),
+        Order::Desc => requests::dsl::requests
+            .order((requests::dsl::created_at.desc(), requests::dsl::team_id.desc()))
+            .into_boxed(),
+    };
+    match query
+        .filter(requests::dsl::user_id.eq(user_id))
+        .offset(page.offset)
+        .limit(page.limit)
+        .load::<Request>(conn) {
+        Ok(r) => return Ok(Page::new(r, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_wanna_join_users_by_event_id(event_id: &String, page: &PageParams) -> anyhow::Result<Page<UserDetail>> {
+    let conn = &mut establish_connection()?;
+    let event_id: Uuid = match Uuid::parse_str(event_id) {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let total: i64 = match solos::dsl::solos
+        .filter(solos::dsl::event_id.eq(event_id))
+        .count()
+        .get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    // created_at is the time the user registered as solo, not the account creation time
+    let query = solos::table.inner_join(users::table).into_boxed();
+    let query = match (page.sort, page.order) {
+        (SortKey::CreatedAt, Order::Asc) => query.order((solos::dsl::created_at.asc(), users::dsl::id.asc())),
+        (SortKey::CreatedAt, Order::Desc) => query.order((solos::dsl::created_at.desc(), users::dsl::id.desc())),
+        (SortKey::Name, Order::Asc) => query.order((users::dsl::name.asc(), users::dsl::id.asc())),
+        (SortKey::Name, Order::Desc) => query.order((users::dsl::name.desc(), users::dsl::id.desc())),
+    };
+    match query
+        .filter(solos::dsl::event_id.eq(event_id))
+        .offset(page.offset)
+        .limit(page.limit)
+        .select(User::as_select())
+        .load::<User>(conn) {
+        Ok(v) => return Ok(Page::new(attach_skills(conn, v)?, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
 
-    match requests::dsl::requests.filter(requests::dsl::user_id.eq(user_id)).load::<Request>(conn) {
-        Ok(r) => return Ok(r),
+// Every solo user of an event, for matchmaking
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_solo_details(event_id: &String) -> anyhow::Result<Vec<UserDetail>> {
+    let conn = &mut establish_connection()?;
+    let event_id: Uuid = match Uuid::parse_str(event_id) {
+        Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-} 
+    match solos::table
+        .inner_join(users::table)
+        .filter(solos::dsl::event_id.eq(event_id))
+        .order(users::dsl::id.asc())
+        .select(User::as_select())
+        .load::<User>(conn) {
+        Ok(v) => return attach_skills(conn, v),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
 
-pub fn get_wanna_join_users_by_event_id(event_id: &String) -> anyhow::Result<Vec<User>> {
+// Every team of an event with its members (leader included), for matchmaking
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_teams_with_members(event_id: &String) -> anyhow::Result<Vec<(TeamDetail, Vec<UserDetail>)>> {
     let conn = &mut establish_connection()?;
     let event_id: Uuid = match Uuid::parse_str(event_id) {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-    let solo_vec: Vec<Solo> = match solos::dsl::solos
-            .filter(solos::dsl::event_id.eq(event_id))
-            .load::<Solo>(conn) {
-        Ok(r) => r,
+    let team_vec: Vec<Team> = match teams::dsl::teams
+        .filter(teams::dsl::event_id.eq(event_id))
+        .order(teams::dsl::id.asc())
+        .load::<Team>(conn) {
+        Ok(v) => v,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let team_ids: Vec<Uuid> = team_vec.iter().map(|t| t.id).collect();
+    let join_vec: Vec<(Uuid, Uuid)> = match joins::dsl::joins
+        .filter(joins::dsl::team_id.eq_any(&team_ids))
+        .select((joins::dsl::team_id, joins::dsl::user_id))
+        .load(conn) {
+        Ok(v) => v,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let mut member_ids: Vec<Uuid> = team_vec.iter().map(|t| t.reader_id).collect();
+    member_ids.extend(join_vec.iter().map(|(_, user_id)| *user_id));
+    let member_vec: Vec<User> = match users::dsl::users
+        .filter(users::dsl::id.eq_any(&member_ids))
+        .load::<User>(conn) {
+        Ok(v) => v,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let members: HashMap<Uuid, UserDetail> = attach_skills(conn, member_vec)?
+        .into_iter()
+        .map(|u| (u.user.id, u))
+        .collect();
+    let mut result = vec![];
+    for team in attach_wanted_roles(conn, team_vec)? {
+        let mut team_members: Vec<UserDetail> = members.get(&team.team.reader_id).cloned().into_iter().collect();
+        for (team_id, user_id) in &join_vec {
+            if *team_id == team.team.id {
+                if let Some(m) = members.get(user_id) {
+                    team_members.push(m.clone());
+                }
+            }
+        }
+        result.push((team, team_members));
+    }
+    Ok(result)
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_notifications_by_user_id(user_id: &Uuid, unread_only: bool, page: &PageParams) -> anyhow::Result<Page<Notification>> {
+    let conn = &mut establish_connection()?;
+    let filtered = || {
+        let mut query = notifications::dsl::notifications
+            .filter(notifications::dsl::user_id.eq(*user_id))
+            .into_boxed();
+        if unread_only {
+            query = query.filter(notifications::dsl::read_at.is_null());
+        }
+        query
+    };
+    let total: i64 = match filtered().count().get_result(conn) {
+        Ok(c) => c,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-    let mut result: Vec<User> = vec![];
-    // Unpack vector
-    for s in solo_vec {
-        result.push(match search_user_by_id(s.user_id){
-            Ok(u) => u,
-            Err(e) => return Err(anyhow!("{}", e)),
-        });
+    // Notifications have no name, so they can only be ordered by creation time
+    let query = match page.order {
+        Order::Asc => filtered()
+            .order((notifications::dsl::created_at.asc(), notifications::dsl::id.asc())),
+        Order::Desc => filtered()
+            .order((notifications::dsl::created_at.desc(), notifications::dsl::id.desc())),
+    };
+    match query
+        .offset(page.offset)
+        .limit(page.limit)
+        .load::<Notification>(conn) {
+        Ok(v) => return Ok(Page::new(v, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
     }
+}
 
-    return Ok(result);
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn count_unread_notifications(user_id: &Uuid) -> anyhow::Result<i64> {
+    let conn = &mut establish_connection()?;
+    match notifications::dsl::notifications
+        .filter(notifications::dsl::user_id.eq(user_id))
+        .filter(notifications::dsl::read_at.is_null())
+        .count()
+        .get_result(conn) {
+        Ok(c) => return Ok(c),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
 
-} 
+// Sizes of the main tables, as reported on /metrics
+pub struct Totals {
+    pub events: i64,
+    pub teams: i64,
+    pub solos: i64,
+    pub pending_requests: i64,
+}
 
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn count_totals() -> anyhow::Result<Totals> {
+    let conn = &mut establish_connection()?;
+    let events = events::dsl::events.count().get_result(conn)
+        .with_context(|| "Failed to count events")?;
+    let teams = teams::dsl::teams.count().get_result(conn)
+        .with_context(|| "Failed to count teams")?;
+    let solos = solos::dsl::solos.count().get_result(conn)
+        .with_context(|| "Failed to count solos")?;
+    let pending_requests = requests::dsl::requests
+        .filter(requests::dsl::status.eq("pending"))
+        .count()
+        .get_result(conn)
+        .with_context(|| "Failed to count requests")?;
+    Ok(Totals{events, teams, solos, pending_requests})
+}
 
-pub fn get_user_info_by_name(user_name: &String) -> anyhow::Result<User> {
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_email_preference(user_id: &Uuid) -> anyhow::Result<Option<EmailPreference>> {
+    let conn = &mut establish_connection()?;
+    email_preferences::dsl::email_preferences
+        .find(user_id)
+        .first::<EmailPreference>(conn)
+        .optional()
+        .with_context(|| "Failed to get email preference")
+}
+
+// Queued emails whose next attempt is due, oldest first
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_due_emails(limit: i64) -> anyhow::Result<Vec<QueuedEmail>> {
+    let conn = &mut establish_connection()?;
+    email_queue::dsl::email_queue
+        .filter(email_queue::dsl::sent_at.is_null())
+        .filter(email_queue::dsl::attempts.lt(mail::MAX_ATTEMPTS))
+        .filter(email_queue::dsl::next_attempt_at.le(now))
+        .order(email_queue::dsl::next_attempt_at.asc())
+        .limit(limit)
+        .load::<QueuedEmail>(conn)
+        .with_context(|| "Failed to get due emails")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_integration(event_id: &String) -> anyhow::Result<Option<EventIntegration>> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
     let conn = &mut establish_connection()?;
+    event_integrations::dsl::event_integrations
+        .find(event_id)
+        .first::<EventIntegration>(conn)
+        .optional()
+        .with_context(|| "Failed to get event integration")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_webhook_subscriptions_by_event_id(event_id: &String) -> anyhow::Result<Vec<WebhookSubscription>> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    webhook_subscriptions::dsl::webhook_subscriptions
+        .filter(webhook_subscriptions::dsl::event_id.eq(event_id))
+        .order(webhook_subscriptions::dsl::created_at.asc())
+        .load::<WebhookSubscription>(conn)
+        .with_context(|| "Failed to get webhook subscriptions")
+}
+
+// A subscription only counts as found through the event it belongs to
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_webhook_subscription(event_id: &String, webhook_id: &String) -> anyhow::Result<Option<WebhookSubscription>> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(webhook_id);
+    let webhook_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    webhook_subscriptions::dsl::webhook_subscriptions
+        .filter(webhook_subscriptions::dsl::id.eq(webhook_id))
+        .filter(webhook_subscriptions::dsl::event_id.eq(event_id))
+        .first::<WebhookSubscription>(conn)
+        .optional()
+        .with_context(|| "Failed to get webhook subscription")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_webhook_deliveries(webhook_id: &Uuid, page: &PageParams) -> anyhow::Result<Page<WebhookDelivery>> {
+    let conn = &mut establish_connection()?;
+    let total: i64 = match webhook_deliveries::dsl::webhook_deliveries
+        .filter(webhook_deliveries::dsl::subscription_id.eq(webhook_id))
+        .count()
+        .get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let query = webhook_deliveries::dsl::webhook_deliveries
+        .filter(webhook_deliveries::dsl::subscription_id.eq(webhook_id))
+        .into_boxed();
+    let query = match page.order {
+        Order::Asc => query
+            .order((webhook_deliveries::dsl::created_at.asc(), webhook_deliveries::dsl::id.asc())),
+        Order::Desc => query
+            .order((webhook_deliveries::dsl::created_at.desc(), webhook_deliveries::dsl::id.desc())),
+    };
+    match query
+        .offset(page.offset)
+        .limit(page.limit)
+        .load::<WebhookDelivery>(conn) {
+        Ok(v) => return Ok(Page::new(v, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+// Deliveries whose next attempt is due, with the subscription to send them to
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_due_webhook_deliveries(limit: i64) -> anyhow::Result<Vec<(WebhookDelivery, WebhookSubscription)>> {
+    let conn = &mut establish_connection()?;
+    webhook_deliveries::table
+        .inner_join(webhook_subscriptions::table)
+        .filter(webhook_deliveries::dsl::delivered_at.is_null())
+        .filter(webhook_deliveries::dsl::attempts.lt(webhook::MAX_ATTEMPTS))
+        .filter(webhook_deliveries::dsl::next_attempt_at.le(now))
+        .order(webhook_deliveries::dsl::next_attempt_at.asc())
+        .limit(limit)
+        .select((WebhookDelivery::as_select(), WebhookSubscription::as_select()))
+        .load::<(WebhookDelivery, WebhookSubscription)>(conn)
+        .with_context(|| "Failed to get due webhook deliveries")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_skill_list(page: &PageParams) -> anyhow::Result<Page<Skill>> {
+    use crate::schema::skills::dsl::*;
+    let conn = &mut establish_connection()?;
+    let total: i64 = match skills.count().get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let query = match (page.sort, page.order) {
+        (SortKey::Name, Order::Asc) => skills.order((name.asc(), id.asc())).into_boxed(),
+        (SortKey::Name, Order::Desc) => skills.order((name.desc(), id.desc())).into_boxed(),
+        (SortKey::CreatedAt, Order::Asc) => skills.order((created_at.asc(), id.asc())).into_boxed(),
+        (SortKey::CreatedAt, Order::Desc) => skills.order((created_at.desc(), id.desc())).into_boxed(),
+    };
+    match query
+        .offset(page.offset)
+        .limit(page.limit)
+        .load::<Skill>(conn) {
+        Ok(v) => return Ok(Page::new(v, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_user_detail(user: User) -> anyhow::Result<UserDetail> {
+    let conn = &mut establish_connection()?;
+    match attach_skills(conn, vec![user])?.pop() {
+        Some(u) => return Ok(u),
+        None => return Err(anyhow!("User disappeared while loading skills")),
+    }
+}
+
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn is_event_organizer(event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+    let conn = &mut establish_connection()?;
+    let event_id: Uuid = match Uuid::parse_str(event_id) {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    match diesel::select(diesel::dsl::exists(event_organizers::dsl::event_organizers
+            .filter(event_organizers::dsl::event_id.eq(event_id))
+            .filter(event_organizers::dsl::user_id.eq(user_id))))
+        .get_result::<bool>(conn) {
+        Ok(b) => return Ok(b),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_user_info_by_name(user_name: &String) -> anyhow::Result<User> {
     match search_user_by_name(user_name) {
         Ok(u) => return Ok(u),
         Err(e) => Err(anyhow!("{}", e)),
     }
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn get_user_info_by_id(user_id: &String) -> anyhow::Result<User> {
+    // A malformed id can't name a user either
+    let user_id = match conv_string_to_uuid(user_id) {
+        Ok(u) => u,
+        Err(_) => return Err(CrudError::NotFound("No such user".to_string()).into()),
+    };
     let conn = &mut establish_connection()?;
-    let binding = conv_string_to_uuid(user_id);
-    let user_id = match binding {
+    match users::dsl::users.find(user_id).first::<User>(conn).optional() {
+        Ok(Some(u)) => return Ok(u),
+        Ok(None) => return Err(CrudError::NotFound("No such user".to_string()).into()),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_info_by_id(event_id: &String) -> anyhow::Result<Event> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match binding {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-    match search_user_by_id(user_id) {
-        Ok(u) => return Ok(u),
+    match search_event_by_id(event_id) {
+        Ok(v) => return Ok(v),
         Err(e) => Err(anyhow!("{}", e)),
     }
 }
 
-pub fn get_team_info_by_id(team_id: &String) -> anyhow::Result<Team> {
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_organizers(event_id: &String) -> anyhow::Result<Vec<User>> {
     let conn = &mut establish_connection()?;
-    let binding = conv_string_to_uuid(team_id);
-    let team_id = match binding {
+    let event_id: Uuid = match Uuid::parse_str(event_id) {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-    match search_team_by_id(team_id) {
-        Ok(u) => return Ok(u),
-        Err(e) => Err(anyhow!("{}", e)),
+    match event_organizers::table
+        .inner_join(users::table)
+        .filter(event_organizers::dsl::event_id.eq(event_id))
+        .order(event_organizers::dsl::created_at.asc())
+        .select(User::as_select())
+        .load::<User>(conn) {
+        Ok(v) => return Ok(v),
+        Err(e) => return Err(anyhow!("{}", e)),
     }
 }
 
-pub fn get_wanna_join_teams_by_event_id(event_id: &String) -> anyhow::Result<Vec<Team>> {
+// Every request sent to a team of the event, whatever its status
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_event_requests(event_id: &String) -> anyhow::Result<Vec<Request>> {
     let conn = &mut establish_connection()?;
     let event_id: Uuid = match Uuid::parse_str(event_id) {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
-    let team_vec: Vec<Team> = match teams::dsl::teams
-            .filter(teams::dsl::event_id.eq(event_id))
-            .load::<Team>(conn) {
+    match requests::table
+        .inner_join(teams::table)
+        .filter(teams::dsl::event_id.eq(event_id))
+        .order((requests::dsl::created_at.asc(), requests::dsl::team_id.asc()))
+        .select(Request::as_select())
+        .load::<Request>(conn) {
         Ok(v) => return Ok(v),
         Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_team_detail(team: Team) -> anyhow::Result<TeamDetail> {
+    let conn = &mut establish_connection()?;
+    match attach_wanted_roles(conn, vec![team])?.pop() {
+        Some(t) => return Ok(t),
+        None => return Err(anyhow!("Team disappeared while loading wanted roles")),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_team_info_by_id(team_id: &String) -> anyhow::Result<Team> {
+    // A malformed id can't name a team either
+    let team_id = match conv_string_to_uuid(team_id) {
+        Ok(u) => u,
+        Err(_) => return Err(CrudError::NotFound("No such team".to_string()).into()),
+    };
+    let conn = &mut establish_connection()?;
+    match teams::dsl::teams.find(team_id).first::<Team>(conn).optional() {
+        Ok(Some(t)) => return Ok(t),
+        Ok(None) => return Err(CrudError::NotFound("No such team".to_string()).into()),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn get_wanna_join_teams_by_event_id(event_id: &String, page: &PageParams) -> anyhow::Result<Page<TeamDetail>> {
+    let conn = &mut establish_connection()?;
+    let event_id: Uuid = match Uuid::parse_str(event_id) {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
     };
+    let total: i64 = match teams::dsl::teams
+        .filter(teams::dsl::event_id.eq(event_id))
+        .count()
+        .get_result(conn) {
+        Ok(c) => c,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let query = match (page.sort, page.order) {
+        (SortKey::CreatedAt, Order::Asc) => teams::dsl::teams
+            .order((teams::dsl::created_at.asc(), teams::dsl::id.asc()))
+            .into_boxed(),
+        (SortKey::CreatedAt, Order::Desc) => teams::dsl::teams
+            .order((teams::dsl::created_at.desc(), teams::dsl::id.desc()))
+            .into_boxed(),
+        (SortKey::Name, Order::Asc) => teams::dsl::teams
+            .order((teams::dsl::name.asc(), teams::dsl::id.asc()))
+            .into_boxed(),
+        (SortKey::Name, Order::Desc) => teams::dsl::teams
+            .order((teams::dsl::name.desc(), teams::dsl::id.desc()))
+            .into_boxed(),
+    };
+    match query
+        .filter(teams::dsl::event_id.eq(event_id))
+        .offset(page.offset)
+        .limit(page.limit)
+        .load::<Team>(conn) {
+        Ok(v) => return Ok(Page::new(attach_wanted_roles(conn, v)?, total, page)),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
 }
  
 // Update
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn update_user_profile(
+    user_id: &String,
+    profile: Option<&String>,
+    display_name: Option<&String>,
+    links: Option<&Vec<Option<String>>>
+) -> anyhow::Result<User> {
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let changes = UpdateUser{profile, display_name, links};
+    if profile.is_none() && display_name.is_none() && links.is_none() {
+        // Nothing to update; an empty changeset is an error in diesel
+        return search_user_by_id(user_id);
+    }
+    let conn = &mut establish_connection()?;
+    diesel::update(users::dsl::users.filter(users::dsl::id.eq(user_id)))
+        .set(&changes)
+        .get_result::<User>(conn)
+        .with_context(|| "Failed to update user")
+}
+
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn set_user_skill(
+    user_id: &String,
+    skill_id: &String,
+    level: &i32
+) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(skill_id);
+    let skill_id = match &binding {
+        Ok(u) => u,
+        Err(_) => return Err(CrudError::NotFound("No such skill".to_string()).into()),
+    };
+    let conn = &mut establish_connection()?;
+    let new_user_skill = NewUserSkill{user_id, skill_id, level};
+    let upserted = conn.transaction(|conn| {
+        insert_into(user_skills::dsl::user_skills)
+            .values(&new_user_skill)
+            .on_conflict((user_skills::dsl::user_id, user_skills::dsl::skill_id))
+            .do_update()
+            .set(user_skills::dsl::level.eq(level))
+            .execute(conn)
+    });
+    match upserted {
+        Ok(_) => Ok(()),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+            Err(CrudError::NotFound("No such skill".to_string()).into())
+        },
+        Err(e) => Err(anyhow::Error::new(e).context("Failed to upsert user_skill")),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn set_team_wanted_role(
+    team_id: &String,
+    role: &String,
+    count: &i32
+) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    conn.transaction(|conn| {
+        let filled: Option<i32> = team_wanted_roles::dsl::team_wanted_roles
+            .filter(team_wanted_roles::dsl::team_id.eq(team_id))
+            .filter(team_wanted_roles::dsl::role.eq(role))
+            .select(team_wanted_roles::dsl::filled)
+            .for_update()
+            .first(conn)
+            .optional()
+            .with_context(|| "Failed to load team_wanted_role")?;
+        if let Some(filled) = filled.filter(|filled| count < filled) {
+            let mut errors = FieldErrors::new();
+            errors.add("count", format!("must be at least {}, the members already filling the role", filled));
+            return Err(CrudError::Invalid(errors).into());
+        }
+        let new_wanted_role = NewWantedRole{team_id, role, count};
+        insert_into(team_wanted_roles::dsl::team_wanted_roles)
+            .values(&new_wanted_role)
+            .on_conflict((team_wanted_roles::dsl::team_id, team_wanted_roles::dsl::role))
+            .do_update()
+            .set(team_wanted_roles::dsl::count.eq(count))
+            .execute(conn)
+            .with_context(|| "Failed to upsert team_wanted_role")?;
+        Ok(())
+    })
+}
+
+// Only the recipient can mark a notification as read.
+// Returns false when the user has no such notification.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_notification_read(user_id: &Uuid, notification_id: &String) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(notification_id);
+    let notification_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = notifications::dsl::notifications
+        .filter(notifications::dsl::id.eq(notification_id))
+        .filter(notifications::dsl::user_id.eq(user_id));
+    let updated = diesel::update(target)
+        .set(notifications::dsl::read_at.eq(diesel::dsl::now.nullable()))
+        .execute(conn)
+        .with_context(|| "Failed to mark notification read")?;
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_all_notifications_read(user_id: &Uuid) -> anyhow::Result<usize> {
+    let conn = &mut establish_connection()?;
+    let target = notifications::dsl::notifications
+        .filter(notifications::dsl::user_id.eq(user_id))
+        .filter(notifications::dsl::read_at.is_null());
+    diesel::update(target)
+        .set(notifications::dsl::read_at.eq(diesel::dsl::now.nullable()))
+        .execute(conn)
+        .with_context(|| "Failed to mark notifications read")
+}
+
+// A new address, or one not verified yet, gets a fresh verification link,
+// and nothing else is mailed to it until the link is followed.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn set_email_preference(preference: &NewEmailPreference) -> anyhow::Result<EmailPreference> {
+    let conn = &mut establish_connection()?;
+    conn.transaction(|conn| {
+        let previous: Option<EmailPreference> = email_preferences::dsl::email_preferences
+            .find(preference.user_id)
+            .for_update()
+            .first(conn)
+            .optional()
+            .with_context(|| "Failed to get email preference")?;
+        let saved: EmailPreference = insert_into(email_preferences::dsl::email_preferences)
+            .values(preference)
+            .on_conflict(email_preferences::dsl::user_id)
+            .do_update()
+            .set(preference)
+            .get_result(conn)
+            .with_context(|| "Failed to set email preference")?;
+        if previous.is_some_and(|p| p.verified_at.is_some() && p.email == *preference.email) {
+            return Ok(saved);
+        }
+        let saved: EmailPreference = diesel::update(email_preferences::dsl::email_preferences.find(preference.user_id))
+            .set((
+                email_preferences::dsl::verification_token.eq(Uuid::new_v4()),
+                email_preferences::dsl::verified_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+            .with_context(|| "Failed to reset email verification")?;
+        if config::get().features.email {
+            let rendered = mail::render_verification(&saved.locale, &mail::verification_url(&saved));
+            insert_into(email_queue::dsl::email_queue)
+                .values(NewQueuedEmail{recipient: saved.email.clone(), subject: rendered.subject, body: rendered.body})
+                .execute(conn)
+                .with_context(|| "Failed to queue verification email")?;
+        }
+        Ok(saved)
+    })
+}
+
+// Mark the address behind the token as verified.
+// Returns false when the token is unknown.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn verify_email(token: &String) -> anyhow::Result<bool> {
+    let token = match Uuid::parse_str(token) {
+        Ok(t) => t,
+        Err(_) => return Ok(false),
+    };
+    let conn = &mut establish_connection()?;
+    let target = email_preferences::dsl::email_preferences
+        .filter(email_preferences::dsl::verification_token.eq(token));
+    match diesel::update(target)
+        .set(email_preferences::dsl::verified_at.eq(now.nullable()))
+        .execute(conn) {
+        Ok(n) => return Ok(n > 0),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+// Turn off every kind of email for the owner of the token.
+// Returns false when the token is unknown.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn unsubscribe_email(token: &String) -> anyhow::Result<bool> {
+    let token = match Uuid::parse_str(token) {
+        Ok(t) => t,
+        Err(_) => return Ok(false),
+    };
+    let conn = &mut establish_connection()?;
+    let target = email_preferences::dsl::email_preferences
+        .filter(email_preferences::dsl::unsubscribe_token.eq(token));
+    match diesel::update(target)
+        .set((
+            email_preferences::dsl::on_request.eq(false),
+            email_preferences::dsl::on_join.eq(false),
+            email_preferences::dsl::on_team.eq(false),
+        ))
+        .execute(conn) {
+        Ok(n) => return Ok(n > 0),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_email_sent(email_id: &Uuid) -> anyhow::Result<()> {
+    let conn = &mut establish_connection()?;
+    diesel::update(email_queue::dsl::email_queue.find(email_id))
+        .set(email_queue::dsl::sent_at.eq(now.nullable()))
+        .execute(conn)
+        .with_context(|| "Failed to mark email sent")?;
+    Ok(())
+}
+
+// Retry delays are added on the database side so they line up with now()
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_email_failed(email_id: &Uuid, attempts: i32, retry_in_secs: i32, error: &String) -> anyhow::Result<()> {
+    let conn = &mut establish_connection()?;
+    diesel::update(email_queue::dsl::email_queue.find(email_id))
+        .set((
+            email_queue::dsl::attempts.eq(attempts),
+            email_queue::dsl::next_attempt_at.eq(now + retry_in_secs.seconds()),
+            email_queue::dsl::last_error.eq(error),
+        ))
+        .execute(conn)
+        .with_context(|| "Failed to reschedule email")?;
+    Ok(())
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn set_event_integration(integration: &NewEventIntegration) -> anyhow::Result<EventIntegration> {
+    let conn = &mut establish_connection()?;
+    insert_into(event_integrations::dsl::event_integrations)
+        .values(integration)
+        .on_conflict(event_integrations::dsl::event_id)
+        .do_update()
+        .set(integration)
+        .get_result::<EventIntegration>(conn)
+        .with_context(|| "Failed to set event integration")
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_webhook_delivered(delivery_id: &Uuid, attempts: i32, status: Option<i32>) -> anyhow::Result<()> {
+    let conn = &mut establish_connection()?;
+    diesel::update(webhook_deliveries::dsl::webhook_deliveries.find(delivery_id))
+        .set((
+            webhook_deliveries::dsl::attempts.eq(attempts),
+            webhook_deliveries::dsl::delivered_at.eq(now.nullable()),
+            webhook_deliveries::dsl::response_status.eq(status),
+            webhook_deliveries::dsl::last_error.eq(None::<String>),
+        ))
+        .execute(conn)
+        .with_context(|| "Failed to mark webhook delivered")?;
+    Ok(())
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn mark_webhook_failed(delivery_id: &Uuid, attempts: i32, status: Option<i32>, retry_in_secs: i32, error: &String) -> anyhow::Result<()> {
+    let conn = &mut establish_connection()?;
+    diesel::update(webhook_deliveries::dsl::webhook_deliveries.find(delivery_id))
+        .set((
+            webhook_deliveries::dsl::attempts.eq(attempts),
+            webhook_deliveries::dsl::next_attempt_at.eq(now + retry_in_secs.seconds()),
+            webhook_deliveries::dsl::response_status.eq(status),
+            webhook_deliveries::dsl::last_error.eq(error),
+        ))
+        .execute(conn)
+        .with_context(|| "Failed to reschedule webhook delivery")?;
+    Ok(())
+}
+
+// Queue a delivery again right away, even one that succeeded or gave up.
+// Returns false when the subscription has no such delivery.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn redeliver_webhook(webhook_id: &Uuid, delivery_id: &String) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(delivery_id);
+    let delivery_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = webhook_deliveries::dsl::webhook_deliveries
+        .filter(webhook_deliveries::dsl::id.eq(delivery_id))
+        .filter(webhook_deliveries::dsl::subscription_id.eq(webhook_id));
+    match diesel::update(target)
+        .set((
+            webhook_deliveries::dsl::attempts.eq(0),
+            webhook_deliveries::dsl::next_attempt_at.eq(now),
+            webhook_deliveries::dsl::delivered_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn) {
+        Ok(n) => return Ok(n > 0),
+        Err(e) => return Err(anyhow!("{}", e)),
+    }
+}
+
+// Move a member to another team of the same event, counting them towards
+// its open roles. Returns the team they left.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn move_member(user_id: &Uuid, team_id: &String) -> anyhow::Result<Team> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let (from, to, notifications) = conn.transaction(|conn| {
+        let to: Team = teams::dsl::teams
+            .find(team_id)
+            .first(conn)
+            .with_context(|| "Failed to load team")?;
+        if to.reader_id == *user_id {
+            return Err(anyhow!("The user already leads {}", to.name));
+        }
+        let led: i64 = teams::dsl::teams
+            .filter(teams::dsl::event_id.eq(to.event_id))
+            .filter(teams::dsl::reader_id.eq(user_id))
+            .count()
+            .get_result(conn)
+            .with_context(|| "Failed to load led teams")?;
+        if led > 0 {
+            return Err(anyhow!("The user leads a team of this event; disband it first"));
+        }
+        let from: Option<Team> = teams::table
+            .inner_join(joins::table)
+            .filter(teams::dsl::event_id.eq(to.event_id))
+            .filter(joins::dsl::user_id.eq(user_id))
+            .select(Team::as_select())
+            .first(conn)
+            .optional()
+            .with_context(|| "Failed to load current team")?;
+        let from = match from {
+            Some(t) if t.id == to.id => return Err(anyhow!("The user is already in {}", to.name)),
+            Some(t) => t,
+            None => return Err(anyhow!("The user isn't in any team of this event")),
+        };
+        ensure_team_has_room(conn, team_id)?;
+        release_wanted_role(conn, &from.id, user_id)?;
+        diesel::delete(joins::dsl::joins
+                .filter(joins::dsl::team_id.eq(from.id))
+                .filter(joins::dsl::user_id.eq(user_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete join")?;
+        let new_join = NewJoin{team_id, user_id};
+        insert_into(joins::dsl::joins)
+            .values(&new_join)
+            .execute(conn)
+            .with_context(|| "Failed to insert new_join")?;
+        fill_wanted_role(conn, team_id, user_id)?;
+        queue_webhooks(conn, &to.event_id, WebhookEvent::JoinCreated, json!({"team_id": team_id, "user_id": user_id}))?;
+        let left_behind = others(get_team_member_ids(conn, &from.id)?, user_id);
+        let mut notifications = notify(conn, &left_behind, NoticeKind::MemberLeft, &from.id, user_id)?;
+        let members = others(get_team_member_ids(conn, team_id)?, user_id);
+        notifications.extend(notify(conn, &members, NoticeKind::MemberJoined, team_id, user_id)?);
+        Ok::<_, anyhow::Error>((from, to, notifications))
+    })?;
+    publish_all(notifications);
+    tracing::debug!(from = %from.id, to = %to.id, "Moved member");
+    Ok(from)
+}
+
+// Fold a duplicate account into `user_id`: everything the duplicate
+// organized, joined, asked for or was told about moves over, rows the kept
+// user already has win, and the duplicate is deleted.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn merge_users(duplicate_id: &Uuid, user_id: &Uuid) -> anyhow::Result<()> {
+    if duplicate_id == user_id {
+        return Err(anyhow!("Can't merge a user into itself"));
+    }
+    let conn = &mut establish_connection()?;
+    conn.transaction(|conn| {
+        use crate::schema::{event_organizers as eo, joins as j, requests as r, solos as s, user_skills as us};
+        let kept: Vec<Uuid> = eo::table.filter(eo::user_id.eq(user_id)).select(eo::event_id).load(conn)?;
+        diesel::delete(eo::table
+                .filter(eo::user_id.eq(duplicate_id))
+                .filter(eo::event_id.eq_any(&kept)))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate event_organizers")?;
+        diesel::update(eo::table.filter(eo::user_id.eq(duplicate_id)))
+            .set(eo::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move event_organizers")?;
+        let kept: Vec<Uuid> = j::table.filter(j::user_id.eq(user_id)).select(j::team_id).load(conn)?;
+        diesel::delete(j::table
+                .filter(j::user_id.eq(duplicate_id))
+                .filter(j::team_id.eq_any(&kept)))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate joins")?;
+        diesel::update(j::table.filter(j::user_id.eq(duplicate_id)))
+            .set(j::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move joins")?;
+        let kept: Vec<Uuid> = r::table.filter(r::user_id.eq(user_id)).select(r::team_id).load(conn)?;
+        diesel::delete(r::table
+                .filter(r::user_id.eq(duplicate_id))
+                .filter(r::team_id.eq_any(&kept)))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate requests")?;
+        diesel::update(r::table.filter(r::user_id.eq(duplicate_id)))
+            .set(r::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move requests")?;
+        let kept: Vec<Uuid> = s::table.filter(s::user_id.eq(user_id)).select(s::event_id).load(conn)?;
+        diesel::delete(s::table
+                .filter(s::user_id.eq(duplicate_id))
+                .filter(s::event_id.eq_any(&kept)))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate solos")?;
+        diesel::update(s::table.filter(s::user_id.eq(duplicate_id)))
+            .set(s::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move solos")?;
+        let kept: Vec<Uuid> = us::table.filter(us::user_id.eq(user_id)).select(us::skill_id).load(conn)?;
+        diesel::delete(us::table
+                .filter(us::user_id.eq(duplicate_id))
+                .filter(us::skill_id.eq_any(&kept)))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate user_skills")?;
+        diesel::update(us::table.filter(us::user_id.eq(duplicate_id)))
+            .set(us::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move user_skills")?;
+        diesel::update(teams::dsl::teams.filter(teams::dsl::reader_id.eq(duplicate_id)))
+            .set(teams::dsl::reader_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move led teams")?;
+        // A leader isn't also a member, and nobody in a team is solo
+        diesel::delete(j::table
+                .filter(j::user_id.eq(user_id))
+                .filter(j::team_id.eq_any(teams::dsl::teams.filter(teams::dsl::reader_id.eq(user_id)).select(teams::dsl::id))))
+            .execute(conn)
+            .with_context(|| "Failed to delete joins of led teams")?;
+        let team_ids: Vec<Uuid> = get_user_team_ids(conn, user_id)?;
+        diesel::delete(s::table
+                .filter(s::user_id.eq(user_id))
+                .filter(s::event_id.eq_any(teams::dsl::teams.filter(teams::dsl::id.eq_any(&team_ids)).select(teams::dsl::event_id))))
+            .execute(conn)
+            .with_context(|| "Failed to delete solos of team members")?;
+        diesel::update(notifications::dsl::notifications.filter(notifications::dsl::user_id.eq(duplicate_id)))
+            .set(notifications::dsl::user_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move notifications")?;
+        diesel::update(notifications::dsl::notifications.filter(notifications::dsl::actor_id.eq(duplicate_id)))
+            .set(notifications::dsl::actor_id.eq(user_id))
+            .execute(conn)
+            .with_context(|| "Failed to move notification actors")?;
+        // The kept user's email settings win
+        let has_preference = diesel::select(diesel::dsl::exists(email_preferences::dsl::email_preferences.find(user_id)))
+            .get_result::<bool>(conn)
+            .with_context(|| "Failed to get email preference")?;
+        if has_preference {
+            diesel::delete(email_preferences::dsl::email_preferences.find(duplicate_id))
+                .execute(conn)
+                .with_context(|| "Failed to delete duplicate email preference")?;
+        } else {
+            diesel::update(email_preferences::dsl::email_preferences.find(duplicate_id))
+                .set(email_preferences::dsl::user_id.eq(user_id))
+                .execute(conn)
+                .with_context(|| "Failed to move email preference")?;
+        }
+        let deleted = diesel::delete(users::dsl::users.find(duplicate_id))
+            .execute(conn)
+            .with_context(|| "Failed to delete duplicate user")?;
+        if deleted == 0 {
+            return Err(anyhow!("No such user: {}", duplicate_id));
+        }
+        Ok(())
+    })
+}
+
+// Accept a pending request: the user joins the team and stops being solo.
+// Returns false when there was no pending request.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn accept_request(team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let notifications = conn.transaction(|conn| {
+        let updated = set_request_status(conn, team_id, user_id, "accepted")?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        ensure_team_has_room(conn, team_id)?;
+        let new_join = NewJoin{team_id, user_id};
+        insert_into(joins::dsl::joins)
+            .values(&new_join)
+            .execute(conn)
+            .with_context(|| "Failed to insert new_join")?;
+        fill_wanted_role(conn, team_id, user_id)?;
+        let event_id: Uuid = teams::dsl::teams
+            .filter(teams::dsl::id.eq(team_id))
+            .select(teams::dsl::event_id)
+            .first(conn)
+            .with_context(|| "Failed to load team")?;
+        diesel::delete(solos::dsl::solos
+                .filter(solos::dsl::event_id.eq(event_id))
+                .filter(solos::dsl::user_id.eq(user_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete solo")?;
+        queue_webhooks(conn, &event_id, WebhookEvent::RequestAccepted, json!({"team_id": team_id, "user_id": user_id}))?;
+        let mut notifications = notify(conn, &[*user_id], NoticeKind::RequestAccepted, team_id, user_id)?;
+        let members = others(get_team_member_ids(conn, team_id)?, user_id);
+        notifications.extend(notify(conn, &members, NoticeKind::MemberJoined, team_id, user_id)?);
+        Ok::<Option<(Vec<Notification>, Vec<Outgoing>)>, anyhow::Error>(Some((notifications, chat_if_team_full(conn, team_id)?)))
+    })?;
+    match notifications {
+        Some((n, outgoing)) => {
+            publish_all(n);
+            chat::send_all(outgoing);
+            return Ok(true);
+        },
+        None => return Ok(false),
+    }
+}
+
+// Returns false when there was no pending request
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn decline_request(team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let notifications = conn.transaction(|conn| {
+        let updated = set_request_status(conn, team_id, user_id, "declined")?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        queue_team_webhooks(conn, team_id, WebhookEvent::RequestDeclined, json!({"team_id": team_id, "user_id": user_id}))?;
+        let notifications = notify(conn, &[*user_id], NoticeKind::RequestDeclined, team_id, user_id)?;
+        Ok::<Option<Vec<Notification>>, anyhow::Error>(Some(notifications))
+    })?;
+    match notifications {
+        Some(n) => {
+            publish_all(n);
+            return Ok(true);
+        },
+        None => return Ok(false),
+    }
+}
 
 // Delete
-pub fn delete_event_by_id(event_id: &String) -> anyhow::Result<()> {
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_join(team_id: &String, user_id: &String) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = joins::dsl::joins
+        .filter(joins::dsl::team_id.eq(team_id))
+        .filter(joins::dsl::user_id.eq(user_id));
+    let notifications = conn.transaction(|conn| {
+        release_wanted_role(conn, team_id, user_id)?;
+        diesel::delete(target)
+            .execute(conn)
+            .with_context(|| "Failed to delete join")?;
+        let members = others(get_team_member_ids(conn, team_id)?, user_id);
+        notify(conn, &members, NoticeKind::MemberLeft, team_id, user_id)
+    })?;
+    publish_all(notifications);
+    Ok(())
+}
+
+// Disband a team, removing everything that refers to it
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_team_by_id(team_id: &String, actor_id: &Uuid) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let notifications = conn.transaction(|conn| {
+        let members = others(get_team_member_ids(conn, team_id)?, actor_id);
+        let notifications = notify(conn, &members, NoticeKind::TeamDisbanded, team_id, actor_id)?;
+        diesel::delete(team_wanted_roles::dsl::team_wanted_roles.filter(team_wanted_roles::dsl::team_id.eq(team_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete team_wanted_roles")?;
+        diesel::delete(requests::dsl::requests.filter(requests::dsl::team_id.eq(team_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete requests")?;
+        diesel::delete(joins::dsl::joins.filter(joins::dsl::team_id.eq(team_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete joins")?;
+        diesel::delete(teams::dsl::teams.filter(teams::dsl::id.eq(team_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete team")?;
+        Ok::<Vec<Notification>, anyhow::Error>(notifications)
+    })?;
+    publish_all(notifications);
+    Ok(())
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_team_wanted_role(team_id: &String, role: &String) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(team_id);
+    let team_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = team_wanted_roles::dsl::team_wanted_roles
+        .filter(team_wanted_roles::dsl::team_id.eq(team_id))
+        .filter(team_wanted_roles::dsl::role.eq(role));
+    conn.transaction(|conn| {
+        // Members who filled it no longer hold a slot to free
+        diesel::update(joins::dsl::joins
+                .filter(joins::dsl::team_id.eq(team_id))
+                .filter(joins::dsl::filled_role.eq(role)))
+            .set(joins::dsl::filled_role.eq(None::<String>))
+            .execute(conn)
+            .with_context(|| "Failed to clear filled_role")?;
+        diesel::delete(target)
+            .execute(conn)
+            .with_context(|| "Failed to delete team_wanted_role")?;
+        Ok(())
+    })
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_user_skill(user_id: &String, skill_id: &String) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(user_id);
+    let user_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let binding = conv_string_to_uuid(skill_id);
+    let skill_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = user_skills::dsl::user_skills
+        .filter(user_skills::dsl::user_id.eq(user_id))
+        .filter(user_skills::dsl::skill_id.eq(skill_id));
+    diesel::delete(target)
+        .execute(conn)
+        .with_context(|| "Failed to delete user_skill")?;
+    Ok(())
+}
+
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_event_integration(event_id: &String) -> anyhow::Result<()> {
     let binding = conv_string_to_uuid(event_id);
     let event_id = match &binding {
         Ok(u) => u,
         Err(e) => return Err(anyhow!("{}", e)),
     };
     let conn = &mut establish_connection()?;
-    let target = events::dsl::events
-        .filter(events::dsl::id.eq(event_id));
-    diesel::delete(target)
+    diesel::delete(event_integrations::dsl::event_integrations.find(event_id))
         .execute(conn)
-        .with_context(|| "Failed to delete event")?;
+        .with_context(|| "Failed to delete event integration")?;
     Ok(())
 }
 
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_webhook_subscription(webhook_id: &Uuid) -> anyhow::Result<()> {
+    let conn = &mut establish_connection()?;
+    conn.transaction(|conn| {
+        diesel::delete(webhook_deliveries::dsl::webhook_deliveries
+                .filter(webhook_deliveries::dsl::subscription_id.eq(webhook_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete webhook deliveries")?;
+        diesel::delete(webhook_subscriptions::dsl::webhook_subscriptions.find(webhook_id))
+            .execute(conn)
+            .with_context(|| "Failed to delete webhook subscription")?;
+        Ok(())
+    })
+}
+
+// Returns false when the user didn't organize the event
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_event_organizer(event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    let target = event_organizers::dsl::event_organizers
+        .filter(event_organizers::dsl::event_id.eq(event_id))
+        .filter(event_organizers::dsl::user_id.eq(user_id));
+    let deleted = diesel::delete(target)
+        .execute(conn)
+        .with_context(|| "Failed to delete event_organizer")?;
+    Ok(deleted > 0)
+}
+
+// Delete an event, removing everything that refers to it
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn delete_event_by_id(event_id: &String) -> anyhow::Result<()> {
+    let binding = conv_string_to_uuid(event_id);
+    let event_id = match &binding {
+        Ok(u) => u,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let conn = &mut establish_connection()?;
+    conn.transaction(|conn| {
+        let team_ids = teams::dsl::teams
+            .filter(teams::dsl::event_id.eq(event_id))
+            .select(teams::dsl::id);
+        diesel::delete(team_wanted_roles::dsl::team_wanted_roles.filter(team_wanted_roles::dsl::team_id.eq_any(team_ids)))
+            .execute(conn)
+            .with_context(|| "Failed to delete team_wanted_roles")?;
+        diesel::delete(requests::dsl::requests.filter(requests::dsl::team_id.eq_any(team_ids)))
+            .execute(conn)
+            .with_context(|| "Failed to delete requests")?;
+        diesel::delete(joins::dsl::joins.filter(joins::dsl::team_id.eq_any(team_ids)))
+            .execute(conn)
+            .with_context(|| "Failed to delete joins")?;
+        diesel::delete(teams::dsl::teams.filter(teams::dsl::event_id.eq(event_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete teams")?;
+        diesel::delete(solos::dsl::solos.filter(solos::dsl::event_id.eq(event_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete solos")?;
+        diesel::delete(event_organizers::dsl::event_organizers.filter(event_organizers::dsl::event_id.eq(event_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete event_organizers")?;
+        diesel::delete(event_integrations::dsl::event_integrations.find(event_id))
+            .execute(conn)
+            .with_context(|| "Failed to delete event integration")?;
+        let subscription_ids = webhook_subscriptions::dsl::webhook_subscriptions
+            .filter(webhook_subscriptions::dsl::event_id.eq(event_id))
+            .select(webhook_subscriptions::dsl::id);
+        diesel::delete(webhook_deliveries::dsl::webhook_deliveries.filter(webhook_deliveries::dsl::subscription_id.eq_any(subscription_ids)))
+            .execute(conn)
+            .with_context(|| "Failed to delete webhook deliveries")?;
+        diesel::delete(webhook_subscriptions::dsl::webhook_subscriptions.filter(webhook_subscriptions::dsl::event_id.eq(event_id)))
+            .execute(conn)
+            .with_context(|| "Failed to delete webhook subscriptions")?;
+        diesel::delete(events::dsl::events.find(event_id))
+            .execute(conn)
+            .with_context(|| "Failed to delete event")?;
+        Ok(())
+    })
+}
+
 // Search
 fn search_event_by_id(event_id: Uuid) -> anyhow::Result<Event> {
     let conn = &mut establish_connection()?;
@@ -263,17 +1744,6 @@ fn search_event_by_id(event_id: Uuid) -> anyhow::Result<Event> {
     }
 }
 
-fn search_team_by_id(team_id: Uuid) -> anyhow::Result<Team> {
-    let conn = &mut establish_connection()?;
-    match teams::dsl::teams
-        .filter(teams::dsl::id.eq(team_id))
-        .limit(1)
-        .first::<Team>(conn) {
-        Ok(v) => return Ok(v),
-        Err(e) => return Err(anyhow!("{}", e)),
-    }
-}
-
 
 fn search_user_by_name(name: &String) -> anyhow::Result<User> {
     let conn = &mut establish_connection()?;
@@ -298,9 +1768,446 @@ fn search_user_by_id(id: Uuid) -> anyhow::Result<User> {
 }
 
 // Utils
-fn conv_string_to_uuid(str_uuid: &str) -> anyhow::Result<(Uuid)> {
+fn set_request_status(conn: &mut PgConnection, team_id: &Uuid, user_id: &Uuid, status: &str) -> anyhow::Result<usize> {
+    let target = requests::dsl::requests
+        .filter(requests::dsl::team_id.eq(team_id))
+        .filter(requests::dsl::user_id.eq(user_id))
+        .filter(requests::dsl::status.eq("pending"));
+    diesel::update(target)
+        .set(requests::dsl::status.eq(status))
+        .execute(conn)
+        .with_context(|| "Failed to update request status")
+}
+
+// The leader followed by every member who joined
+fn get_team_member_ids(conn: &mut PgConnection, team_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+    let mut ids: Vec<Uuid> = teams::dsl::teams
+        .filter(teams::dsl::id.eq(team_id))
+        .select(teams::dsl::reader_id)
+        .load(conn)
+        .with_context(|| "Failed to load team leader")?;
+    let joined: Vec<Uuid> = joins::dsl::joins
+        .filter(joins::dsl::team_id.eq(team_id))
+        .order(joins::dsl::created_at.asc())
+        .select(joins::dsl::user_id)
+        .load(conn)
+        .with_context(|| "Failed to load team members")?;
+    ids.extend(joined);
+    Ok(ids)
+}
+
+// Teams the user leads or joined
+fn get_user_team_ids(conn: &mut PgConnection, user_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+    let mut ids: Vec<Uuid> = teams::dsl::teams
+        .filter(teams::dsl::reader_id.eq(user_id))
+        .select(teams::dsl::id)
+        .load(conn)
+        .with_context(|| "Failed to load led teams")?;
+    let joined: Vec<Uuid> = joins::dsl::joins
+        .filter(joins::dsl::user_id.eq(user_id))
+        .select(joins::dsl::team_id)
+        .load(conn)
+        .with_context(|| "Failed to load joined teams")?;
+    ids.extend(joined);
+    Ok(ids)
+}
+
+// Store a notification for each recipient about something that happened to
+// `actor_id` in a team. Call within the transaction making the change and
+// hand the result to publish_all once it has committed.
+fn notify(
+    conn: &mut PgConnection,
+    recipients: &[Uuid],
+    kind: NoticeKind,
+    team_id: &Uuid,
+    actor_id: &Uuid
+) -> anyhow::Result<Vec<Notification>> {
+    let new_notifications: Vec<NewNotification> = recipients.iter()
+        .map(|user_id| NewNotification{user_id, kind: kind.as_str(), team_id, actor_id})
+        .collect();
+    let notifications = insert_into(notifications::dsl::notifications)
+        .values(&new_notifications)
+        .get_results::<Notification>(conn)
+        .with_context(|| "Failed to insert notifications")?;
+    queue_emails(conn, recipients, kind, team_id, actor_id)?;
+    Ok(notifications)
+}
+
+// Queue an email for every recipient who wants one about `kind`. The mail
+// worker sends them later, so a slow SMTP server never holds up a request.
+fn queue_emails(
+    conn: &mut PgConnection,
+    recipients: &[Uuid],
+    kind: NoticeKind,
+    team_id: &Uuid,
+    actor_id: &Uuid
+) -> anyhow::Result<()> {
+    if !config::get().features.email {
+        return Ok(());
+    }
+    let preferences: Vec<EmailPreference> = email_preferences::dsl::email_preferences
+        .filter(email_preferences::dsl::user_id.eq_any(recipients))
+        .filter(email_preferences::dsl::verified_at.is_not_null())
+        .load(conn)
+        .with_context(|| "Failed to get email preferences")?;
+    let preferences: Vec<EmailPreference> = preferences.into_iter()
+        .filter(|p| mail::wants(p, kind))
+        .collect();
+    if preferences.is_empty() {
+        return Ok(());
+    }
+    let team_name: String = teams::dsl::teams
+        .find(team_id)
+        .select(teams::dsl::name)
+        .first(conn)
+        .with_context(|| "Failed to get team")?;
+    let actor: User = users::dsl::users
+        .find(actor_id)
+        .first(conn)
+        .with_context(|| "Failed to get user")?;
+    let actor_name = actor.display_name.unwrap_or(actor.name);
+    let emails: Vec<NewQueuedEmail> = preferences.iter()
+        .map(|p| {
+            let ctx = MailContext{
+                team_name: &team_name,
+                actor_name: &actor_name,
+                unsubscribe_url: mail::unsubscribe_url(p),
+            };
+            let rendered = mail::render(kind, &p.locale, &ctx);
+            NewQueuedEmail{recipient: p.email.clone(), subject: rendered.subject, body: rendered.body}
+        })
+        .collect();
+    insert_into(email_queue::dsl::email_queue)
+        .values(&emails)
+        .execute(conn)
+        .with_context(|| "Failed to queue emails")?;
+    Ok(())
+}
+
+// Queue a delivery of `event` for every subscription of the event that
+// wants it. Call within the transaction making the change so nothing is
+// announced that didn't happen.
+fn queue_webhooks(
+    conn: &mut PgConnection,
+    event_id: &Uuid,
+    event: WebhookEvent,
+    data: serde_json::Value
+) -> anyhow::Result<()> {
+    if !config::get().features.webhooks {
+        return Ok(());
+    }
+    let subscriptions: Vec<WebhookSubscription> = webhook_subscriptions::dsl::webhook_subscriptions
+        .filter(webhook_subscriptions::dsl::event_id.eq(event_id))
+        .load(conn)
+        .with_context(|| "Failed to get webhook subscriptions")?;
+    for subscription in subscriptions.iter().filter(|s| webhook::subscribes_to(s, event)) {
+        let id = Uuid::new_v4();
+        let payload = webhook::payload(&id, event, event_id, data.clone());
+        let new_delivery = NewWebhookDelivery{
+            id: &id,
+            subscription_id: &subscription.id,
+            event_type: event.as_str(),
+            payload: &payload,
+        };
+        insert_into(webhook_deliveries::dsl::webhook_deliveries)
+            .values(&new_delivery)
+            .execute(conn)
+            .with_context(|| "Failed to queue webhook delivery")?;
+    }
+    Ok(())
+}
+
+// Same as queue_webhooks for changes that only know their team
+fn queue_team_webhooks(
+    conn: &mut PgConnection,
+    team_id: &Uuid,
+    event: WebhookEvent,
+    data: serde_json::Value
+) -> anyhow::Result<()> {
+    let event_id: Uuid = teams::dsl::teams
+        .find(team_id)
+        .select(teams::dsl::event_id)
+        .first(conn)
+        .with_context(|| "Failed to load team")?;
+    queue_webhooks(conn, &event_id, event, data)
+}
+
+// Chat message for the event's channel, if it has one that wants it.
+// `build` gets the event name and is only called when there is a channel.
+fn chat_for_event(
+    conn: &mut PgConnection,
+    event_id: &Uuid,
+    build: impl FnOnce(&mut PgConnection, String) -> anyhow::Result<ChatMessage>
+) -> anyhow::Result<Vec<Outgoing>> {
+    if !config::get().features.chat {
+        return Ok(vec![]);
+    }
+    let integration: Option<EventIntegration> = event_integrations::dsl::event_integrations
+        .find(event_id)
+        .first(conn)
+        .optional()
+        .with_context(|| "Failed to get event integration")?;
+    let integration = match integration {
+        Some(i) => i,
+        None => return Ok(vec![]),
+    };
+    let platform = match Platform::parse(&integration.platform) {
+        Some(p) => p,
+        None => return Ok(vec![]),
+    };
+    let event_name: String = events::dsl::events
+        .find(event_id)
+        .select(events::dsl::name)
+        .first(conn)
+        .with_context(|| "Failed to load event")?;
+    let message = build(conn, event_name)?;
+    if !chat::wants(&integration, &message) {
+        return Ok(vec![]);
+    }
+    Ok(vec![Outgoing{platform, url: integration.webhook_url, message}])
+}
+
+// Refuse another member once the leader and members fill the team. The team
+// row stays locked until the transaction ends, so joins racing for the last
+// place are counted one after the other.
+fn ensure_team_has_room(conn: &mut PgConnection, team_id: &Uuid) -> anyhow::Result<()> {
+    let capacity: Option<i32> = teams::dsl::teams
+        .find(team_id)
+        .select(teams::dsl::capacity)
+        .for_update()
+        .first(conn)
+        .optional()
+        .with_context(|| "Failed to load team")?;
+    let capacity = match capacity {
+        Some(c) => c,
+        None => return Err(CrudError::NotFound("No such team".to_string()).into()),
+    };
+    if get_team_member_ids(conn, team_id)?.len() as i32 >= capacity {
+        return Err(CrudError::Conflict("The team is full".to_string()).into());
+    }
+    Ok(())
+}
+
+// Announce the team once the member that fills it has joined
+fn chat_if_team_full(conn: &mut PgConnection, team_id: &Uuid) -> anyhow::Result<Vec<Outgoing>> {
+    let team: Team = teams::dsl::teams
+        .find(team_id)
+        .first(conn)
+        .with_context(|| "Failed to load team")?;
+    let size = get_team_member_ids(conn, team_id)?.len() as i32;
+    if size != team.capacity {
+        return Ok(vec![]);
+    }
+    chat_for_event(conn, &team.event_id, |_, event_name| {
+        Ok(ChatMessage::TeamFull{event_name, team_name: team.name.clone(), capacity: team.capacity})
+    })
+}
+
+fn publish_all(notifications: Vec<Notification>) {
+    for n in notifications {
+        bus::publish(n);
+    }
+}
+
+fn others(members: Vec<Uuid>, user_id: &Uuid) -> Vec<Uuid> {
+    members.into_iter().filter(|m| m != user_id).collect()
+}
+
+fn attach_skills(conn: &mut PgConnection, users: Vec<User>) -> anyhow::Result<Vec<UserDetail>> {
+    let user_ids: Vec<Uuid> = users.iter().map(|u| u.id).collect();
+    let rows: Vec<UserSkillDetail> = match user_skills::table
+        .inner_join(skills::table)
+        .filter(user_skills::dsl::user_id.eq_any(&user_ids))
+        .order((skills::dsl::kind.asc(), user_skills::dsl::level.desc(), skills::dsl::name.asc()))
+        .select((
+            user_skills::dsl::user_id,
+            skills::dsl::id,
+            skills::dsl::name,
+            skills::dsl::kind,
+            skills::dsl::category,
+            user_skills::dsl::level,
+        ))
+        .load::<UserSkillDetail>(conn) {
+        Ok(v) => v,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let mut by_user: HashMap<Uuid, Vec<UserSkillDetail>> = HashMap::new();
+    for row in rows {
+        by_user.entry(row.user_id).or_default().push(row);
+    }
+    Ok(users.into_iter()
+        .map(|user| {
+            let skills = by_user.remove(&user.id).unwrap_or_default();
+            UserDetail{user, skills}
+        })
+        .collect())
+}
+
+fn filter_events(filter: &EventFilter) -> events::BoxedQuery<'static, Pg> {
+    use crate::schema::events::dsl::*;
+    let mut query = events.into_boxed();
+    if let Some(q) = &filter.q {
+        // Matched with ILIKE so that the pg_trgm indexes on name/desc are used
+        let pattern = format!("%{}%", escape_like(q.trim()));
+        query = query.filter(name.ilike(pattern.clone()).or(desc.ilike(pattern)));
+    }
+    if let Some(from) = filter.from {
+        query = query.filter(started_at.ge(from.and_hms_opt(0, 0, 0).unwrap()));
+    }
+    if let Some(to) = filter.to {
+        // `to` is inclusive, so compare against the start of the following day
+        let next_day = to.succ_opt().unwrap_or(to).and_hms_opt(0, 0, 0).unwrap();
+        query = query.filter(started_at.lt(next_day));
+    }
+    match filter.status {
+        Some(EventStatus::Upcoming) => {
+            query = query.filter(started_at.gt(now.nullable()));
+        },
+        Some(EventStatus::Ongoing) => {
+            query = query
+                .filter(started_at.le(now.nullable()))
+                .filter(ended_at.is_null().or(ended_at.ge(now.nullable())));
+        },
+        Some(EventStatus::Finished) => {
+            query = query.filter(ended_at.lt(now.nullable()));
+        },
+        None => {},
+    }
+    if let Some(has_open) = filter.has_open_teams {
+        // A team is open while the leader plus its members are below capacity
+        let open_teams = "EXISTS (SELECT 1 FROM teams t WHERE t.event_id = events.id \
+            AND t.capacity > 1 + (SELECT COUNT(*) FROM joins j WHERE j.team_id = t.id))";
+        if has_open {
+            query = query.filter(sql::<Bool>(open_teams));
+        } else {
+            query = query.filter(sql::<Bool>(&format!("NOT {}", open_teams)));
+        }
+    }
+    query
+}
+
+fn attach_wanted_roles(conn: &mut PgConnection, teams: Vec<Team>) -> anyhow::Result<Vec<TeamDetail>> {
+    let team_ids: Vec<Uuid> = teams.iter().map(|t| t.id).collect();
+    let rows: Vec<WantedRole> = match team_wanted_roles::dsl::team_wanted_roles
+        .filter(team_wanted_roles::dsl::team_id.eq_any(&team_ids))
+        .order(team_wanted_roles::dsl::role.asc())
+        .load::<WantedRole>(conn) {
+        Ok(v) => v,
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+    let mut by_team: HashMap<Uuid, Vec<WantedRole>> = HashMap::new();
+    for row in rows {
+        by_team.entry(row.team_id).or_default().push(row);
+    }
+    Ok(teams.into_iter()
+        .map(|team| {
+            let wanted_roles = by_team.remove(&team.id).unwrap_or_default();
+            TeamDetail{team, wanted_roles}
+        })
+        .collect())
+}
+
+// Count a newly joined member towards one of the team's open roles
+fn fill_wanted_role(conn: &mut PgConnection, team_id: &Uuid, user_id: &Uuid) -> anyhow::Result<()> {
+    let categories: Vec<String> = user_skills::table
+        .inner_join(skills::table)
+        .filter(user_skills::dsl::user_id.eq(user_id))
+        .filter(skills::dsl::kind.eq("skill"))
+        .filter(skills::dsl::category.is_not_null())
+        .select(skills::dsl::category.assume_not_null())
+        .distinct()
+        .load(conn)
+        .with_context(|| "Failed to load member skill categories")?;
+    let roles: Vec<WantedRole> = team_wanted_roles::dsl::team_wanted_roles
+        .filter(team_wanted_roles::dsl::team_id.eq(team_id))
+        .load(conn)
+        .with_context(|| "Failed to load team_wanted_roles")?;
+    if let Some(role) = pick_role_to_fill(&roles, &categories) {
+        let target = team_wanted_roles::dsl::team_wanted_roles
+            .filter(team_wanted_roles::dsl::team_id.eq(team_id))
+            .filter(team_wanted_roles::dsl::role.eq(role));
+        diesel::update(target)
+            .set(team_wanted_roles::dsl::filled.eq(team_wanted_roles::dsl::filled + 1))
+            .execute(conn)
+            .with_context(|| "Failed to fill team_wanted_role")?;
+        diesel::update(joins::dsl::joins
+                .filter(joins::dsl::team_id.eq(team_id))
+                .filter(joins::dsl::user_id.eq(user_id)))
+            .set(joins::dsl::filled_role.eq(role))
+            .execute(conn)
+            .with_context(|| "Failed to record filled_role")?;
+    }
+    Ok(())
+}
+
+// Give back the slot a leaving member filled, before their join is deleted
+fn release_wanted_role(conn: &mut PgConnection, team_id: &Uuid, user_id: &Uuid) -> anyhow::Result<()> {
+    let role: Option<String> = joins::dsl::joins
+        .filter(joins::dsl::team_id.eq(team_id))
+        .filter(joins::dsl::user_id.eq(user_id))
+        .select(joins::dsl::filled_role)
+        .first::<Option<String>>(conn)
+        .optional()
+        .with_context(|| "Failed to load join")?
+        .flatten();
+    if let Some(role) = role {
+        let target = team_wanted_roles::dsl::team_wanted_roles
+            .filter(team_wanted_roles::dsl::team_id.eq(team_id))
+            .filter(team_wanted_roles::dsl::role.eq(role))
+            .filter(team_wanted_roles::dsl::filled.gt(0));
+        diesel::update(target)
+            .set(team_wanted_roles::dsl::filled.eq(team_wanted_roles::dsl::filled - 1))
+            .execute(conn)
+            .with_context(|| "Failed to release team_wanted_role")?;
+    }
+    Ok(())
+}
+
+// The open role matching one of the categories with the most slots left
+fn pick_role_to_fill<'a>(roles: &'a [WantedRole], categories: &[String]) -> Option<&'a String> {
+    roles.iter()
+        .filter(|r| r.remaining() > 0)
+        .filter(|r| categories.iter().any(|c| c.eq_ignore_ascii_case(&r.role)))
+        .max_by(|a, b| a.remaining().cmp(&b.remaining()).then_with(|| b.role.cmp(&a.role)))
+        .map(|r| &r.role)
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn conv_string_to_uuid(str_uuid: &str) -> anyhow::Result<Uuid> {
     match Uuid::parse_str(str_uuid) {
         Ok(uuid) => return Ok(uuid),
         Err(e) => return Err(anyhow!("{}", e)),
     };
 }
+
+#[cfg(test)]
+mod cruds_tests {
+    use super::*;
+
+    fn wanted_role(role: &str, count: i32, filled: i32) -> WantedRole {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        WantedRole{team_id: Uuid::nil(), role: role.to_string(), count, filled, created_at: at, updated_at: at}
+    }
+
+    #[test]
+    fn picks_open_role_matching_skills() {
+        let roles = vec![
+            wanted_role("backend", 2, 2),
+            wanted_role("designer", 1, 0),
+            wanted_role("frontend", 2, 0),
+        ];
+        let categories = vec!["Backend".to_string(), "frontend".to_string()];
+        assert_eq!(pick_role_to_fill(&roles, &categories), Some(&"frontend".to_string()));
+        assert_eq!(pick_role_to_fill(&roles, &["ml".to_string()]), None);
+    }
+
+    #[test]
+    fn ties_are_broken_by_role_name() {
+        let roles = vec![wanted_role("frontend", 1, 0), wanted_role("backend", 1, 0)];
+        let categories = vec!["frontend".to_string(), "backend".to_string()];
+        assert_eq!(pick_role_to_fill(&roles, &categories), Some(&"backend".to_string()));
+    }
+}
@@ -1,14 +1,103 @@
-use anyhow::Context;
+use actix_web::web;
+use anyhow::{anyhow, Context};
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use dotenv::dotenv;
-use std::env;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection, TestCustomizer};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::sync::OnceLock;
+use std::time::Duration;
 
-pub fn establish_connection() -> anyhow::Result<PgConnection> {
-    dotenv().ok();
-    let database_url = env::var("DATABASE_URL")?;
-    PgConnection::establish(&database_url)
-        .context(format!("Failed to connect {}", database_url))
+use crate::config;
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+pub type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
+
+static POOL: OnceLock<DbPool> = OnceLock::new();
+
+// The migrations/ directory, compiled in so a release carries its own schema
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+// Held while migrating so instances starting together don't race
+const MIGRATION_LOCK_ID: i64 = 0x686f7463685f6d67;
+
+fn build_pool() -> anyhow::Result<DbPool> {
+    let database = &config::get().database;
+    Pool::builder()
+        .max_size(database.max_connections)
+        .min_idle(database.min_idle)
+        .connection_timeout(Duration::from_secs(database.connect_timeout_secs))
+        .build(ConnectionManager::<PgConnection>::new(&database.url))
+        .context("Failed to connect database")
+}
+
+/// Open the pool up front so a bad DATABASE_URL fails at startup
+pub fn init_pool() -> anyhow::Result<()> {
+    if POOL.get().is_none() {
+        let _ = POOL.set(build_pool()?);
+    }
+    Ok(())
+}
+
+pub fn pool() -> anyhow::Result<&'static DbPool> {
+    init_pool()?;
+    Ok(POOL.get().unwrap())
+}
+
+pub fn establish_connection() -> anyhow::Result<DbConnection> {
+    pool()?.get()
+        .context("Failed to get a database connection")
+}
+
+/// Run database work on the blocking thread pool, so the async worker keeps
+/// serving other requests while the query is in flight
+pub async fn block<F, T>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match web::block(f).await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow!("Blocking task failed: {}", e)),
+    }
+}
+
+/// Versions of the embedded migrations the database hasn't run yet
+pub fn pending_migrations(conn: &mut PgConnection) -> anyhow::Result<Vec<String>> {
+    let pending = conn.pending_migrations(MIGRATIONS)
+        .map_err(|e| anyhow!("Failed to list pending migrations: {}", e))?;
+    Ok(pending.iter().map(|m| m.name().version().to_string()).collect())
+}
+
+/// Open a pool of one connection that stays inside a transaction which is
+/// never committed, for tests against a real database. Call before
+/// anything else touches the pool.
+pub fn init_test_pool() -> anyhow::Result<()> {
+    let database = &config::get().database;
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_secs(5))
+        .connection_customizer(Box::new(TestCustomizer))
+        .build(ConnectionManager::<PgConnection>::new(&database.url))
+        .context("Failed to connect database")?;
+    POOL.set(pool).map_err(|_| anyhow!("The pool was already opened"))
+}
+
+/// Run every pending migration and return the versions that were applied
+pub fn run_migrations() -> anyhow::Result<Vec<String>> {
+    migrate(&mut *establish_connection()?)
+}
+
+/// `run_migrations` on a connection of the caller's
+pub fn migrate(conn: &mut PgConnection) -> anyhow::Result<Vec<String>> {
+    diesel::sql_query(format!("SELECT pg_advisory_lock({})", MIGRATION_LOCK_ID))
+        .execute(conn)
+        .context("Failed to take the migration lock")?;
+    let applied = conn.run_pending_migrations(MIGRATIONS)
+        .map(|versions| versions.iter().map(|v| v.to_string()).collect())
+        .map_err(|e| anyhow!("Failed to run migrations: {}", e));
+    diesel::sql_query(format!("SELECT pg_advisory_unlock({})", MIGRATION_LOCK_ID))
+        .execute(conn)
+        .context("Failed to release the migration lock")?;
+    applied
 }
 
 #[cfg(test)]
@@ -20,4 +109,16 @@ mod databse_tests {
         establish_connection()
             .expect("Failed to connect database");
     }
+
+    #[test]
+    fn lists_pending_migrations() {
+        use diesel::migration::MigrationSource;
+        let embedded: Vec<String> = MigrationSource::<diesel::pg::Pg>::migrations(&MIGRATIONS).unwrap()
+            .iter()
+            .map(|m| m.name().version().to_string())
+            .collect();
+        assert!(embedded.contains(&"20230909044305".to_string()));
+        let pending = pending_migrations(&mut establish_connection().unwrap()).unwrap();
+        assert!(pending.iter().all(|v| embedded.contains(v)));
+    }
 }
@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::UserDetail;
+
+// Namespace of the plan ids handed out with previews
+const PLAN_NAMESPACE: Uuid = Uuid::from_u128(0x6f7e_21c4_93d1_4b0a_8a35_5c0e_d7a1_f3b2);
+
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct PlannedTeam {
+    pub name: String,
+    pub leader_id: Uuid,
+    pub members: Vec<UserDetail>,
+}
+
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct Plan {
+    /// Identifies this exact partition; confirming with a stale id is refused
+    pub plan_id: Uuid,
+    pub teams: Vec<PlannedTeam>,
+    pub unassigned: Vec<UserDetail>,
+}
+
+// Users that have to end up in the same team
+struct Unit<'a> {
+    members: Vec<&'a UserDetail>,
+    categories: Vec<String>,
+}
+
+/// Partition `solos` into balanced teams of at most `team_size`.
+///
+/// `together` lists groups of user ids that asked to stay together; ids of
+/// users who are not in `solos` are ignored. The result only depends on the
+/// input, so the same solos always give the same plan.
+pub fn plan(solos: &[UserDetail], team_size: usize, together: &[Vec<Uuid>]) -> anyhow::Result<Plan> {
+    if team_size < 2 {
+        return Err(anyhow!("team_size must be at least 2"));
+    }
+    let mut solos: Vec<&UserDetail> = solos.iter().collect();
+    solos.sort_by_key(|u| u.user.id);
+    solos.dedup_by_key(|u| u.user.id);
+    let n = solos.len();
+    if n < 2 {
+        return Ok(finish(vec![], solos.into_iter().cloned().collect()));
+    }
+
+    // Sizes differ by at most one so no team ends up much smaller
+    let team_count = n.div_ceil(team_size);
+    let mut room: Vec<usize> = (0..team_count)
+        .map(|i| n / team_count + usize::from(i < n % team_count))
+        .collect();
+    let smallest = *room.iter().min().unwrap();
+
+    let units = build_units(&solos, together, smallest)?;
+    let mut slots: Vec<Vec<&UserDetail>> = vec![vec![]; team_count];
+    let mut categories: Vec<HashMap<String, usize>> = vec![HashMap::new(); team_count];
+    for unit in units {
+        let size = unit.members.len();
+        // Prefer the team that has the fewest members sharing this unit's
+        // skills, then the one with the most room left
+        let best = (0..team_count)
+            .filter(|&i| room[i] >= size)
+            .min_by_key(|&i| {
+                let overlap: usize = unit.categories.iter()
+                    .map(|c| categories[i].get(c).copied().unwrap_or(0))
+                    .sum();
+                (overlap, usize::MAX - room[i], i)
+            })
+            .unwrap_or_else(|| (0..team_count).max_by_key(|&i| (room[i], usize::MAX - i)).unwrap());
+        room[best] = room[best].saturating_sub(size);
+        for c in &unit.categories {
+            *categories[best].entry(c.clone()).or_insert(0) += 1;
+        }
+        slots[best].extend(unit.members);
+    }
+
+    let mut teams = vec![];
+    let mut unassigned = vec![];
+    for members in slots {
+        if members.len() < 2 {
+            unassigned.extend(members.into_iter().cloned());
+            continue;
+        }
+        let leader_id = pick_leader(&members);
+        let mut members: Vec<UserDetail> = members.into_iter().cloned().collect();
+        members.sort_by_key(|m| (m.user.id != leader_id, m.user.id));
+        teams.push(PlannedTeam{
+            name: format!("Team {}", teams.len() + 1),
+            leader_id,
+            members,
+        });
+    }
+    Ok(finish(teams, unassigned))
+}
+
+fn build_units<'a>(solos: &[&'a UserDetail], together: &[Vec<Uuid>], max_size: usize) -> anyhow::Result<Vec<Unit<'a>>> {
+    let by_id: HashMap<Uuid, &UserDetail> = solos.iter().map(|u| (u.user.id, *u)).collect();
+    let mut grouped: HashSet<Uuid> = HashSet::new();
+    let mut units = vec![];
+    for group in together {
+        let mut members: Vec<&UserDetail> = group.iter()
+            .filter_map(|id| by_id.get(id).copied())
+            .filter(|u| grouped.insert(u.user.id))
+            .collect();
+        if members.len() > max_size {
+            return Err(anyhow!("A group of {} users can't fit into teams of {}", members.len(), max_size));
+        }
+        if members.is_empty() {
+            continue;
+        }
+        members.sort_by_key(|u| u.user.id);
+        units.push(members);
+    }
+    for u in solos {
+        if !grouped.contains(&u.user.id) {
+            units.push(vec![*u]);
+        }
+    }
+    let mut units: Vec<Unit> = units.into_iter()
+        .map(|members| {
+            let mut categories: Vec<String> = members.iter()
+                .filter_map(|m| primary_category(m))
+                .collect();
+            categories.sort();
+            Unit{members, categories}
+        })
+        .collect();
+    // Place big groups first, then spread each skill category round-robin
+    units.sort_by(|a, b| {
+        b.members.len().cmp(&a.members.len())
+            .then_with(|| a.categories.cmp(&b.categories))
+            .then_with(|| a.members[0].user.id.cmp(&b.members[0].user.id))
+    });
+    Ok(units)
+}
+
+// The category of the user's strongest skill
+fn primary_category(user: &UserDetail) -> Option<String> {
+    user.skills.iter()
+        .filter(|s| s.kind == "skill")
+        .filter_map(|s| s.category.as_ref().map(|c| (s.level, c)))
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(a.1)))
+        .map(|(_, c)| c.to_lowercase())
+}
+
+// The member with the most experience leads; ties go to the lowest id
+fn pick_leader(members: &[&UserDetail]) -> Uuid {
+    members.iter()
+        .max_by(|a, b| {
+            let level = |u: &UserDetail| u.skills.iter().map(|s| s.level).sum::<i32>();
+            level(a).cmp(&level(b)).then_with(|| b.user.id.cmp(&a.user.id))
+        })
+        .map(|u| u.user.id)
+        .unwrap()
+}
+
+fn finish(teams: Vec<PlannedTeam>, unassigned: Vec<UserDetail>) -> Plan {
+    let mut fingerprint = String::new();
+    for t in &teams {
+        fingerprint.push_str(&t.leader_id.to_string());
+        for m in &t.members {
+            fingerprint.push(',');
+            fingerprint.push_str(&m.user.id.to_string());
+        }
+        fingerprint.push(';');
+    }
+    for u in &unassigned {
+        fingerprint.push_str(&u.user.id.to_string());
+        fingerprint.push(',');
+    }
+    Plan{
+        plan_id: Uuid::new_v5(&PLAN_NAMESPACE, fingerprint.as_bytes()),
+        teams,
+        unassigned,
+    }
+}
+
+#[cfg(test)]
+mod formation_tests {
+    use super::*;
+    use crate::models::{User, UserSkillDetail};
+
+    fn user(n: u128, skills: Vec<(&str, i32)>) -> UserDetail {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let id = Uuid::from_u128(n);
+        UserDetail{
+            user: User{
+                id,
+                name: format!("user{}", n),
+                icon_url: None,
+                profile: None,
+                created_at: at,
+                updated_at: at,
+                display_name: None,
+                links: vec![],
+            },
+            skills: skills.into_iter()
+                .map(|(category, level)| UserSkillDetail{
+                    user_id: id,
+                    skill_id: Uuid::new_v5(&Uuid::NAMESPACE_OID, category.as_bytes()),
+                    name: category.to_string(),
+                    kind: "skill".to_string(),
+                    category: Some(category.to_string()),
+                    level,
+                })
+                .collect(),
+        }
+    }
+
+    fn ids(team: &PlannedTeam) -> Vec<u128> {
+        team.members.iter().map(|m| m.user.id.as_u128()).collect()
+    }
+
+    #[test]
+    fn balances_team_sizes() {
+        let solos: Vec<UserDetail> = (1..=7).map(|n| user(n, vec![])).collect();
+        let plan = plan(&solos, 3, &[]).unwrap();
+        let sizes: Vec<usize> = plan.teams.iter().map(|t| t.members.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 2]);
+        assert!(plan.unassigned.is_empty());
+    }
+
+    #[test]
+    fn spreads_skill_categories() {
+        let solos = vec![
+            user(1, vec![("frontend", 3)]),
+            user(2, vec![("frontend", 4)]),
+            user(3, vec![("backend", 2)]),
+            user(4, vec![("backend", 5)]),
+        ];
+        let plan = plan(&solos, 2, &[]).unwrap();
+        for team in &plan.teams {
+            let mut categories: Vec<String> = team.members.iter()
+                .map(|m| primary_category(m).unwrap())
+                .collect();
+            categories.sort();
+            assert_eq!(categories, vec!["backend".to_string(), "frontend".to_string()]);
+        }
+        // The most experienced member leads and is listed first
+        assert_eq!(plan.teams[0].leader_id, plan.teams[0].members[0].user.id);
+    }
+
+    #[test]
+    fn keeps_groups_together() {
+        let solos: Vec<UserDetail> = (1..=6).map(|n| user(n, vec![])).collect();
+        let together = vec![vec![Uuid::from_u128(1), Uuid::from_u128(6), Uuid::from_u128(99)]];
+        let plan = plan(&solos, 3, &together).unwrap();
+        assert!(plan.teams.iter().any(|t| {
+            let ids = ids(t);
+            ids.contains(&1) && ids.contains(&6)
+        }));
+        let too_big = vec![(1..=4).map(Uuid::from_u128).collect()];
+        assert!(super::plan(&solos, 3, &too_big).is_err());
+    }
+
+    #[test]
+    fn is_deterministic() {
+        let mut solos: Vec<UserDetail> = (1..=9).map(|n| user(n, vec![("ml", (n % 5) as i32 + 1)])).collect();
+        let first = plan(&solos, 4, &[]).unwrap();
+        solos.reverse();
+        let second = plan(&solos, 4, &[]).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn single_solo_stays_unassigned() {
+        let plan = plan(&[user(1, vec![])], 3, &[]).unwrap();
+        assert!(plan.teams.is_empty());
+        assert_eq!(plan.unassigned.len(), 1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{config, db};
+
+// Probes are retried by the orchestrator; better to answer fast than to wait
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+const GITHUB_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Fail,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Check {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    pub fn ok() -> Check {
+        Check{status: Status::Ok, error: None}
+    }
+
+    fn from_result(result: anyhow::Result<()>) -> Check {
+        match result {
+            Ok(_) => Check::ok(),
+            Err(e) => Check{status: Status::Fail, error: Some(format!("{:#}", e))},
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    pub status: Status,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Version {
+    pub version: &'static str,
+    pub commit: &'static str,
+    pub built_at: String,
+}
+
+pub fn version() -> Version {
+    let built_at = env!("BUILD_TIMESTAMP").parse().ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default();
+    Version{version: env!("CARGO_PKG_VERSION"), commit: env!("GIT_COMMIT"), built_at}
+}
+
+// Versions of the embedded migrations the database is missing
+fn check_database() -> anyhow::Result<Vec<String>> {
+    let conn = &mut db::pool()?.get_timeout(DATABASE_TIMEOUT)
+        .with_context(|| "Database unreachable")?;
+    db::pending_migrations(conn)
+}
+
+async fn check_github() -> anyhow::Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(GITHUB_TIMEOUT)
+        .build()?;
+    let res = client.get(&config::get().github.api_url)
+        .header(reqwest::header::USER_AGENT, "HotchPotch")
+        .send().await
+        .with_context(|| "GitHub unreachable")?;
+    // Rate limiting still means GitHub is up
+    if res.status().is_server_error() {
+        return Err(anyhow::anyhow!("GitHub responded {}", res.status()));
+    }
+    Ok(())
+}
+
+/// Whether this instance can serve requests
+pub async fn readiness() -> Readiness {
+    let mut checks = BTreeMap::new();
+    match db::block(check_database).await {
+        Ok(pending) => {
+            checks.insert("database", Check::ok());
+            let migrations = if pending.is_empty() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("{} pending: {}", pending.len(), pending.join(", ")))
+            };
+            checks.insert("migrations", Check::from_result(migrations));
+        },
+        Err(e) => {
+            checks.insert("database", Check::from_result(Err(e)));
+            checks.insert("migrations", Check::from_result(Err(anyhow::anyhow!("Database unreachable"))));
+        },
+    }
+    if config::get().health.check_github {
+        checks.insert("github", Check::from_result(check_github().await));
+    }
+    let status = if checks.values().all(|c| c.status == Status::Ok) { Status::Ok } else { Status::Fail };
+    Readiness{status, checks}
+}
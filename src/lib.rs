@@ -0,0 +1,28 @@
+// The match/return style is used deliberately throughout the handlers
+#![allow(clippy::needless_return, clippy::ptr_arg)]
+
+pub mod admin;
+pub mod auth;
+pub mod bus;
+pub mod chat;
+pub mod cli;
+pub mod config;
+pub mod cors;
+pub mod cruds;
+pub mod db;
+pub mod formation;
+pub mod health;
+pub mod mail;
+pub mod memory;
+pub mod metrics;
+pub mod models;
+pub mod outbound;
+pub mod pagination;
+pub mod recommend;
+pub mod repo;
+pub mod router;
+pub mod schema;
+pub mod seed;
+pub mod telemetry;
+pub mod validation;
+pub mod webhook;
@@ -0,0 +1,291 @@
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::Context;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::bus::NoticeKind;
+use crate::cruds;
+use crate::models::{EmailPreference, QueuedEmail};
+
+pub const LOCALES: [&str; 2] = ["en", "ja"];
+// Attempts before a message is given up on and left in the queue for inspection
+pub const MAX_ATTEMPTS: i32 = 5;
+const FIRST_RETRY_SECS: i32 = 30;
+const MAX_RETRY_SECS: i32 = 60 * 60;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 50;
+
+static CONFIG: OnceLock<MailConfig> = OnceLock::new();
+
+pub struct MailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    // Plain SMTP is only meant for a local sink such as MailHog
+    pub smtp_starttls: bool,
+    pub from: String,
+    // Base of the links put into emails
+    pub public_url: String,
+}
+
+impl MailConfig {
+    fn from_env() -> MailConfig {
+        MailConfig{
+            smtp_host: env::var("SMTP_HOST").unwrap_or("localhost".to_string()),
+            smtp_port: env::var("SMTP_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(25),
+            smtp_username: env::var("SMTP_USERNAME").ok(),
+            smtp_password: env::var("SMTP_PASSWORD").ok(),
+            smtp_starttls: env::var("SMTP_STARTTLS").map(|v| v == "true").unwrap_or(false),
+            from: env::var("MAIL_FROM").unwrap_or("hotchpotch <noreply@localhost>".to_string()),
+            public_url: env::var("PUBLIC_URL").unwrap_or("http://localhost:8080".to_string()),
+        }
+    }
+}
+
+pub fn config() -> &'static MailConfig {
+    CONFIG.get_or_init(MailConfig::from_env)
+}
+
+// What a message is about, already resolved to display names
+pub struct MailContext<'a> {
+    pub team_name: &'a str,
+    pub actor_name: &'a str,
+    pub unsubscribe_url: String,
+}
+
+pub struct Rendered {
+    pub subject: String,
+    pub body: String,
+}
+
+pub fn unsubscribe_url(pref: &EmailPreference) -> String {
+    format!("{}/api/v1/email/unsubscribe?token={}", config().public_url.trim_end_matches('/'), pref.unsubscribe_token)
+}
+
+pub fn verification_url(pref: &EmailPreference) -> String {
+    format!("{}/api/v1/email/verify?token={}", config().public_url.trim_end_matches('/'), pref.verification_token)
+}
+
+/// Render the message asking the owner of a new address to confirm it
+pub fn render_verification(locale: &str, url: &str) -> Rendered {
+    match locale {
+        "ja" => Rendered{
+            subject: "メールアドレスの確認".to_string(),
+            body: format!("hotchpotch の通知をこのアドレスで受け取るには、次のリンクを開いて確認してください。\n{}\n\n心当たりがない場合は、このメールを無視してください。これ以上メールは届きません。\n", url),
+        },
+        _ => Rendered{
+            subject: "Confirm your email address".to_string(),
+            body: format!("Open the link below to get hotchpotch notifications at this address.\n{}\n\nIf you didn't ask for this, ignore this email; nothing more will be sent.\n", url),
+        },
+    }
+}
+
+/// Whether the user asked for emails about this kind of notice
+pub fn wants(pref: &EmailPreference, kind: NoticeKind) -> bool {
+    match kind {
+        NoticeKind::RequestCreated | NoticeKind::RequestAccepted | NoticeKind::RequestDeclined => pref.on_request,
+        NoticeKind::MemberJoined | NoticeKind::MemberLeft => pref.on_join,
+        NoticeKind::TeamDisbanded => pref.on_team,
+    }
+}
+
+/// Render the message for `kind` in `locale`, falling back to English
+pub fn render(kind: NoticeKind, locale: &str, ctx: &MailContext) -> Rendered {
+    let team = ctx.team_name;
+    let actor = ctx.actor_name;
+    let (subject, text, footer) = match locale {
+        "ja" => {
+            let (subject, text) = match kind {
+                NoticeKind::RequestCreated => (
+                    format!("{}さんが「{}」への参加をリクエストしました", actor, team),
+                    format!("{}さんからチーム「{}」への参加リクエストが届きました。\nhotchpotch で承認または辞退してください。", actor, team),
+                ),
+                NoticeKind::RequestAccepted => (
+                    format!("「{}」への参加が承認されました", team),
+                    format!("チーム「{}」への参加リクエストが承認されました。", team),
+                ),
+                NoticeKind::RequestDeclined => (
+                    format!("「{}」への参加リクエストが辞退されました", team),
+                    format!("残念ながら、チーム「{}」への参加リクエストは辞退されました。", team),
+                ),
+                NoticeKind::MemberJoined => (
+                    format!("{}さんが「{}」に参加しました", actor, team),
+                    format!("{}さんがチーム「{}」に参加しました。", actor, team),
+                ),
+                NoticeKind::MemberLeft => (
+                    format!("{}さんが「{}」を抜けました", actor, team),
+                    format!("{}さんがチーム「{}」を抜けました。", actor, team),
+                ),
+                NoticeKind::TeamDisbanded => (
+                    format!("「{}」は解散しました", team),
+                    format!("チーム「{}」は{}さんによって解散されました。", team, actor),
+                ),
+            };
+            (subject, text, format!("このメールはメール通知を有効にしているため送信されています。\n配信停止: {}", ctx.unsubscribe_url))
+        },
+        _ => {
+            let (subject, text) = match kind {
+                NoticeKind::RequestCreated => (
+                    format!("{} wants to join {}", actor, team),
+                    format!("{} sent a request to join your team {}.\nOpen hotchpotch to accept or decline it.", actor, team),
+                ),
+                NoticeKind::RequestAccepted => (
+                    format!("You're in {}", team),
+                    format!("Your request to join {} was accepted.", team),
+                ),
+                NoticeKind::RequestDeclined => (
+                    format!("Your request to join {} was declined", team),
+                    format!("Unfortunately your request to join {} was declined.", team),
+                ),
+                NoticeKind::MemberJoined => (
+                    format!("{} joined {}", actor, team),
+                    format!("{} is now a member of {}.", actor, team),
+                ),
+                NoticeKind::MemberLeft => (
+                    format!("{} left {}", actor, team),
+                    format!("{} is no longer a member of {}.", actor, team),
+                ),
+                NoticeKind::TeamDisbanded => (
+                    format!("{} was disbanded", team),
+                    format!("{} disbanded the team {}.", actor, team),
+                ),
+            };
+            (subject, text, format!("You are receiving this because email notifications are on.\nUnsubscribe: {}", ctx.unsubscribe_url))
+        },
+    };
+    Rendered{subject, body: format!("{}\n\n--\n{}\n", text, footer)}
+}
+
+/// Seconds to wait before the next attempt once `attempts` have failed
+pub fn backoff(attempts: i32) -> i32 {
+    let exp = (attempts.max(1) - 1).min(16) as u32;
+    (FIRST_RETRY_SECS * 2_i32.pow(exp)).min(MAX_RETRY_SECS)
+}
+
+fn transport(config: &MailConfig) -> anyhow::Result<SmtpTransport> {
+    let mut builder = if config.smtp_starttls {
+        SmtpTransport::starttls_relay(&config.smtp_host)
+            .with_context(|| format!("Failed to set up TLS for {}", config.smtp_host))?
+    } else {
+        SmtpTransport::builder_dangerous(&config.smtp_host)
+    };
+    builder = builder.port(config.smtp_port);
+    if let (Some(user), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+        builder = builder.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+    Ok(builder.build())
+}
+
+fn send(mailer: &SmtpTransport, config: &MailConfig, email: &QueuedEmail) -> anyhow::Result<()> {
+    let message = Message::builder()
+        .from(config.from.parse().with_context(|| "Invalid MAIL_FROM")?)
+        .to(email.recipient.parse().with_context(|| "Invalid recipient")?)
+        .subject(email.subject.clone())
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body.clone())
+        .with_context(|| "Failed to build message")?;
+    mailer.send(&message).with_context(|| "Failed to send message")?;
+    Ok(())
+}
+
+/// Send every queued email that is due. Failures are rescheduled with backoff.
+pub fn deliver_pending() -> anyhow::Result<usize> {
+    let config = config();
+    let mailer = transport(config)?;
+    let due = cruds::get_due_emails(BATCH_SIZE)?;
+    let mut sent = 0;
+    for email in due {
+        match send(&mailer, config, &email) {
+            Ok(_) => {
+                cruds::mark_email_sent(&email.id)?;
+                sent += 1;
+            },
+            Err(e) => {
+                let attempts = email.attempts + 1;
+                cruds::mark_email_failed(&email.id, attempts, backoff(attempts), &format!("{:#}", e))?;
+            },
+        }
+    }
+    Ok(sent)
+}
+
+/// Poll the queue on a background thread. There is a single worker per
+/// process, so rows don't need to be locked while they are being sent.
+pub fn spawn_worker() {
+    std::thread::spawn(|| loop {
+        if let Err(e) = deliver_pending() {
+            tracing::warn!(error = format!("{:#}", e), "Email delivery failed");
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod mail_tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn context() -> MailContext<'static> {
+        MailContext{
+            team_name: "Rustaceans",
+            actor_name: "alice",
+            unsubscribe_url: "http://localhost:8080/api/v1/email/unsubscribe?token=t".to_string(),
+        }
+    }
+
+    #[test]
+    fn renders_verification_in_the_locale() {
+        let url = "http://localhost:8080/api/v1/email/verify?token=t";
+        let en = render_verification("en", url);
+        assert_eq!(en.subject, "Confirm your email address");
+        assert!(en.body.contains(url));
+        assert_eq!(render_verification("ja", url).subject, "メールアドレスの確認");
+        assert_eq!(render_verification("fr", url).subject, en.subject);
+    }
+
+    #[test]
+    fn renders_localized_templates() {
+        let en = render(NoticeKind::RequestCreated, "en", &context());
+        assert_eq!(en.subject, "alice wants to join Rustaceans");
+        assert!(en.body.ends_with("Unsubscribe: http://localhost:8080/api/v1/email/unsubscribe?token=t\n"));
+        let ja = render(NoticeKind::RequestAccepted, "ja", &context());
+        assert_eq!(ja.subject, "「Rustaceans」への参加が承認されました");
+        assert!(ja.body.contains("配信停止: "));
+        // Unknown locales get English
+        assert_eq!(render(NoticeKind::MemberLeft, "fr", &context()).subject, "alice left Rustaceans");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(1), 30);
+        assert_eq!(backoff(2), 60);
+        assert_eq!(backoff(4), 240);
+        assert_eq!(backoff(30), 3600);
+    }
+
+    #[test]
+    fn preferences_follow_notice_groups() {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let pref = EmailPreference{
+            user_id: Uuid::nil(),
+            email: "a@example.com".to_string(),
+            locale: "en".to_string(),
+            on_request: true,
+            on_join: false,
+            on_team: true,
+            unsubscribe_token: Uuid::nil(),
+            created_at: at,
+            updated_at: at,
+            verification_token: Uuid::nil(),
+            verified_at: Some(at),
+        };
+        assert!(wants(&pref, NoticeKind::RequestDeclined));
+        assert!(!wants(&pref, NoticeKind::MemberJoined));
+        assert!(wants(&pref, NoticeKind::TeamDisbanded));
+    }
+}
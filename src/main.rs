@@ -1,52 +1,100 @@
-use actix_cors::Cors;
-use actix_web::{
-    App,
-    HttpServer,
-    http::header,
-};
-use actix_web::middleware::Logger;
-use dotenv::dotenv;
-use env_logger::Env;
+use actix_web::{web, App, HttpServer};
+use clap::Parser;
 
-mod auth;
-mod cruds;
-mod db;
-mod models;
-mod router;
-mod schema;
+use hotchpotch_web_backend::{admin, chat, config, cors, db, mail, metrics, router, seed, telemetry, webhook};
+use hotchpotch_web_backend::cli::{Cli, Command};
+use hotchpotch_web_backend::repo::Repos;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    // Flags may come from .env too
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
     // Init
-    env_logger::init_from_env(Env::default().default_filter_or("debug"));
-    dotenv().ok();
-    HttpServer::new(||{
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000")
-            .allowed_methods(vec!["GET", "POST", "DELETE"])
-            .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
-            .allowed_header(header::CONTENT_TYPE)
-            .supports_credentials()
-            .max_age(3600);
+    let config = match config::init() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+    telemetry::init(config);
+    if let Err(e) = db::init_pool() {
+        tracing::error!(error = format!("{:#}", e), "Database unavailable");
+        std::process::exit(1);
+    }
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Migrate => {
+            migrate();
+            return Ok(());
+        },
+        Command::Seed(args) => {
+            match seed::run(&args) {
+                Ok(summary) => {
+                    println!("Seeded {} events, {} users, {} teams, {} solos and {} requests",
+                        summary.events, summary.users, summary.teams, summary.solos, summary.requests);
+                    println!("Mock tokens are in {}", args.tokens.display());
+                    if !config.github.mock_tokens {
+                        println!("Turn on github.mock_tokens to sign in with them");
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{:#}", e);
+                    std::process::exit(1);
+                },
+            }
+            return Ok(());
+        },
+        Command::Admin(command) => {
+            if let Err(e) = admin::run(command) {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        },
+        Command::Serve => {
+            if cli.migrate_on_start {
+                migrate();
+            }
+        },
+    }
+    if config.cors.dev_mode {
+        tracing::warn!("CORS dev mode is on; requests from any origin are accepted");
+    }
+    if config.github.mock_tokens {
+        tracing::warn!("Mock tokens are on; anyone can sign in as any user");
+    }
+    if config.features.email {
+        mail::spawn_worker();
+    }
+    if config.features.webhooks {
+        webhook::spawn_worker();
+    }
+    if config.features.chat {
+        chat::spawn_worker();
+    }
+    let repos = web::Data::new(Repos::diesel());
+    HttpServer::new(move ||{
         App::new()
-            .wrap(cors)
-            .wrap(Logger::default())
-            .service(router::index)
-            .service(router::create_user)
-            .service(router::get_user)
-            .service(router::create_event)
-            .service(router::get_event)
-            .service(router::delete_event)
-            .service(router::create_solo)
-            .service(router::get_solo)
-            .service(router::create_team)
-            .service(router::get_team)
-            .service(router::get_team_by_event)
-            .service(router::create_join)
-            .service(router::create_request)
-            .service(router::get_request)
+            .app_data(repos.clone())
+            .wrap(cors::build(&config::get().cors))
+            .wrap(metrics::RequestMetrics)
+            .wrap(telemetry::RequestTracing)
+            .configure(router::configure)
     })
-    .bind(("127.0.0.1", 8080))?
+    .bind((config.server.host.as_str(), config.server.port))?
     .run()
     .await
 }
+
+// Apply pending migrations, or stop: serving an outdated schema only fails later
+fn migrate() {
+    match db::run_migrations() {
+        Ok(applied) if applied.is_empty() => tracing::info!("Database is up to date"),
+        Ok(applied) => tracing::info!(versions = applied.join(", "), "Applied migrations"),
+        Err(e) => {
+            tracing::error!(error = format!("{:#}", e), "Migration failed");
+            std::process::exit(1);
+        },
+    }
+}
@@ -0,0 +1,729 @@
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::anyhow;
+use chrono::{NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::bus::{self, NoticeKind};
+use crate::cruds::{CrudError, EventFilter, EventStatus};
+use crate::models::{Event, Join, Notification, Request, Solo, Team, TeamDetail, User, UserDetail, WantedRole};
+use crate::pagination::{Order, Page, PageParams, SortKey};
+use crate::repo::{EventRepo, NotificationRepo, RequestRepo, TeamRepo, UserRepo};
+use crate::validation::FieldErrors;
+
+// Same as the column default in the event_search migration
+const DEFAULT_TEAM_CAPACITY: i32 = 5;
+
+// Storage kept in a Vec per table, for handler tests that don't need
+// Postgres. It checks primary and foreign keys, cascades deletes and
+// publishes notifications like cruds, but it is not a full stand-in:
+// there are no skills, so joining never fills a wanted role, and no
+// webhooks, emails or chat messages are queued. Tests of those stay on
+// Postgres.
+#[derive(Default)]
+pub struct MemoryRepo {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    users: Vec<User>,
+    events: Vec<Event>,
+    organizers: Vec<(Uuid, Uuid)>,
+    solos: Vec<Solo>,
+    teams: Vec<Team>,
+    wanted_roles: Vec<WantedRole>,
+    joins: Vec<Join>,
+    requests: Vec<Request>,
+    notifications: Vec<Notification>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> MemoryRepo {
+        MemoryRepo::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // A test that panicked mid-change can't leave anything worth protecting
+        match self.state.lock() {
+            Ok(s) => s,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl State {
+    fn user(&self, user_id: &Uuid) -> anyhow::Result<&User> {
+        match self.users.iter().find(|u| u.id == *user_id) {
+            Some(u) => return Ok(u),
+            None => return Err(anyhow!("Record not found")),
+        }
+    }
+
+    fn team(&self, team_id: &Uuid) -> anyhow::Result<&Team> {
+        match self.teams.iter().find(|t| t.id == *team_id) {
+            Some(t) => return Ok(t),
+            None => return Err(anyhow!("Record not found")),
+        }
+    }
+
+    fn require_user(&self, user_id: &Uuid, table: &str) -> anyhow::Result<()> {
+        if !self.users.iter().any(|u| u.id == *user_id) {
+            return Err(anyhow!("insert or update on table \"{}\" violates foreign key constraint on user_id", table));
+        }
+        Ok(())
+    }
+
+    fn require_event(&self, event_id: &Uuid, table: &str) -> anyhow::Result<()> {
+        if !self.events.iter().any(|e| e.id == *event_id) {
+            return Err(anyhow!("insert or update on table \"{}\" violates foreign key constraint on event_id", table));
+        }
+        Ok(())
+    }
+
+    fn require_team(&self, team_id: &Uuid, table: &str) -> anyhow::Result<()> {
+        if !self.teams.iter().any(|t| t.id == *team_id) {
+            return Err(anyhow!("insert or update on table \"{}\" violates foreign key constraint on team_id", table));
+        }
+        Ok(())
+    }
+
+    // The leader followed by every member who joined
+    fn team_member_ids(&self, team_id: &Uuid) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = self.teams.iter()
+            .filter(|t| t.id == *team_id)
+            .map(|t| t.reader_id)
+            .collect();
+        let mut joined: Vec<&Join> = self.joins.iter().filter(|j| j.team_id == *team_id).collect();
+        joined.sort_by_key(|j| j.created_at);
+        ids.extend(joined.iter().map(|j| j.user_id));
+        ids
+    }
+
+    fn wanted_roles(&self, team_id: &Uuid) -> Vec<WantedRole> {
+        let mut roles: Vec<WantedRole> = self.wanted_roles.iter()
+            .filter(|r| r.team_id == *team_id)
+            .cloned()
+            .collect();
+        roles.sort_by(|a, b| a.role.cmp(&b.role));
+        roles
+    }
+
+    fn insert_join(&mut self, team_id: &Uuid, user_id: &Uuid) -> anyhow::Result<()> {
+        let capacity = match self.team(team_id) {
+            Ok(t) => t.capacity,
+            Err(_) => return Err(CrudError::NotFound("No such team".to_string()).into()),
+        };
+        if self.team_member_ids(team_id).len() as i32 >= capacity {
+            return Err(CrudError::Conflict("The team is full".to_string()).into());
+        }
+        self.require_user(user_id, "joins")?;
+        if self.joins.iter().any(|j| j.team_id == *team_id && j.user_id == *user_id) {
+            return Err(anyhow!("duplicate key value violates unique constraint \"joins_pkey\""));
+        }
+        let at = now();
+        self.joins.push(Join{team_id: *team_id, user_id: *user_id, created_at: at, updated_at: at, filled_role: None});
+        Ok(())
+    }
+
+    fn notify(&mut self, recipients: &[Uuid], kind: NoticeKind, team_id: &Uuid, actor_id: &Uuid) -> Vec<Notification> {
+        let at = now();
+        let notifications: Vec<Notification> = recipients.iter()
+            .map(|user_id| Notification{
+                id: Uuid::new_v4(),
+                user_id: *user_id,
+                kind: kind.as_str().to_string(),
+                team_id: *team_id,
+                actor_id: *actor_id,
+                read_at: None,
+                created_at: at,
+                updated_at: at,
+            })
+            .collect();
+        self.notifications.extend(notifications.iter().cloned());
+        notifications
+    }
+}
+
+impl UserRepo for MemoryRepo {
+    fn create_user(&self, name: &String, icon_url: &String, profile: &String) -> anyhow::Result<User> {
+        let at = now();
+        let user = User{
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            icon_url: Some(icon_url.clone()),
+            profile: Some(profile.clone()),
+            created_at: at,
+            updated_at: at,
+            display_name: None,
+            links: vec![],
+        };
+        self.state().users.push(user.clone());
+        Ok(user)
+    }
+
+    fn get_user_by_name(&self, name: &String) -> anyhow::Result<User> {
+        match self.state().users.iter().find(|u| u.name == *name) {
+            Some(u) => return Ok(u.clone()),
+            None => return Err(anyhow!("Record not found")),
+        }
+    }
+
+    fn get_user_by_id(&self, user_id: &String) -> anyhow::Result<User> {
+        let found = Uuid::parse_str(user_id).ok().and_then(|id| self.state().user(&id).ok().cloned());
+        found.ok_or_else(|| CrudError::NotFound("No such user".to_string()).into())
+    }
+
+    fn get_user_detail(&self, user: User) -> anyhow::Result<UserDetail> {
+        Ok(UserDetail{user, skills: vec![]})
+    }
+
+    fn update_user_profile(
+        &self,
+        user_id: &String,
+        profile: Option<&String>,
+        display_name: Option<&String>,
+        links: Option<&Vec<Option<String>>>
+    ) -> anyhow::Result<User> {
+        let user_id = parse_uuid(user_id)?;
+        let mut state = self.state();
+        let user = match state.users.iter_mut().find(|u| u.id == user_id) {
+            Some(u) => u,
+            None => return Err(anyhow!("Failed to update user: Record not found")),
+        };
+        if profile.is_none() && display_name.is_none() && links.is_none() {
+            return Ok(user.clone());
+        }
+        if let Some(profile) = profile {
+            user.profile = Some(profile.clone());
+        }
+        if let Some(display_name) = display_name {
+            user.display_name = Some(display_name.clone());
+        }
+        if let Some(links) = links {
+            user.links = links.clone();
+        }
+        user.updated_at = now();
+        Ok(user.clone())
+    }
+}
+
+impl EventRepo for MemoryRepo {
+    fn create_event(
+        &self,
+        name: &String,
+        desc: &String,
+        url: &String,
+        started_at: Option<&NaiveDateTime>,
+        ended_at: Option<&NaiveDateTime>,
+        organizer_id: &String
+    ) -> anyhow::Result<Event> {
+        let organizer_id = parse_uuid(organizer_id)?;
+        let mut state = self.state();
+        state.require_user(&organizer_id, "event_organizers")?;
+        let at = now();
+        let event = Event{
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            desc: Some(desc.clone()),
+            url: Some(url.clone()),
+            created_at: at,
+            updated_at: at,
+            started_at: started_at.cloned(),
+            ended_at: ended_at.cloned(),
+        };
+        state.events.push(event.clone());
+        state.organizers.push((event.id, organizer_id));
+        Ok(event)
+    }
+
+    fn get_event_list(&self, filter: &EventFilter, page: &PageParams) -> anyhow::Result<Page<Event>> {
+        let state = self.state();
+        let at = now();
+        let mut events: Vec<Event> = state.events.iter()
+            .filter(|e| matches_filter(&state, e, filter, &at))
+            .cloned()
+            .collect();
+        match page.sort {
+            SortKey::CreatedAt => events.sort_by_key(|e| (e.created_at, e.id)),
+            SortKey::Name => events.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id))),
+        }
+        Ok(paginate(events, page))
+    }
+
+    fn is_event_organizer(&self, event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+        let event_id = parse_uuid(event_id)?;
+        Ok(self.state().organizers.contains(&(event_id, *user_id)))
+    }
+
+    fn delete_event(&self, event_id: &String) -> anyhow::Result<()> {
+        let event_id = parse_uuid(event_id)?;
+        let mut state = self.state();
+        let team_ids: Vec<Uuid> = state.teams.iter()
+            .filter(|t| t.event_id == event_id)
+            .map(|t| t.id)
+            .collect();
+        state.wanted_roles.retain(|r| !team_ids.contains(&r.team_id));
+        state.requests.retain(|r| !team_ids.contains(&r.team_id));
+        state.joins.retain(|j| !team_ids.contains(&j.team_id));
+        state.teams.retain(|t| t.event_id != event_id);
+        state.solos.retain(|s| s.event_id != event_id);
+        state.organizers.retain(|(e, _)| *e != event_id);
+        state.events.retain(|e| e.id != event_id);
+        Ok(())
+    }
+
+    fn create_solo(&self, event_id: &String, user_id: &String) -> anyhow::Result<()> {
+        let event_id = parse_uuid(event_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let mut state = self.state();
+        state.require_event(&event_id, "solos")?;
+        state.require_user(&user_id, "solos")?;
+        if state.solos.iter().any(|s| s.event_id == event_id && s.user_id == user_id) {
+            return Err(anyhow!("Failed to insert new_solo: duplicate key value violates unique constraint \"solos_pkey\""));
+        }
+        let at = now();
+        state.solos.push(Solo{event_id, user_id, created_at: at, updated_at: at});
+        Ok(())
+    }
+
+    fn get_solos(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<UserDetail>> {
+        let event_id = parse_uuid(event_id)?;
+        let state = self.state();
+        let mut solos: Vec<(NaiveDateTime, User)> = vec![];
+        for solo in state.solos.iter().filter(|s| s.event_id == event_id) {
+            solos.push((solo.created_at, state.user(&solo.user_id)?.clone()));
+        }
+        // created_at is the time the user registered as solo, not the account creation time
+        match page.sort {
+            SortKey::CreatedAt => solos.sort_by(|(a_at, a), (b_at, b)| (a_at, a.id).cmp(&(b_at, b.id))),
+            SortKey::Name => solos.sort_by(|(_, a), (_, b)| (&a.name, a.id).cmp(&(&b.name, b.id))),
+        }
+        let users: Vec<UserDetail> = solos.into_iter()
+            .map(|(_, user)| UserDetail{user, skills: vec![]})
+            .collect();
+        Ok(paginate(users, page))
+    }
+}
+
+impl TeamRepo for MemoryRepo {
+    fn create_team(&self, event_id: &String, leader_id: &String, name: &String, desc: &String, capacity: Option<&i32>) -> anyhow::Result<Team> {
+        let event_id = parse_uuid(event_id)?;
+        let leader_id = parse_uuid(leader_id)?;
+        let mut state = self.state();
+        state.require_event(&event_id, "teams")?;
+        state.require_user(&leader_id, "teams")?;
+        let at = now();
+        let team = Team{
+            id: Uuid::new_v4(),
+            event_id,
+            reader_id: leader_id,
+            name: name.clone(),
+            desc: Some(desc.clone()),
+            created_at: at,
+            updated_at: at,
+            capacity: capacity.cloned().unwrap_or(DEFAULT_TEAM_CAPACITY),
+        };
+        state.teams.push(team.clone());
+        Ok(team)
+    }
+
+    fn get_team(&self, team_id: &String) -> anyhow::Result<Team> {
+        let found = Uuid::parse_str(team_id).ok().and_then(|id| self.state().team(&id).ok().cloned());
+        found.ok_or_else(|| CrudError::NotFound("No such team".to_string()).into())
+    }
+
+    fn get_team_detail(&self, team: Team) -> anyhow::Result<TeamDetail> {
+        let wanted_roles = self.state().wanted_roles(&team.id);
+        Ok(TeamDetail{team, wanted_roles})
+    }
+
+    fn get_teams_by_event(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<TeamDetail>> {
+        let event_id = parse_uuid(event_id)?;
+        let state = self.state();
+        let mut teams: Vec<Team> = state.teams.iter()
+            .filter(|t| t.event_id == event_id)
+            .cloned()
+            .collect();
+        match page.sort {
+            SortKey::CreatedAt => teams.sort_by_key(|t| (t.created_at, t.id)),
+            SortKey::Name => teams.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id))),
+        }
+        let teams: Vec<TeamDetail> = teams.into_iter()
+            .map(|team| {
+                let wanted_roles = state.wanted_roles(&team.id);
+                TeamDetail{team, wanted_roles}
+            })
+            .collect();
+        Ok(paginate(teams, page))
+    }
+
+    fn delete_team(&self, team_id: &String, actor_id: &Uuid) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        let notifications = {
+            let mut state = self.state();
+            let members = others(state.team_member_ids(&team_id), actor_id);
+            let notifications = state.notify(&members, NoticeKind::TeamDisbanded, &team_id, actor_id);
+            state.wanted_roles.retain(|r| r.team_id != team_id);
+            state.requests.retain(|r| r.team_id != team_id);
+            state.joins.retain(|j| j.team_id != team_id);
+            state.teams.retain(|t| t.id != team_id);
+            notifications
+        };
+        publish_all(notifications);
+        Ok(())
+    }
+
+    fn set_wanted_role(&self, team_id: &String, role: &String, count: &i32) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        let mut state = self.state();
+        state.require_team(&team_id, "team_wanted_roles")?;
+        if *count <= 0 {
+            return Err(anyhow!("Failed to upsert team_wanted_role: violates check constraint on count"));
+        }
+        let at = now();
+        match state.wanted_roles.iter_mut().find(|r| r.team_id == team_id && r.role == *role) {
+            Some(r) if *count < r.filled => {
+                let mut errors = FieldErrors::new();
+                errors.add("count", format!("must be at least {}, the members already filling the role", r.filled));
+                return Err(CrudError::Invalid(errors).into());
+            },
+            Some(r) => {
+                r.count = *count;
+                r.updated_at = at;
+            },
+            None => state.wanted_roles.push(WantedRole{team_id, role: role.clone(), count: *count, filled: 0, created_at: at, updated_at: at}),
+        }
+        Ok(())
+    }
+
+    fn delete_wanted_role(&self, team_id: &String, role: &String) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        self.state().wanted_roles.retain(|r| !(r.team_id == team_id && r.role == *role));
+        Ok(())
+    }
+
+    fn create_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let notifications = {
+            let mut state = self.state();
+            state.insert_join(&team_id, &user_id)?;
+            let members = others(state.team_member_ids(&team_id), &user_id);
+            state.notify(&members, NoticeKind::MemberJoined, &team_id, &user_id)
+        };
+        publish_all(notifications);
+        Ok(())
+    }
+
+    fn delete_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let notifications = {
+            let mut state = self.state();
+            state.joins.retain(|j| !(j.team_id == team_id && j.user_id == user_id));
+            let members = others(state.team_member_ids(&team_id), &user_id);
+            state.notify(&members, NoticeKind::MemberLeft, &team_id, &user_id)
+        };
+        publish_all(notifications);
+        Ok(())
+    }
+}
+
+impl RequestRepo for MemoryRepo {
+    fn create_request(&self, team_id: &String, user_id: &String, message: &String) -> anyhow::Result<()> {
+        let team_id = parse_uuid(team_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let notifications = {
+            let mut state = self.state();
+            if !state.teams.iter().any(|t| t.id == team_id) {
+                return Err(CrudError::NotFound("No such team".to_string()).into());
+            }
+            state.require_user(&user_id, "requests")?;
+            let at = now();
+            let joined = state.joins.iter().any(|j| j.team_id == team_id && j.user_id == user_id);
+            match state.requests.iter_mut().find(|r| r.team_id == team_id && r.user_id == user_id) {
+                None => state.requests.push(Request{
+                    team_id,
+                    user_id,
+                    message: Some(message.clone()),
+                    created_at: at,
+                    updated_at: at,
+                    status: "pending".to_string(),
+                }),
+                Some(request) if request.status == "declined" || (request.status == "accepted" && !joined) => {
+                    request.status = "pending".to_string();
+                    request.message = Some(message.clone());
+                    request.updated_at = at;
+                },
+                Some(_) => return Err(CrudError::Conflict("You already asked to join this team".to_string()).into()),
+            }
+            let leader_id = state.team(&team_id)?.reader_id;
+            state.notify(&[leader_id], NoticeKind::RequestCreated, &team_id, &user_id)
+        };
+        publish_all(notifications);
+        Ok(())
+    }
+
+    fn accept_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+        let team_id = parse_uuid(team_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let notifications = {
+            let mut state = self.state();
+            let index = match pending_request(&state, &team_id, &user_id) {
+                Some(i) => i,
+                None => return Ok(false),
+            };
+            // Join first: when that fails nothing else may have changed
+            state.insert_join(&team_id, &user_id)?;
+            state.requests[index].status = "accepted".to_string();
+            state.requests[index].updated_at = now();
+            let event_id = state.team(&team_id)?.event_id;
+            state.solos.retain(|s| !(s.event_id == event_id && s.user_id == user_id));
+            let mut notifications = state.notify(&[user_id], NoticeKind::RequestAccepted, &team_id, &user_id);
+            let members = others(state.team_member_ids(&team_id), &user_id);
+            notifications.extend(state.notify(&members, NoticeKind::MemberJoined, &team_id, &user_id));
+            notifications
+        };
+        publish_all(notifications);
+        Ok(true)
+    }
+
+    fn decline_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+        let team_id = parse_uuid(team_id)?;
+        let user_id = parse_uuid(user_id)?;
+        let notifications = {
+            let mut state = self.state();
+            let index = match pending_request(&state, &team_id, &user_id) {
+                Some(i) => i,
+                None => return Ok(false),
+            };
+            state.requests[index].status = "declined".to_string();
+            state.requests[index].updated_at = now();
+            state.notify(&[user_id], NoticeKind::RequestDeclined, &team_id, &user_id)
+        };
+        publish_all(notifications);
+        Ok(true)
+    }
+
+    fn get_requests_by_user(&self, user_id: &String, page: &PageParams) -> anyhow::Result<Page<Request>> {
+        let user_id = parse_uuid(user_id)?;
+        let mut requests: Vec<Request> = self.state().requests.iter()
+            .filter(|r| r.user_id == user_id)
+            .cloned()
+            .collect();
+        // Requests have no name, so they can only be ordered by creation time
+        requests.sort_by_key(|r| (r.created_at, r.team_id));
+        Ok(paginate(requests, page))
+    }
+}
+
+impl NotificationRepo for MemoryRepo {
+    fn get_notifications(&self, user_id: &Uuid, unread_only: bool, page: &PageParams) -> anyhow::Result<Page<Notification>> {
+        let mut notifications: Vec<Notification> = self.state().notifications.iter()
+            .filter(|n| n.user_id == *user_id)
+            .filter(|n| !unread_only || n.read_at.is_none())
+            .cloned()
+            .collect();
+        notifications.sort_by_key(|n| (n.created_at, n.id));
+        Ok(paginate(notifications, page))
+    }
+
+    fn count_unread_notifications(&self, user_id: &Uuid) -> anyhow::Result<i64> {
+        let state = self.state();
+        Ok(state.notifications.iter().filter(|n| n.user_id == *user_id && n.read_at.is_none()).count() as i64)
+    }
+
+    fn mark_notification_read(&self, user_id: &Uuid, notification_id: &String) -> anyhow::Result<bool> {
+        let notification_id = parse_uuid(notification_id)?;
+        let mut state = self.state();
+        match state.notifications.iter_mut().find(|n| n.id == notification_id && n.user_id == *user_id) {
+            Some(n) => {
+                n.read_at = Some(now());
+                return Ok(true);
+            },
+            None => return Ok(false),
+        }
+    }
+
+    fn mark_all_notifications_read(&self, user_id: &Uuid) -> anyhow::Result<usize> {
+        let at = now();
+        let mut state = self.state();
+        let mut updated = 0;
+        for n in state.notifications.iter_mut().filter(|n| n.user_id == *user_id && n.read_at.is_none()) {
+            n.read_at = Some(at);
+            updated += 1;
+        }
+        Ok(updated)
+    }
+}
+
+fn matches_filter(state: &State, event: &Event, filter: &EventFilter, at: &NaiveDateTime) -> bool {
+    if let Some(q) = &filter.q {
+        let q = q.trim().to_lowercase();
+        let in_name = event.name.to_lowercase().contains(&q);
+        let in_desc = event.desc.as_ref().is_some_and(|d| d.to_lowercase().contains(&q));
+        if !in_name && !in_desc {
+            return false;
+        }
+    }
+    if let Some(from) = filter.from {
+        if event.started_at.is_none_or(|s| s < from.and_hms_opt(0, 0, 0).unwrap()) {
+            return false;
+        }
+    }
+    if let Some(to) = filter.to {
+        // `to` is inclusive, so compare against the start of the following day
+        let next_day = to.succ_opt().unwrap_or(to).and_hms_opt(0, 0, 0).unwrap();
+        if event.started_at.is_none_or(|s| s >= next_day) {
+            return false;
+        }
+    }
+    let matches_status = match filter.status {
+        Some(EventStatus::Upcoming) => event.started_at.is_some_and(|s| s > *at),
+        Some(EventStatus::Ongoing) => {
+            event.started_at.is_some_and(|s| s <= *at) && event.ended_at.is_none_or(|e| e >= *at)
+        },
+        Some(EventStatus::Finished) => event.ended_at.is_some_and(|e| e < *at),
+        None => true,
+    };
+    if !matches_status {
+        return false;
+    }
+    if let Some(has_open) = filter.has_open_teams {
+        // A team is open while the leader plus its members are below capacity
+        let open = state.teams.iter()
+            .filter(|t| t.event_id == event.id)
+            .any(|t| t.capacity as usize > 1 + state.joins.iter().filter(|j| j.team_id == t.id).count());
+        if open != has_open {
+            return false;
+        }
+    }
+    true
+}
+
+fn pending_request(state: &State, team_id: &Uuid, user_id: &Uuid) -> Option<usize> {
+    state.requests.iter()
+        .position(|r| r.team_id == *team_id && r.user_id == *user_id && r.status == "pending")
+}
+
+// `rows` sorted ascending, as a page in the requested order
+fn paginate<T>(mut rows: Vec<T>, page: &PageParams) -> Page<T> {
+    if page.order == Order::Desc {
+        rows.reverse();
+    }
+    let total = rows.len() as i64;
+    let items: Vec<T> = rows.into_iter()
+        .skip(page.offset as usize)
+        .take(page.limit as usize)
+        .collect();
+    Page::new(items, total, page)
+}
+
+fn publish_all(notifications: Vec<Notification>) {
+    for n in notifications {
+        bus::publish(n);
+    }
+}
+
+fn others(members: Vec<Uuid>, user_id: &Uuid) -> Vec<Uuid> {
+    members.into_iter().filter(|m| m != user_id).collect()
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+fn parse_uuid(str_uuid: &str) -> anyhow::Result<Uuid> {
+    match Uuid::parse_str(str_uuid) {
+        Ok(uuid) => return Ok(uuid),
+        Err(e) => return Err(anyhow!("{}", e)),
+    };
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+    use crate::pagination::PageQuery;
+
+    fn page() -> PageParams {
+        PageQuery{limit: None, cursor: None, sort: None, order: None}
+            .parse(&[SortKey::CreatedAt, SortKey::Name])
+            .unwrap()
+    }
+
+    // octo leads a team of the event and hubot is solo
+    fn seeded() -> (MemoryRepo, User, User, Team) {
+        let repo = MemoryRepo::new();
+        let empty = String::new();
+        let octo = repo.create_user(&"octo".to_string(), &empty, &empty).unwrap();
+        let hubot = repo.create_user(&"hubot".to_string(), &empty, &empty).unwrap();
+        let event = repo.create_event(&"Jam".to_string(), &empty, &empty, None, None, &octo.id.to_string()).unwrap();
+        let team = repo.create_team(&event.id.to_string(), &octo.id.to_string(), &"Crabs".to_string(), &empty, None).unwrap();
+        repo.create_solo(&event.id.to_string(), &hubot.id.to_string()).unwrap();
+        (repo, octo, hubot, team)
+    }
+
+    #[test]
+    fn enforces_keys() {
+        let (repo, octo, hubot, team) = seeded();
+        let team_id = team.id.to_string();
+        let stranger = Uuid::new_v4().to_string();
+        assert!(repo.create_solo(&team.event_id.to_string(), &hubot.id.to_string()).is_err());
+        assert!(repo.create_join(&team_id, &stranger).is_err());
+        assert!(repo.create_join(&Uuid::new_v4().to_string(), &hubot.id.to_string()).is_err());
+        assert!(repo.create_join(&"not-a-team".to_string(), &hubot.id.to_string()).is_err());
+        repo.create_join(&team_id, &hubot.id.to_string()).unwrap();
+        assert!(repo.create_join(&team_id, &hubot.id.to_string()).is_err());
+        assert_eq!(repo.get_team(&team_id).unwrap().capacity, DEFAULT_TEAM_CAPACITY);
+        assert!(repo.set_wanted_role(&team_id, &"design".to_string(), &0).is_err());
+        assert_eq!(repo.count_unread_notifications(&octo.id).unwrap(), 1);
+    }
+
+    #[test]
+    fn only_answers_pending_requests() {
+        let (repo, octo, hubot, team) = seeded();
+        let team_id = team.id.to_string();
+        let hubot_id = hubot.id.to_string();
+        assert!(!repo.accept_request(&team_id, &hubot_id).unwrap());
+        repo.create_request(&team_id, &hubot_id, &"Hi".to_string()).unwrap();
+        let e = repo.create_request(&team_id, &hubot_id, &"Hi".to_string()).unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(CrudError::Conflict(_))));
+        assert!(repo.accept_request(&team_id, &hubot_id).unwrap());
+        assert!(!repo.accept_request(&team_id, &hubot_id).unwrap());
+        assert!(!repo.decline_request(&team_id, &hubot_id).unwrap());
+
+        // The accepted user joined and is no longer solo
+        assert_eq!(repo.get_solos(&team.event_id.to_string(), &page()).unwrap().total, 0);
+        assert!(repo.create_join(&team_id, &hubot_id).is_err());
+        let requests = repo.get_requests_by_user(&hubot_id, &page()).unwrap();
+        assert_eq!(requests.items[0].status, "accepted");
+        let kinds: Vec<String> = repo.get_notifications(&octo.id, false, &page()).unwrap()
+            .items.into_iter().map(|n| n.kind).collect();
+        assert_eq!(kinds, vec!["request_created", "member_joined"]);
+        assert_eq!(repo.get_notifications(&hubot.id, false, &page()).unwrap().items[0].kind, "request_accepted");
+    }
+
+    #[test]
+    fn deletes_cascade() {
+        let (repo, octo, hubot, team) = seeded();
+        let team_id = team.id.to_string();
+        let event_id = team.event_id.to_string();
+        repo.set_wanted_role(&team_id, &"backend".to_string(), &2).unwrap();
+        repo.create_join(&team_id, &hubot.id.to_string()).unwrap();
+        repo.delete_team(&team_id, &octo.id).unwrap();
+        assert!(repo.get_team(&team_id).is_err());
+        assert_eq!(repo.get_notifications(&hubot.id, true, &page()).unwrap().items[0].kind, "team_disbanded");
+        assert_eq!(repo.mark_all_notifications_read(&hubot.id).unwrap(), 1);
+        assert_eq!(repo.count_unread_notifications(&hubot.id).unwrap(), 0);
+
+        repo.create_team(&event_id, &octo.id.to_string(), &"Again".to_string(), &String::new(), None).unwrap();
+        assert!(repo.is_event_organizer(&event_id, &octo.id).unwrap());
+        assert!(!repo.is_event_organizer(&event_id, &hubot.id).unwrap());
+        repo.delete_event(&event_id).unwrap();
+        assert!(!repo.is_event_organizer(&event_id, &octo.id).unwrap());
+        assert_eq!(repo.get_teams_by_event(&event_id, &page()).unwrap().total, 0);
+        assert_eq!(repo.get_solos(&event_id, &page()).unwrap().total, 0);
+        assert_eq!(repo.get_event_list(&EventFilter::default(), &page()).unwrap().total, 0);
+    }
+}
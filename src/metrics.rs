@@ -0,0 +1,231 @@
+use std::future::{ready, Ready};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use crate::{cruds, db};
+
+// Label for requests that didn't match any route, so scanners can't blow up
+// the number of series
+const UNMATCHED_ROUTE: &str = "unmatched";
+// Put in front of every metric name by the registry
+const PREFIX: &str = "hotchpotch";
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    github_duration: Histogram,
+    token_cache: IntCounterVec,
+    pool_connections: IntGauge,
+    pool_idle: IntGauge,
+    events: IntGauge,
+    teams: IntGauge,
+    solos: IntGauge,
+    pending_requests: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some(PREFIX.to_string()), None)
+            .expect("Prefix is valid");
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        ).unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to respond to HTTP requests"),
+            &["method", "route"],
+        ).unwrap();
+        let github_duration = Histogram::with_opts(
+            HistogramOpts::new("github_verification_duration_seconds", "Time GitHub took to verify an access token"),
+        ).unwrap();
+        let token_cache = IntCounterVec::new(
+            Opts::new("token_cache_lookups_total", "Access token cache lookups by result"),
+            &["result"],
+        ).unwrap();
+        let gauge = |name: &str, help: &str| IntGauge::new(name, help).unwrap();
+        let metrics = Metrics{
+            registry,
+            http_requests,
+            http_duration,
+            github_duration,
+            token_cache,
+            pool_connections: gauge("db_pool_connections", "Connections held by the database pool"),
+            pool_idle: gauge("db_pool_idle_connections", "Idle connections in the database pool"),
+            events: gauge("events", "Events"),
+            teams: gauge("teams", "Teams"),
+            solos: gauge("solos", "Participants looking for a team"),
+            pending_requests: gauge("pending_requests", "Join requests waiting for an answer"),
+        };
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_duration.clone()),
+            Box::new(metrics.github_duration.clone()),
+            Box::new(metrics.token_cache.clone()),
+            Box::new(metrics.pool_connections.clone()),
+            Box::new(metrics.pool_idle.clone()),
+            Box::new(metrics.events.clone()),
+            Box::new(metrics.teams.clone()),
+            Box::new(metrics.solos.clone()),
+            Box::new(metrics.pending_requests.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).expect("Metric names are unique");
+        }
+        metrics
+    }
+}
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+pub fn observe_github_verification(elapsed: Duration) {
+    metrics().github_duration.observe(elapsed.as_secs_f64());
+}
+
+pub fn count_token_cache(hit: bool) {
+    metrics().token_cache.with_label_values(&[if hit { "hit" } else { "miss" }]).inc();
+}
+
+/// Text exposition of every metric. Pool and domain gauges are sampled now;
+/// when the database can't be reached they are left out, so the rest still
+/// gets scraped and the gauges don't report stale values.
+pub fn render() -> anyhow::Result<String> {
+    let m = metrics();
+    let mut skipped: Vec<&IntGauge> = vec![];
+    match db::pool() {
+        Ok(pool) => {
+            let state = pool.state();
+            m.pool_connections.set(state.connections as i64);
+            m.pool_idle.set(state.idle_connections as i64);
+        },
+        Err(e) => {
+            tracing::warn!(error = format!("{:#}", e), "Skipped database pool metrics");
+            skipped.extend([&m.pool_connections, &m.pool_idle]);
+        },
+    }
+    match cruds::count_totals() {
+        Ok(totals) => {
+            m.events.set(totals.events);
+            m.teams.set(totals.teams);
+            m.solos.set(totals.solos);
+            m.pending_requests.set(totals.pending_requests);
+        },
+        Err(e) => {
+            tracing::warn!(error = format!("{:#}", e), "Skipped domain metrics");
+            skipped.extend([&m.events, &m.teams, &m.solos, &m.pending_requests]);
+        },
+    }
+    encode_except(&m.registry, &skipped)
+}
+
+fn encode_except(registry: &Registry, skipped: &[&IntGauge]) -> anyhow::Result<String> {
+    let names: Vec<String> = skipped.iter()
+        .flat_map(|g| g.desc().into_iter().map(|d| format!("{}_{}", PREFIX, d.fq_name)))
+        .collect();
+    let families: Vec<_> = registry.gather().into_iter()
+        .filter(|f| !names.iter().any(|n| n == f.name()))
+        .collect();
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&families, &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Middleware counting requests and timing responses per route. The route
+/// is the pattern it was registered with, such as `/api/v1/teams/{team_id}`.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware{service}))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let route = res.request().match_pattern().unwrap_or(UNMATCHED_ROUTE.to_string());
+            let m = metrics();
+            m.http_requests
+                .with_label_values(&[method.as_str(), route.as_str(), res.status().as_str()])
+                .inc();
+            m.http_duration
+                .with_label_values(&[method.as_str(), route.as_str()])
+                .observe(started.elapsed().as_secs_f64());
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn counts_requests_by_route_pattern() {
+        let app = init_service(
+            App::new()
+                .wrap(RequestMetrics)
+                .route("/metrics-test/{id}", web::get().to(HttpResponse::Ok))
+        ).await;
+        for uri in ["/metrics-test/1", "/metrics-test/2", "/metrics-test-missing"] {
+            call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        }
+        let m = metrics();
+        assert_eq!(m.http_requests.with_label_values(&["GET", "/metrics-test/{id}", "200"]).get(), 2);
+        assert!(m.http_requests.with_label_values(&["GET", UNMATCHED_ROUTE, "404"]).get() >= 1);
+        assert_eq!(m.http_duration.with_label_values(&["GET", "/metrics-test/{id}"]).get_sample_count(), 2);
+    }
+
+    #[test]
+    fn leaves_out_gauges_that_could_not_be_sampled() {
+        let m = metrics();
+        let text = encode_except(&m.registry, &[&m.teams, &m.pool_idle]).unwrap();
+        assert!(text.contains("hotchpotch_events "));
+        assert!(!text.contains("hotchpotch_teams "));
+        assert!(!text.contains("hotchpotch_db_pool_idle_connections "));
+    }
+}
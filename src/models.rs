@@ -1,12 +1,68 @@
 use chrono::NaiveDateTime;
-use diesel::prelude::{Queryable, Selectable, Insertable};
+use diesel::prelude::{AsChangeset, Queryable, Selectable, Insertable};
 use diesel_derives::Identifiable;
 use serde::Serialize;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::schema::*;
 
-#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone)]
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = email_preferences)]
+pub struct EmailPreference {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub email: String,
+    pub locale: String,
+    pub on_request: bool,
+    pub on_join: bool,
+    pub on_team: bool,
+    // Only ever sent inside emails
+    #[serde(skip)]
+    pub unsubscribe_token: Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    #[serde(skip)]
+    pub verification_token: Uuid,
+    // Nothing is mailed until the address is verified
+    pub verified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = email_preferences)]
+pub struct  NewEmailPreference<'a> {
+    pub user_id: &'a Uuid,
+    pub email: &'a String,
+    pub locale: &'a String,
+    pub on_request: bool,
+    pub on_join: bool,
+    pub on_team: bool,
+}
+
+#[derive(Queryable, Selectable, PartialEq, Debug, Clone)]
+#[diesel(table_name = email_queue)]
+pub struct QueuedEmail {
+    pub id: Uuid,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = email_queue)]
+pub struct  NewQueuedEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = events)]
 pub struct Event {
     pub id: Uuid,
@@ -15,6 +71,8 @@ pub struct Event {
     pub url: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub ended_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -23,15 +81,50 @@ pub struct  NewEvent<'a> {
     pub name: &'a String,
     pub desc: &'a String,
     pub url: &'a String,
+    pub started_at: Option<&'a NaiveDateTime>,
+    pub ended_at: Option<&'a NaiveDateTime>,
+}
+
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = event_integrations)]
+pub struct EventIntegration {
+    pub event_id: Uuid,
+    pub platform: String,
+    pub webhook_url: String,
+    pub notify_recruiting: bool,
+    pub notify_full: bool,
+    pub notify_solos: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = event_integrations)]
+pub struct  NewEventIntegration<'a> {
+    pub event_id: &'a Uuid,
+    pub platform: &'a String,
+    pub webhook_url: &'a String,
+    pub notify_recruiting: bool,
+    pub notify_full: bool,
+    pub notify_solos: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = event_organizers)]
+pub struct  NewEventOrganizer<'a> {
+    pub event_id: &'a Uuid,
+    pub user_id: &'a Uuid,
 }
 
-#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone)]
+#[allow(dead_code)]
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = joins)]
 pub struct Join {
     pub team_id: Uuid,
     pub user_id: Uuid,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub filled_role: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -41,7 +134,29 @@ pub struct  NewJoin<'a> {
     pub user_id: &'a Uuid,
 }
 
-#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone)]
+#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = notifications)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub team_id: Uuid,
+    pub actor_id: Uuid,
+    pub read_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = notifications)]
+pub struct  NewNotification<'a> {
+    pub user_id: &'a Uuid,
+    pub kind: &'a str,
+    pub team_id: &'a Uuid,
+    pub actor_id: &'a Uuid,
+}
+
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = requests)]
 pub struct Request {
     pub team_id: Uuid,
@@ -49,6 +164,7 @@ pub struct Request {
     pub message: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub status: String,
 }
 
 #[derive(Insertable)]
@@ -59,7 +175,7 @@ pub struct  NewRequest<'a> {
     pub message: &'a String,
 }
 
-#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone)]
+#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = users)]
 pub struct User {
     pub id: Uuid,
@@ -68,6 +184,8 @@ pub struct User {
     pub profile: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub display_name: Option<String>,
+    pub links: Vec<Option<String>>,
 }
 
 #[derive(Insertable)]
@@ -78,7 +196,72 @@ pub struct  NewUser<'a> {
     pub profile: &'a String
 }
 
-#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone)]
+// None fields are left untouched
+#[derive(AsChangeset)]
+#[diesel(table_name = users)]
+pub struct  UpdateUser<'a> {
+    pub profile: Option<&'a String>,
+    pub display_name: Option<&'a String>,
+    pub links: Option<&'a Vec<Option<String>>>,
+}
+
+// User with the skills and interests they registered
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct UserDetail {
+    #[serde(flatten)]
+    pub user: User,
+    pub skills: Vec<UserSkillDetail>,
+}
+
+// The signed-in user, with what only they get to see
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct CurrentUserDetail {
+    #[serde(flatten)]
+    pub user: UserDetail,
+    pub unread_notifications: i64,
+}
+
+#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = skills)]
+pub struct Skill {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub category: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = skills)]
+pub struct  NewSkill<'a> {
+    pub name: &'a String,
+    pub kind: &'a String,
+    pub category: Option<&'a String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_skills)]
+pub struct  NewUserSkill<'a> {
+    pub user_id: &'a Uuid,
+    pub skill_id: &'a Uuid,
+    pub level: &'a i32,
+}
+
+// A row of user_skills joined with its skill
+#[derive(Queryable, Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct UserSkillDetail {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub skill_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub category: Option<String>,
+    pub level: i32,
+}
+
+#[allow(dead_code)]
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = solos)]
 pub struct Solo {
     pub event_id: Uuid,
@@ -94,7 +277,7 @@ pub struct  NewSolo<'a> {
     pub user_id: &'a Uuid,
 }
 
-#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone)]
+#[derive(Queryable, Serialize, Identifiable, Selectable, PartialEq, Debug, Clone, ToSchema)]
 #[diesel(table_name = teams)]
 pub struct Team {
     pub id: Uuid,
@@ -104,6 +287,7 @@ pub struct Team {
     pub desc: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub capacity: i32,
 }
 
 #[derive(Insertable)]
@@ -113,4 +297,94 @@ pub struct  NewTeam<'a> {
     pub reader_id: &'a Uuid,
     pub name: &'a String,
     pub desc: &'a String,
+    pub capacity: Option<&'a i32>,
+}
+
+// Team with the roles it is still looking for
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct TeamDetail {
+    #[serde(flatten)]
+    pub team: Team,
+    pub wanted_roles: Vec<WantedRole>,
+}
+
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = team_wanted_roles)]
+pub struct WantedRole {
+    #[serde(skip)]
+    pub team_id: Uuid,
+    pub role: String,
+    pub count: i32,
+    pub filled: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl WantedRole {
+    pub fn remaining(&self) -> i32 {
+        (self.count - self.filled).max(0)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = team_wanted_roles)]
+pub struct  NewWantedRole<'a> {
+    pub team_id: &'a Uuid,
+    pub role: &'a String,
+    pub count: &'a i32,
+}
+
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct  NewWebhookDelivery<'a> {
+    pub id: &'a Uuid,
+    pub subscription_id: &'a Uuid,
+    pub event_type: &'a str,
+    pub payload: &'a String,
+}
+
+#[derive(Queryable, Serialize, Selectable, PartialEq, Debug, Clone, ToSchema)]
+#[diesel(table_name = webhook_subscriptions)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub url: String,
+    // Only shown once, when the subscription is created
+    #[serde(skip)]
+    pub secret: String,
+    pub event_types: Vec<Option<String>>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct CreatedWebhookSubscription {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = webhook_subscriptions)]
+pub struct  NewWebhookSubscription<'a> {
+    pub event_id: &'a Uuid,
+    pub url: &'a String,
+    pub secret: &'a String,
+    pub event_types: &'a Vec<Option<String>>,
 }
@@ -0,0 +1,99 @@
+// Organizers hand us webhook and chat URLs that the server then posts to.
+// These checks keep those posts away from the server's own network: hosts
+// resolving to loopback, private, link-local or other non-public addresses
+// are refused, unless outbound.allow_private_networks is on for local
+// development.
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use reqwest::Url;
+
+use crate::config;
+
+/// Whether `ip` is an address on the public internet
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // 100.64.0.0/10, shared address space for carrier-grade NAT
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        },
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+/// Resolve the host of `url`, failing when any of its addresses isn't public.
+/// Returns the host with the addresses that were checked.
+pub fn resolve(url: &str) -> anyhow::Result<(String, Vec<SocketAddr>)> {
+    resolve_allowing(url, config::get().outbound.allow_private_networks)
+}
+
+fn resolve_allowing(url: &str, allow_private_networks: bool) -> anyhow::Result<(String, Vec<SocketAddr>)> {
+    let parsed = Url::parse(url).with_context(|| "is not a valid URL")?;
+    let host = parsed.host_str().ok_or_else(|| anyhow!("has no host"))?.to_string();
+    let port = parsed.port_or_known_default().ok_or_else(|| anyhow!("has no port"))?;
+    let addrs: Vec<SocketAddr> = (host.trim_start_matches('[').trim_end_matches(']'), port)
+        .to_socket_addrs()
+        .with_context(|| format!("host {} could not be resolved", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("host {} could not be resolved", host));
+    }
+    if !allow_private_networks {
+        if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+            return Err(anyhow!("host {} resolves to {}, which is not a public address", host, addr.ip()));
+        }
+    }
+    Ok((host, addrs))
+}
+
+/// Client for posting to `url`, checked with `resolve` and pinned to the
+/// addresses it returned so a DNS change can't send the post elsewhere.
+/// Redirects aren't followed for the same reason.
+pub fn client(url: &str, timeout: Duration) -> anyhow::Result<reqwest::blocking::Client> {
+    let (host, addrs) = resolve(url)?;
+    reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(&host, &addrs)
+        .build()
+        .with_context(|| "Failed to build HTTP client")
+}
+
+#[cfg(test)]
+mod outbound_tests {
+    use super::*;
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.215.14", "100.128.0.1", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn resolves_only_public_hosts() {
+        assert!(resolve_allowing("https://93.184.215.14/hook", false).is_ok());
+        assert!(resolve_allowing("http://127.0.0.1:8080/hook", false).is_err());
+        assert!(resolve_allowing("http://[::1]/hook", false).is_err());
+        assert!(resolve_allowing("http://localhost/hook", false).is_err());
+        assert!(resolve_allowing("http://localhost/hook", true).is_ok());
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::anyhow;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+// Query parameters shared by every list endpoint
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortKey {
+    CreatedAt,
+    Name,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PageParams {
+    pub limit: i64,
+    pub offset: i64,
+    pub sort: SortKey,
+    pub order: Order,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: i64,
+}
+
+impl SortKey {
+    fn parse(s: &str) -> anyhow::Result<SortKey> {
+        match s {
+            "created_at" => Ok(SortKey::CreatedAt),
+            "name" => Ok(SortKey::Name),
+            _ => Err(anyhow!("Unknown sort key: {}", s)),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::CreatedAt => "created_at",
+            SortKey::Name => "name",
+        }
+    }
+}
+
+impl Order {
+    fn parse(s: &str) -> anyhow::Result<Order> {
+        match s {
+            "asc" => Ok(Order::Asc),
+            "desc" => Ok(Order::Desc),
+            _ => Err(anyhow!("Unknown order: {}", s)),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+}
+
+impl PageQuery {
+    /// Validate the raw query against the sort keys a list supports.
+    /// The first entry of `allowed` is used when `sort` is omitted.
+    pub fn parse(&self, allowed: &[SortKey]) -> anyhow::Result<PageParams> {
+        self.parse_with_order(allowed, Order::Asc)
+    }
+
+    /// Same as `parse`, for lists that default to another order
+    pub fn parse_with_order(&self, allowed: &[SortKey], default_order: Order) -> anyhow::Result<PageParams> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(anyhow!("limit must be between 1 and {}", MAX_LIMIT));
+        }
+        let sort = match &self.sort {
+            Some(s) => SortKey::parse(s)?,
+            None => allowed[0],
+        };
+        if !allowed.contains(&sort) {
+            return Err(anyhow!("Unsupported sort key: {}", sort.as_str()));
+        }
+        let order = match &self.order {
+            Some(o) => Order::parse(o)?,
+            None => default_order,
+        };
+        let offset = match &self.cursor {
+            Some(c) => decode_cursor(c, sort, order)?,
+            None => 0,
+        };
+        Ok(PageParams{limit, offset, sort, order})
+    }
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: i64, params: &PageParams) -> Page<T> {
+        let next_offset = params.offset + items.len() as i64;
+        let next_cursor = if !items.is_empty() && next_offset < total {
+            Some(encode_cursor(next_offset, params.sort, params.order))
+        } else {
+            None
+        };
+        Page{items, next_cursor, total}
+    }
+}
+
+// Cursors are opaque to clients; they carry the sort they were issued for
+// so that a cursor can't be replayed against a different ordering.
+fn encode_cursor(offset: i64, sort: SortKey, order: Order) -> String {
+    let raw = format!("{}:{}:{}", sort.as_str(), order.as_str(), offset);
+    URL_SAFE_NO_PAD.encode(raw)
+}
+
+fn decode_cursor(cursor: &str, sort: SortKey, order: Order) -> anyhow::Result<i64> {
+    let raw = URL_SAFE_NO_PAD.decode(cursor)
+        .map_err(|_| anyhow!("Malformed cursor"))?;
+    let raw = String::from_utf8(raw)
+        .map_err(|_| anyhow!("Malformed cursor"))?;
+    let parts: Vec<&str> = raw.splitn(3, ':').collect();
+    if parts.len() != 3 {
+        return Err(anyhow!("Malformed cursor"));
+    }
+    if parts[0] != sort.as_str() || parts[1] != order.as_str() {
+        return Err(anyhow!("Cursor does not match sort order"));
+    }
+    match parts[2].parse::<i64>() {
+        Ok(offset) if offset >= 0 => Ok(offset),
+        _ => Err(anyhow!("Malformed cursor")),
+    }
+}
+
+#[cfg(test)]
+mod pagination_tests {
+    use super::*;
+
+    fn query(limit: Option<i64>, cursor: Option<String>, sort: Option<&str>, order: Option<&str>) -> PageQuery {
+        PageQuery{
+            limit,
+            cursor,
+            sort: sort.map(|s| s.to_string()),
+            order: order.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let p = query(None, None, None, None)
+            .parse(&[SortKey::CreatedAt, SortKey::Name])
+            .unwrap();
+        assert_eq!(p, PageParams{limit: DEFAULT_LIMIT, offset: 0, sort: SortKey::CreatedAt, order: Order::Asc});
+    }
+
+    #[test]
+    fn rejects_unsupported_sort_and_limit() {
+        assert!(query(None, None, Some("name"), None).parse(&[SortKey::CreatedAt]).is_err());
+        assert!(query(Some(0), None, None, None).parse(&[SortKey::CreatedAt]).is_err());
+        assert!(query(Some(MAX_LIMIT + 1), None, None, None).parse(&[SortKey::CreatedAt]).is_err());
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let params = PageParams{limit: 2, offset: 0, sort: SortKey::Name, order: Order::Desc};
+        let page = Page::new(vec![1, 2], 5, &params);
+        let cursor = page.next_cursor.expect("next_cursor should be set");
+        let next = query(Some(2), Some(cursor.clone()), Some("name"), Some("desc"))
+            .parse(&[SortKey::CreatedAt, SortKey::Name])
+            .unwrap();
+        assert_eq!(next.offset, 2);
+        // Replaying the cursor with another ordering is refused
+        assert!(query(Some(2), Some(cursor), Some("name"), Some("asc"))
+            .parse(&[SortKey::CreatedAt, SortKey::Name])
+            .is_err());
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        let params = PageParams{limit: 2, offset: 4, sort: SortKey::CreatedAt, order: Order::Asc};
+        let page = Page::new(vec![5], 5, &params);
+        assert!(page.next_cursor.is_none());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{TeamDetail, UserDetail};
+
+// Weights of each score component; they add up to 1
+const ROLE_WEIGHT: f64 = 0.6;
+const SIZE_WEIGHT: f64 = 0.25;
+const INTEREST_WEIGHT: f64 = 0.15;
+// Role fit of a team that hasn't listed any wanted roles
+const NEUTRAL_ROLE_FIT: f64 = 0.5;
+const MAX_SKILL_LEVEL: f64 = 5.0;
+
+// Team as seen by the scorer: its wanted roles plus everyone already in it
+pub struct TeamCandidate<'a> {
+    pub team: &'a TeamDetail,
+    pub members: &'a [UserDetail],
+}
+
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct ScoreBreakdown {
+    pub role_fit: f64,
+    pub size_gap: f64,
+    pub shared_interests: f64,
+}
+
+#[derive(Serialize, PartialEq, Debug, Clone, ToSchema)]
+pub struct Recommendation<T> {
+    pub candidate: T,
+    pub score: f64,
+    pub breakdown: ScoreBreakdown,
+    pub matched_roles: Vec<String>,
+}
+
+pub struct Scored {
+    pub score: f64,
+    pub breakdown: ScoreBreakdown,
+    pub matched_roles: Vec<String>,
+}
+
+/// Score how well `user` fits `team`, or None if the team has no room left.
+/// The score is in 0..=100.
+pub fn score(user: &UserDetail, team: &TeamCandidate) -> Option<Scored> {
+    let capacity = team.team.team.capacity as f64;
+    let size = team.members.len() as f64;
+    if size >= capacity {
+        return None;
+    }
+
+    // Best level per skill category of the user
+    let mut levels: HashMap<String, i32> = HashMap::new();
+    for s in user.skills.iter().filter(|s| s.kind == "skill") {
+        if let Some(category) = &s.category {
+            let level = levels.entry(category.to_lowercase()).or_insert(0);
+            *level = (*level).max(s.level);
+        }
+    }
+
+    let open_roles: Vec<&String> = team.team.wanted_roles.iter()
+        .filter(|r| r.remaining() > 0)
+        .map(|r| &r.role)
+        .collect();
+    let mut matched_roles = vec![];
+    let role_fit = if open_roles.is_empty() {
+        NEUTRAL_ROLE_FIT
+    } else {
+        let mut total = 0.0;
+        for role in &open_roles {
+            if let Some(level) = levels.get(&role.to_lowercase()) {
+                total += *level as f64 / MAX_SKILL_LEVEL;
+                matched_roles.push(role.to_string());
+            }
+        }
+        total / open_roles.len() as f64
+    };
+
+    // Teams that still need more people rank higher
+    let size_gap = if capacity > 1.0 {
+        ((capacity - size) / (capacity - 1.0)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let user_interests = interests(std::slice::from_ref(user));
+    let team_interests = interests(team.members);
+    let shared_interests = jaccard(&user_interests, &team_interests);
+
+    let score = 100.0 * (ROLE_WEIGHT * role_fit + SIZE_WEIGHT * size_gap + INTEREST_WEIGHT * shared_interests);
+    Some(Scored{
+        score,
+        breakdown: ScoreBreakdown{role_fit, size_gap, shared_interests},
+        matched_roles,
+    })
+}
+
+/// Rank the teams `user` could join, best first
+pub fn teams_for_user(user: &UserDetail, teams: &[(TeamDetail, Vec<UserDetail>)]) -> Vec<Recommendation<TeamDetail>> {
+    let mut result: Vec<Recommendation<TeamDetail>> = teams.iter()
+        .filter(|(_, members)| members.iter().all(|m| m.user.id != user.user.id))
+        .filter_map(|(team, members)| {
+            let scored = score(user, &TeamCandidate{team, members})?;
+            Some(Recommendation{
+                candidate: team.clone(),
+                score: scored.score,
+                breakdown: scored.breakdown,
+                matched_roles: scored.matched_roles,
+            })
+        })
+        .collect();
+    sort(&mut result, |t| t.team.id);
+    result
+}
+
+/// Rank the solo participants that could join `team`, best first
+pub fn solos_for_team(team: &TeamDetail, members: &[UserDetail], solos: &[UserDetail]) -> Vec<Recommendation<UserDetail>> {
+    let candidate = TeamCandidate{team, members};
+    let mut result: Vec<Recommendation<UserDetail>> = solos.iter()
+        .filter(|s| members.iter().all(|m| m.user.id != s.user.id))
+        .filter_map(|user| {
+            let scored = score(user, &candidate)?;
+            Some(Recommendation{
+                candidate: user.clone(),
+                score: scored.score,
+                breakdown: scored.breakdown,
+                matched_roles: scored.matched_roles,
+            })
+        })
+        .collect();
+    sort(&mut result, |u| u.user.id);
+    result
+}
+
+// Highest score first, ties broken by id so the order is stable
+fn sort<T>(result: &mut [Recommendation<T>], id: fn(&T) -> Uuid) {
+    result.sort_by(|a, b| {
+        b.score.total_cmp(&a.score).then_with(|| id(&a.candidate).cmp(&id(&b.candidate)))
+    });
+}
+
+fn interests(users: &[UserDetail]) -> BTreeSet<Uuid> {
+    users.iter()
+        .flat_map(|u| u.skills.iter())
+        .filter(|s| s.kind == "interest")
+        .map(|s| s.skill_id)
+        .collect()
+}
+
+fn jaccard(a: &BTreeSet<Uuid>, b: &BTreeSet<Uuid>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod recommend_tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use crate::models::{Team, User, UserSkillDetail, WantedRole};
+
+    fn at() -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc()
+    }
+
+    fn user(n: u128, skills: Vec<(&str, Option<&str>, i32)>) -> UserDetail {
+        let id = Uuid::from_u128(n);
+        let skills = skills.into_iter()
+            .map(|(name, category, level)| UserSkillDetail{
+                user_id: id,
+                skill_id: Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes()),
+                name: name.to_string(),
+                kind: if category.is_some() { "skill" } else { "interest" }.to_string(),
+                category: category.map(|c| c.to_string()),
+                level,
+            })
+            .collect();
+        UserDetail{
+            user: User{
+                id,
+                name: format!("user{}", n),
+                icon_url: None,
+                profile: None,
+                created_at: at(),
+                updated_at: at(),
+                display_name: None,
+                links: vec![],
+            },
+            skills,
+        }
+    }
+
+    fn team(n: u128, capacity: i32, roles: Vec<(&str, i32, i32)>) -> TeamDetail {
+        let id = Uuid::from_u128(n);
+        TeamDetail{
+            team: Team{
+                id,
+                event_id: Uuid::nil(),
+                reader_id: Uuid::nil(),
+                name: format!("team{}", n),
+                desc: None,
+                created_at: at(),
+                updated_at: at(),
+                capacity,
+            },
+            wanted_roles: roles.into_iter()
+                .map(|(role, count, filled)| WantedRole{
+                    team_id: id,
+                    role: role.to_string(),
+                    count,
+                    filled,
+                    created_at: at(),
+                    updated_at: at(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn scores_role_size_and_interest_components() {
+        let solo = user(1, vec![("Rust", Some("backend"), 5), ("React", Some("frontend"), 2), ("AI", None, 1)]);
+        let leader = user(2, vec![("AI", None, 1), ("Game", None, 1)]);
+        let t = team(10, 4, vec![("backend", 1, 0), ("designer", 1, 0), ("frontend", 1, 1)]);
+        let members = vec![leader];
+        let scored = score(&solo, &TeamCandidate{team: &t, members: &members}).unwrap();
+        // backend matched at level 5, designer unmatched, frontend already filled
+        assert_eq!(scored.breakdown.role_fit, 0.5);
+        assert_eq!(scored.breakdown.size_gap, 1.0);
+        assert_eq!(scored.breakdown.shared_interests, 0.5);
+        assert_eq!(scored.matched_roles, vec!["backend".to_string()]);
+        assert!((scored.score - 62.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn full_teams_are_skipped() {
+        let solo = user(1, vec![]);
+        let t = team(10, 2, vec![]);
+        let members = vec![user(2, vec![]), user(3, vec![])];
+        assert!(score(&solo, &TeamCandidate{team: &t, members: &members}).is_none());
+    }
+
+    #[test]
+    fn teams_for_user_is_sorted_and_deterministic() {
+        let solo = user(1, vec![("Figma", Some("designer"), 4)]);
+        let teams = vec![
+            (team(12, 4, vec![("backend", 1, 0)]), vec![user(2, vec![])]),
+            (team(11, 4, vec![("designer", 1, 0)]), vec![user(3, vec![])]),
+            (team(13, 4, vec![("backend", 1, 0)]), vec![user(4, vec![])]),
+            // The user's own team is never recommended
+            (team(14, 4, vec![("designer", 1, 0)]), vec![user(1, vec![])]),
+        ];
+        let ids: Vec<Uuid> = teams_for_user(&solo, &teams).iter().map(|r| r.candidate.team.id).collect();
+        assert_eq!(ids, vec![Uuid::from_u128(11), Uuid::from_u128(12), Uuid::from_u128(13)]);
+    }
+
+    #[test]
+    fn solos_for_team_prefers_wanted_skills() {
+        let t = team(10, 3, vec![("ml", 1, 0)]);
+        let members = vec![user(2, vec![])];
+        let solos = vec![
+            user(5, vec![("Rust", Some("backend"), 5)]),
+            user(4, vec![("PyTorch", Some("ml"), 2)]),
+            user(3, vec![("PyTorch", Some("ml"), 5)]),
+        ];
+        let ids: Vec<Uuid> = solos_for_team(&t, &members, &solos).iter().map(|r| r.candidate.user.id).collect();
+        assert_eq!(ids, vec![Uuid::from_u128(3), Uuid::from_u128(4), Uuid::from_u128(5)]);
+    }
+}
@@ -0,0 +1,266 @@
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::cruds::{self, EventFilter};
+use crate::db;
+use crate::memory::MemoryRepo;
+use crate::models::{Event, Notification, Request, Team, TeamDetail, User, UserDetail};
+use crate::pagination::{Page, PageParams};
+
+// Storage the handlers go through, so they can run against Postgres or
+// against memory in tests. IDs come in as strings the way clients send
+// them; a malformed one is an error, as with the crud functions.
+//
+// TODO: skills, email preferences, webhooks, chat integrations,
+// recommendations and team formation still go through db::block and
+// cruds, so their handlers need Postgres in tests. Give them SkillRepo,
+// WebhookRepo, IntegrationRepo and FormationRepo here and route those
+// handlers through Repos too.
+
+pub trait UserRepo: Send + Sync {
+    fn create_user(&self, name: &String, icon_url: &String, profile: &String) -> anyhow::Result<User>;
+    fn get_user_by_name(&self, name: &String) -> anyhow::Result<User>;
+    fn get_user_by_id(&self, user_id: &String) -> anyhow::Result<User>;
+    fn get_user_detail(&self, user: User) -> anyhow::Result<UserDetail>;
+    fn update_user_profile(
+        &self,
+        user_id: &String,
+        profile: Option<&String>,
+        display_name: Option<&String>,
+        links: Option<&Vec<Option<String>>>
+    ) -> anyhow::Result<User>;
+}
+
+pub trait EventRepo: Send + Sync {
+    // The organizer organizes the event from the start
+    fn create_event(
+        &self,
+        name: &String,
+        desc: &String,
+        url: &String,
+        started_at: Option<&NaiveDateTime>,
+        ended_at: Option<&NaiveDateTime>,
+        organizer_id: &String
+    ) -> anyhow::Result<Event>;
+    fn get_event_list(&self, filter: &EventFilter, page: &PageParams) -> anyhow::Result<Page<Event>>;
+    fn is_event_organizer(&self, event_id: &String, user_id: &Uuid) -> anyhow::Result<bool>;
+    // Along with its teams, solos and organizers
+    fn delete_event(&self, event_id: &String) -> anyhow::Result<()>;
+    fn create_solo(&self, event_id: &String, user_id: &String) -> anyhow::Result<()>;
+    fn get_solos(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<UserDetail>>;
+}
+
+pub trait TeamRepo: Send + Sync {
+    fn create_team(&self, event_id: &String, leader_id: &String, name: &String, desc: &String, capacity: Option<&i32>) -> anyhow::Result<Team>;
+    fn get_team(&self, team_id: &String) -> anyhow::Result<Team>;
+    fn get_team_detail(&self, team: Team) -> anyhow::Result<TeamDetail>;
+    fn get_teams_by_event(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<TeamDetail>>;
+    // Along with its wanted roles, requests and members; the others hear about it
+    fn delete_team(&self, team_id: &String, actor_id: &Uuid) -> anyhow::Result<()>;
+    fn set_wanted_role(&self, team_id: &String, role: &String, count: &i32) -> anyhow::Result<()>;
+    fn delete_wanted_role(&self, team_id: &String, role: &String) -> anyhow::Result<()>;
+    fn create_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()>;
+    fn delete_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()>;
+}
+
+pub trait RequestRepo: Send + Sync {
+    fn create_request(&self, team_id: &String, user_id: &String, message: &String) -> anyhow::Result<()>;
+    // Both return false when there was no pending request
+    fn accept_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool>;
+    fn decline_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool>;
+    fn get_requests_by_user(&self, user_id: &String, page: &PageParams) -> anyhow::Result<Page<Request>>;
+}
+
+pub trait NotificationRepo: Send + Sync {
+    fn get_notifications(&self, user_id: &Uuid, unread_only: bool, page: &PageParams) -> anyhow::Result<Page<Notification>>;
+    fn count_unread_notifications(&self, user_id: &Uuid) -> anyhow::Result<i64>;
+    // Returns false when the user has no such notification
+    fn mark_notification_read(&self, user_id: &Uuid, notification_id: &String) -> anyhow::Result<bool>;
+    fn mark_all_notifications_read(&self, user_id: &Uuid) -> anyhow::Result<usize>;
+}
+
+/// Every repository, as the handlers get them from app data
+#[derive(Clone)]
+pub struct Repos {
+    pub users: Arc<dyn UserRepo>,
+    pub events: Arc<dyn EventRepo>,
+    pub teams: Arc<dyn TeamRepo>,
+    pub requests: Arc<dyn RequestRepo>,
+    pub notifications: Arc<dyn NotificationRepo>,
+}
+
+impl Repos {
+    pub fn new<R>(repo: R) -> Repos
+    where
+        R: UserRepo + EventRepo + TeamRepo + RequestRepo + NotificationRepo + 'static,
+    {
+        let repo = Arc::new(repo);
+        Repos{
+            users: repo.clone(),
+            events: repo.clone(),
+            teams: repo.clone(),
+            requests: repo.clone(),
+            notifications: repo,
+        }
+    }
+
+    /// Postgres, through the crud functions
+    pub fn diesel() -> Repos {
+        Repos::new(DieselRepo)
+    }
+
+    /// Empty storage that lives as long as the value
+    pub fn memory() -> Repos {
+        Repos::new(MemoryRepo::new())
+    }
+
+    /// Call the repositories from an async handler without blocking its worker
+    pub async fn run<F, T>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&Repos) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let repos = self.clone();
+        db::block(move || f(&repos)).await
+    }
+}
+
+pub struct DieselRepo;
+
+impl UserRepo for DieselRepo {
+    fn create_user(&self, name: &String, icon_url: &String, profile: &String) -> anyhow::Result<User> {
+        cruds::create_user(name, icon_url, profile)
+    }
+
+    fn get_user_by_name(&self, name: &String) -> anyhow::Result<User> {
+        cruds::get_user_info_by_name(name)
+    }
+
+    fn get_user_by_id(&self, user_id: &String) -> anyhow::Result<User> {
+        cruds::get_user_info_by_id(user_id)
+    }
+
+    fn get_user_detail(&self, user: User) -> anyhow::Result<UserDetail> {
+        cruds::get_user_detail(user)
+    }
+
+    fn update_user_profile(
+        &self,
+        user_id: &String,
+        profile: Option<&String>,
+        display_name: Option<&String>,
+        links: Option<&Vec<Option<String>>>
+    ) -> anyhow::Result<User> {
+        cruds::update_user_profile(user_id, profile, display_name, links)
+    }
+}
+
+impl EventRepo for DieselRepo {
+    fn create_event(
+        &self,
+        name: &String,
+        desc: &String,
+        url: &String,
+        started_at: Option<&NaiveDateTime>,
+        ended_at: Option<&NaiveDateTime>,
+        organizer_id: &String
+    ) -> anyhow::Result<Event> {
+        cruds::create_event(name, desc, url, started_at, ended_at, organizer_id)
+    }
+
+    fn get_event_list(&self, filter: &EventFilter, page: &PageParams) -> anyhow::Result<Page<Event>> {
+        cruds::get_event_list(filter, page)
+    }
+
+    fn is_event_organizer(&self, event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+        cruds::is_event_organizer(event_id, user_id)
+    }
+
+    fn delete_event(&self, event_id: &String) -> anyhow::Result<()> {
+        cruds::delete_event_by_id(event_id)
+    }
+
+    fn create_solo(&self, event_id: &String, user_id: &String) -> anyhow::Result<()> {
+        cruds::create_solo(event_id, user_id)
+    }
+
+    fn get_solos(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<UserDetail>> {
+        cruds::get_wanna_join_users_by_event_id(event_id, page)
+    }
+}
+
+impl TeamRepo for DieselRepo {
+    fn create_team(&self, event_id: &String, leader_id: &String, name: &String, desc: &String, capacity: Option<&i32>) -> anyhow::Result<Team> {
+        cruds::create_team(event_id, leader_id, name, desc, capacity)
+    }
+
+    fn get_team(&self, team_id: &String) -> anyhow::Result<Team> {
+        cruds::get_team_info_by_id(team_id)
+    }
+
+    fn get_team_detail(&self, team: Team) -> anyhow::Result<TeamDetail> {
+        cruds::get_team_detail(team)
+    }
+
+    fn get_teams_by_event(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<TeamDetail>> {
+        cruds::get_wanna_join_teams_by_event_id(event_id, page)
+    }
+
+    fn delete_team(&self, team_id: &String, actor_id: &Uuid) -> anyhow::Result<()> {
+        cruds::delete_team_by_id(team_id, actor_id)
+    }
+
+    fn set_wanted_role(&self, team_id: &String, role: &String, count: &i32) -> anyhow::Result<()> {
+        cruds::set_team_wanted_role(team_id, role, count)
+    }
+
+    fn delete_wanted_role(&self, team_id: &String, role: &String) -> anyhow::Result<()> {
+        cruds::delete_team_wanted_role(team_id, role)
+    }
+
+    fn create_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()> {
+        cruds::create_join(team_id, user_id)
+    }
+
+    fn delete_join(&self, team_id: &String, user_id: &String) -> anyhow::Result<()> {
+        cruds::delete_join(team_id, user_id)
+    }
+}
+
+impl RequestRepo for DieselRepo {
+    fn create_request(&self, team_id: &String, user_id: &String, message: &String) -> anyhow::Result<()> {
+        cruds::create_request(team_id, user_id, message)
+    }
+
+    fn accept_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+        cruds::accept_request(team_id, user_id)
+    }
+
+    fn decline_request(&self, team_id: &String, user_id: &String) -> anyhow::Result<bool> {
+        cruds::decline_request(team_id, user_id)
+    }
+
+    fn get_requests_by_user(&self, user_id: &String, page: &PageParams) -> anyhow::Result<Page<Request>> {
+        cruds::get_requests_from_user_id(user_id, page)
+    }
+}
+
+impl NotificationRepo for DieselRepo {
+    fn get_notifications(&self, user_id: &Uuid, unread_only: bool, page: &PageParams) -> anyhow::Result<Page<Notification>> {
+        cruds::get_notifications_by_user_id(user_id, unread_only, page)
+    }
+
+    fn count_unread_notifications(&self, user_id: &Uuid) -> anyhow::Result<i64> {
+        cruds::count_unread_notifications(user_id)
+    }
+
+    fn mark_notification_read(&self, user_id: &Uuid, notification_id: &String) -> anyhow::Result<bool> {
+        cruds::mark_notification_read(user_id, notification_id)
+    }
+
+    fn mark_all_notifications_read(&self, user_id: &Uuid) -> anyhow::Result<usize> {
+        cruds::mark_all_notifications_read(user_id)
+    }
+}
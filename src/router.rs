@@ -1,78 +1,369 @@
 use actix_web::{
     web,
-    get, post, delete,
+    get, post, put, patch, delete,
+    http::header,
     Error,
     HttpResponse,
     HttpRequest,
 };
-use diesel::r2d2::event;
+use actix_web::middleware::DefaultHeaders;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::Deserialize;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+use uuid::Uuid;
 
-use crate::{auth, cruds};
+use crate::{auth, bus, chat, config, cruds, db, formation, health, mail, metrics, outbound, recommend, webhook};
+use crate::cruds::CrudError;
+use crate::formation::Plan;
+use crate::models::{
+    CreatedWebhookSubscription, CurrentUserDetail, EmailPreference, Event, EventIntegration, Notification, Request,
+    Skill, Team, TeamDetail, User, UserDetail, WebhookDelivery, WebhookSubscription,
+    NewEmailPreference, NewEventIntegration,
+};
+use crate::pagination::Page;
+use crate::recommend::Recommendation;
+use crate::repo::Repos;
+use crate::pagination::{Order, PageQuery, SortKey};
+use crate::validation::{
+    FieldErrors,
+    CHAT_WEBHOOK_URL_MAX_LENGTH,
+    EMAIL_MAX_LENGTH,
+    SKILL_CATEGORY_MAX_LENGTH,
+    SKILL_KINDS,
+    SKILL_LEVEL_MAX,
+    SKILL_LEVEL_MIN,
+    SKILL_NAME_MAX_LENGTH,
+    USER_DISPLAY_NAME_MAX_LENGTH,
+    USER_LINK_MAX_LENGTH,
+    USER_LINKS_MAX_COUNT,
+    USER_PROFILE_MAX_LENGTH,
+    TEAM_CAPACITY_MAX,
+    WANTED_ROLE_MAX_LENGTH,
+    WEBHOOK_SECRET_MAX_LENGTH,
+    WEBHOOK_SECRET_MIN_LENGTH,
+    WEBHOOK_URL_MAX_LENGTH,
+};
 
 #[get("/")]
 async fn index() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().content_type("text/html").body("0w0"))
 }
 
+// The client only gets a bare 500, so the cause goes to the log
+fn internal_error(e: anyhow::Error) -> HttpResponse {
+    tracing::error!(error = format!("{:#}", e), "Internal server error");
+    HttpResponse::InternalServerError().finish()
+}
+
+// 404 or 409 for what the request got wrong, 500 for anything else
+fn error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<CrudError>() {
+        Some(CrudError::NotFound(message)) => HttpResponse::NotFound().body(message.clone()),
+        Some(CrudError::Conflict(message)) => HttpResponse::Conflict().body(message.clone()),
+        Some(CrudError::Invalid(errors)) => HttpResponse::UnprocessableEntity().json(errors),
+        None => internal_error(e),
+    }
+}
+
+// Probes
+#[get("/healthz")]
+async fn healthz() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(health::Check::ok()))
+}
+
+#[get("/readyz")]
+async fn readyz() -> Result<HttpResponse, Error> {
+    let readiness = health::readiness().await;
+    match readiness.status {
+        health::Status::Ok => Ok(HttpResponse::Ok().json(readiness)),
+        health::Status::Fail => Ok(HttpResponse::ServiceUnavailable().json(readiness)),
+    }
+}
+
+#[get("/version")]
+async fn version() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(health::version()))
+}
+
+#[get("/metrics")]
+async fn get_metrics() -> Result<HttpResponse, Error> {
+    match db::block(metrics::render).await {
+        Ok(text) => Ok(HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(text)),
+        Err(e) => Ok(internal_error(e)),
+    }
+}
+
 // Structs
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateEventReqBody {
     name: String,
     desc: String,
     url: String,
+    started_at: Option<NaiveDateTime>,
+    ended_at: Option<NaiveDateTime>,
 }
 
-#[derive(Deserialize)]
-struct CreateSoloReqBody {
-    event_id: String,
+#[derive(Deserialize, ToSchema)]
+struct AutoTeamReqBody {
+    team_size: i32,
+    together: Option<Vec<Vec<Uuid>>>,
+    plan_id: Option<Uuid>,
 }
 
-#[derive(Deserialize)]
+impl AutoTeamReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_range("team_size", self.team_size, 2, TEAM_CAPACITY_MAX);
+        errors
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 struct CreateTeamReqBody {
-    event_id: String,
     name: String,
     desc: String,
+    capacity: Option<i32>,
 }
 
-#[derive(Deserialize)]
-struct CreateJoinReqBody {
-    team_id: String,
+#[derive(Deserialize, ToSchema)]
+struct SetWantedRoleReqBody {
+    role: String,
+    count: i32,
 }
 
-#[derive(Deserialize)]
+impl SetWantedRoleReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_not_blank("role", &self.role);
+        errors.check_max_length("role", &self.role, WANTED_ROLE_MAX_LENGTH);
+        errors.check_range("count", self.count, 1, TEAM_CAPACITY_MAX);
+        errors
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+// The applicant is whoever signed the request in
 struct CreateRequestReqBody {
-    team_id: String,
-    user_id: String,
     message: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+struct UpdateUserReqBody {
+    profile: Option<String>,
+    display_name: Option<String>,
+    links: Option<Vec<String>>,
+}
+
+impl UpdateUserReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        if let Some(profile) = &self.profile {
+            errors.check_max_length("profile", profile, USER_PROFILE_MAX_LENGTH);
+        }
+        if let Some(display_name) = &self.display_name {
+            errors.check_not_blank("display_name", display_name);
+            errors.check_max_length("display_name", display_name, USER_DISPLAY_NAME_MAX_LENGTH);
+        }
+        if let Some(links) = &self.links {
+            if links.len() > USER_LINKS_MAX_COUNT {
+                errors.add("links", format!("must contain at most {} entries", USER_LINKS_MAX_COUNT));
+            }
+            for (i, link) in links.iter().enumerate() {
+                let field = format!("links[{}]", i);
+                errors.check_url(&field, link);
+                errors.check_max_length(&field, link, USER_LINK_MAX_LENGTH);
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct EmailPreferenceReqBody {
+    email: String,
+    locale: Option<String>,
+    on_request: Option<bool>,
+    on_join: Option<bool>,
+    on_team: Option<bool>,
+}
+
+impl EmailPreferenceReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_email("email", &self.email);
+        errors.check_max_length("email", &self.email, EMAIL_MAX_LENGTH);
+        if let Some(locale) = &self.locale {
+            errors.check_one_of("locale", locale, &mail::LOCALES);
+        }
+        errors
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateWebhookReqBody {
+    url: String,
+    secret: Option<String>,
+    event_types: Vec<String>,
+}
+
+impl CreateWebhookReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_url("url", &self.url);
+        errors.check_max_length("url", &self.url, WEBHOOK_URL_MAX_LENGTH);
+        if let Some(secret) = &self.secret {
+            if secret.chars().count() < WEBHOOK_SECRET_MIN_LENGTH {
+                errors.add("secret", format!("must be at least {} characters", WEBHOOK_SECRET_MIN_LENGTH));
+            }
+            errors.check_max_length("secret", secret, WEBHOOK_SECRET_MAX_LENGTH);
+        }
+        if self.event_types.is_empty() {
+            errors.add("event_types", "must not be empty".to_string());
+        }
+        let names = webhook::WebhookEvent::names();
+        for (i, t) in self.event_types.iter().enumerate() {
+            errors.check_one_of(&format!("event_types[{}]", i), t, &names);
+        }
+        errors
+    }
+}
+
+// Resolving the host waits on DNS, so it runs on the blocking pool
+async fn check_outbound_url(field: &str, url: &String) -> FieldErrors {
+    let mut errors = FieldErrors::new();
+    let url = url.clone();
+    if let Err(e) = db::block(move || outbound::resolve(&url)).await {
+        errors.add(field, format!("{:#}", e));
+    }
+    errors
+}
+
+#[derive(Deserialize, ToSchema)]
+struct EventIntegrationReqBody {
+    platform: String,
+    webhook_url: String,
+    notify_recruiting: Option<bool>,
+    notify_full: Option<bool>,
+    notify_solos: Option<bool>,
+}
+
+impl EventIntegrationReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_one_of("platform", &self.platform, &chat::PLATFORMS);
+        errors.check_url("webhook_url", &self.webhook_url);
+        errors.check_max_length("webhook_url", &self.webhook_url, CHAT_WEBHOOK_URL_MAX_LENGTH);
+        errors
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EmailTokenQuery {
+    token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateSkillReqBody {
+    name: String,
+    kind: String,
+    category: Option<String>,
+}
+
+impl CreateSkillReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_not_blank("name", &self.name);
+        errors.check_max_length("name", &self.name, SKILL_NAME_MAX_LENGTH);
+        errors.check_one_of("kind", &self.kind, &SKILL_KINDS);
+        if let Some(category) = &self.category {
+            errors.check_not_blank("category", category);
+            errors.check_max_length("category", category, SKILL_CATEGORY_MAX_LENGTH);
+        }
+        errors
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SetUserSkillReqBody {
+    skill_id: String,
+    level: i32,
+}
+
+impl SetUserSkillReqBody {
+    fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        errors.check_range("level", self.level, SKILL_LEVEL_MIN, SKILL_LEVEL_MAX);
+        errors
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct NotificationQuery {
+    unread: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StreamQuery {
+    // EventSource can't send headers, so the token may come in the query
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct UserIdQuery {
     user_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventSearchQuery {
+    q: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    status: Option<cruds::EventStatus>,
+    has_open_teams: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct EventIdQuery {
     event_id: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RecommendationQuery {
+    team_id: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct TeamIdQuery {
     team_id: String,
 }
 
 // API
-#[post("/api/users")]
-async fn create_user(req: HttpRequest) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 201, description = "Signed up with the GitHub account of the token"),
+        (status = 401),
+    ),
+)]
+#[post("/users")]
+async fn create_user(req: HttpRequest, repos: web::Data<Repos>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    let empty = String::new();
-                    match cruds::create_user(&user_data.login, &user_data.avatar_url, &empty) {
+                    match repos.run(move |r| r.users.create_user(&user_data.login, &user_data.avatar_url, &String::new())).await {
                         Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -82,25 +373,61 @@ async fn create_user(req: HttpRequest) -> Result<HttpResponse, Error> {
     };
 }
 
-#[get("api/users")]
-async fn get_user(req: HttpRequest, query: web::Query<UserIdQuery>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = CurrentUserDetail),
+        (status = 401),
+    ),
+)]
+#[get("/users/me")]
+async fn get_current_user(req: HttpRequest, repos: web::Data<Repos>) -> Result<HttpResponse, Error> {
+    return handle_get_user(req, &repos, None).await;
+}
+
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = UserDetail),
+        (status = 401),
+        (status = 404, description = "No such user"),
+    ),
+)]
+#[get("/users/{user_id}")]
+async fn get_user(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_get_user(req, &repos, Some(&path.into_inner())).await;
+}
+
+// Another user when `user_id` is given, otherwise the current user
+async fn handle_get_user(req: HttpRequest, repos: &Repos, user_id: Option<&String>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match &query.user_id {
-                        // queryあり
+                    match user_id {
+                        // user_id指定あり
                         Some(user_id) => {
-                            match cruds::get_user_info_by_id(&user_id) {
+                            match repos.run({
+                                let user_id = user_id.clone();
+                                move |r| r.users.get_user_by_id(&user_id).and_then(|u| r.users.get_user_detail(u))
+                            }).await {
                                 Ok(user) => return Ok(HttpResponse::Ok().content_type("text/html").json(user)),
-                                Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                                Err(e) => return Ok(error_response(e))
                             };
                         },
-                        // queryなし
+                        // user_id指定なし
                         None => {
-                            match cruds::get_user_info_by_name(&user_data.login) {
-                                Ok(user) => return Ok(HttpResponse::Ok().content_type("text/html").json(user)),
-                                Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                            let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login).and_then(|u| r.users.get_user_detail(u))).await {
+                                Ok(u) => u,
+                                Err(e) => return Ok(internal_error(e))
+                            };
+                            let user_id = user.user.id;
+                            match repos.run(move |r| r.notifications.count_unread_notifications(&user_id)).await {
+                                Ok(unread_notifications) => {
+                                    let me = CurrentUserDetail{user, unread_notifications};
+                                    return Ok(HttpResponse::Ok().content_type("text/html").json(me))
+                                },
+                                Err(e) => return Ok(internal_error(e))
                             };
                         }
                     };
@@ -112,15 +439,258 @@ async fn get_user(req: HttpRequest, query: web::Query<UserIdQuery>) -> Result<Ht
     };
 }
 
-#[post("/api/events")]
-async fn create_event(req: HttpRequest, body: web::Json<CreateEventReqBody>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = User),
+        (status = 401),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[patch("/users/me")]
+async fn update_user(req: HttpRequest, repos: web::Data<Repos>, body: web::Json<UpdateUserReqBody>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
-                Ok(_) => {
-                    match cruds::create_event(&body.name, &body.desc, &body.url) {
-                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                Ok(user_data) => {
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let links = body.links.as_ref()
+                        .map(|links| links.iter().map(|l| Some(l.clone())).collect::<Vec<_>>());
+                    match repos.run(move |r| r.users.update_user_profile(&user.id.to_string(), body.profile.as_ref(), body.display_name.as_ref(), links.as_ref())).await {
+                        Ok(user) => return Ok(HttpResponse::Ok().content_type("text/html").json(user)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "email",
+    responses(
+        (status = 200, body = EmailPreference),
+        (status = 401),
+        (status = 404, description = "Email not set up"),
+    ),
+)]
+#[get("/users/me/email")]
+async fn get_email_preference(req: HttpRequest) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match db::block(move || cruds::get_user_info_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block(move || cruds::get_email_preference(&user.id)).await {
+                        Ok(Some(preference)) => return Ok(HttpResponse::Ok().content_type("text/html").json(preference)),
+                        Ok(None) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "email",
+    responses(
+        (status = 200, description = "Saved; a new address is mailed a verification link first", body = EmailPreference),
+        (status = 401),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[put("/users/me/email")]
+async fn set_email_preference(req: HttpRequest, body: web::Json<EmailPreferenceReqBody>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    let user = match db::block(move || cruds::get_user_info_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let locale = body.locale.clone().unwrap_or(mail::LOCALES[0].to_string());
+                    match db::block(move || {
+                        let preference = NewEmailPreference{
+                            user_id: &user.id,
+                            email: &body.email,
+                            locale: &locale,
+                            on_request: body.on_request.unwrap_or(true),
+                            on_join: body.on_join.unwrap_or(true),
+                            on_team: body.on_team.unwrap_or(true),
+                        };
+                        cruds::set_email_preference(&preference)
+                    }).await {
+                        Ok(preference) => return Ok(HttpResponse::Ok().content_type("text/html").json(preference)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+// Links in emails land on a page whose button sends the POST, so mail
+// scanners and link prefetchers opening them change nothing. The form has no
+// action, so it posts back to the same URL, token included.
+fn email_confirmation_page(text: &str, button: &str) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>hotchpotch</title></head>\n<body><p>{}</p><form method=\"post\"><button type=\"submit\">{}</button></form></body></html>\n",
+        text, button,
+    ))
+}
+
+// Linked from every email, so it works without logging in
+#[utoipa::path(
+    tag = "email",
+    security(()),
+    params(EmailTokenQuery),
+    responses(
+        (status = 200, description = "A page asking to confirm the unsubscribe", body = String),
+    ),
+)]
+#[get("/email/unsubscribe")]
+async fn confirm_unsubscribe_email(_query: web::Query<EmailTokenQuery>) -> Result<HttpResponse, Error> {
+    Ok(email_confirmation_page("Stop all emails from hotchpotch?", "Unsubscribe"))
+}
+
+#[utoipa::path(
+    tag = "email",
+    security(()),
+    params(EmailTokenQuery),
+    responses(
+        (status = 200, description = "Every kind of email turned off", body = String),
+        (status = 404),
+    ),
+)]
+#[post("/email/unsubscribe")]
+async fn unsubscribe_email(query: web::Query<EmailTokenQuery>) -> Result<HttpResponse, Error> {
+    match db::block(move || cruds::unsubscribe_email(&query.token)).await {
+        Ok(true) => return Ok(HttpResponse::Ok().content_type("text/html").body("You will no longer receive emails from hotchpotch.")),
+        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+        Err(e) => return Ok(internal_error(e))
+    };
+}
+
+// Linked from the verification email
+#[utoipa::path(
+    tag = "email",
+    security(()),
+    params(EmailTokenQuery),
+    responses(
+        (status = 200, description = "A page asking to confirm the address", body = String),
+    ),
+)]
+#[get("/email/verify")]
+async fn confirm_verify_email(_query: web::Query<EmailTokenQuery>) -> Result<HttpResponse, Error> {
+    Ok(email_confirmation_page("Get hotchpotch notifications at this address?", "Confirm"))
+}
+
+#[utoipa::path(
+    tag = "email",
+    security(()),
+    params(EmailTokenQuery),
+    responses(
+        (status = 200, description = "The address is verified", body = String),
+        (status = 404),
+    ),
+)]
+#[post("/email/verify")]
+async fn verify_email(query: web::Query<EmailTokenQuery>) -> Result<HttpResponse, Error> {
+    match db::block(move || cruds::verify_email(&query.token)).await {
+        Ok(true) => return Ok(HttpResponse::Ok().content_type("text/html").body("Your email address is confirmed.")),
+        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+        Err(e) => return Ok(internal_error(e))
+    };
+}
+
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = UserDetail),
+        (status = 401),
+        (status = 404, description = "No such skill"),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[put("/users/me/skills")]
+async fn set_user_skill(req: HttpRequest, body: web::Json<SetUserSkillReqBody>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    let user = match db::block(move || cruds::get_user_info_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block(move || cruds::set_user_skill(&user.id.to_string(), &body.skill_id, &body.level)).await {
+                        Ok(_) => {},
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    match db::block(move || cruds::get_user_detail(user)).await {
+                        Ok(user) => return Ok(HttpResponse::Ok().content_type("text/html").json(user)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 204),
+        (status = 401),
+    ),
+)]
+#[delete("/users/me/skills/{skill_id}")]
+async fn delete_user_skill(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_delete_user_skill(req, &path.into_inner()).await;
+}
+
+async fn handle_delete_user_skill(req: HttpRequest, skill_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match db::block(move || cruds::get_user_info_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block({
+                        let skill_id = skill_id.clone();
+                        move || cruds::delete_user_skill(&user.id.to_string(), &skill_id)
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -130,15 +700,30 @@ async fn create_event(req: HttpRequest, body: web::Json<CreateEventReqBody>) ->
     };
 }
 
-#[get("/api/events")]
-async fn get_event(req: HttpRequest) -> Result<HttpResponse, Error> {
+// The catalogue is shared and open: like tags, any signed-in user may add a
+// skill that is missing, and names are unique so it isn't added twice
+#[utoipa::path(
+    tag = "skills",
+    responses(
+        (status = 201, body = Skill),
+        (status = 401),
+        (status = 409, description = "A skill with that name exists"),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[post("/skills")]
+async fn create_skill(req: HttpRequest, body: web::Json<CreateSkillReqBody>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(_) => {
-                    match cruds::get_event_list() {
-                        Ok(event_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(event_list)),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    match db::block(move || cruds::create_skill(&body.name, &body.kind, body.category.as_ref())).await {
+                        Ok(skill) => return Ok(HttpResponse::Created().content_type("text/html").json(skill)),
+                        Err(e) => return Ok(error_response(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -148,15 +733,28 @@ async fn get_event(req: HttpRequest) -> Result<HttpResponse, Error> {
     };
 }
 
-#[delete("/api/events")]
-async fn delete_event(req: HttpRequest, query: web::Query<EventIdQuery>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "skills",
+    params(PageQuery),
+    responses(
+        (status = 200, body = Page<Skill>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/skills")]
+async fn get_skill(req: HttpRequest, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(_) => {
-                    match cruds::delete_event_by_id(&query.event_id) {
-                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let page = match page.parse(&[SortKey::Name, SortKey::CreatedAt]) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    match db::block(move || cruds::get_skill_list(&page)).await {
+                        Ok(skill_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(skill_list)),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -166,15 +764,32 @@ async fn delete_event(req: HttpRequest, query: web::Query<EventIdQuery>) -> Resu
     };
 }
 
-#[post("/api/solos")]
-async fn create_solo(req: HttpRequest, body: web::Query<CreateSoloReqBody>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 201),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[post("/events")]
+async fn create_event(req: HttpRequest, repos: web::Data<Repos>, body: web::Json<CreateEventReqBody>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match cruds::create_solo(&body.event_id, &user_data.login) {
+                    if let (Some(started_at), Some(ended_at)) = (&body.started_at, &body.ended_at) {
+                        if started_at > ended_at {
+                            return Ok(HttpResponse::BadRequest().body("started_at must not be after ended_at"))
+                        }
+                    }
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run(move |r| r.events.create_event(&body.name, &body.desc, &body.url, body.started_at.as_ref(), body.ended_at.as_ref(), &user.id.to_string())).await {
                         Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -184,15 +799,41 @@ async fn create_solo(req: HttpRequest, body: web::Query<CreateSoloReqBody>) -> R
     };
 }
 
-#[get("/api/solos")]
-async fn get_solo(req: HttpRequest, query: web::Query<EventIdQuery>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "events",
+    params(EventSearchQuery, PageQuery),
+    responses(
+        (status = 200, body = Page<Event>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/events")]
+async fn get_event(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<EventSearchQuery>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(_) => {
-                    match cruds::get_wanna_join_users_by_event_id(&query.event_id) {
-                        Ok(user_list) => return Ok(HttpResponse::Created().content_type("text/html").json(user_list)),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let page = match page.parse(&[SortKey::CreatedAt, SortKey::Name]) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    let query = query.into_inner();
+                    if let (Some(from), Some(to)) = (query.from, query.to) {
+                        if from > to {
+                            return Ok(HttpResponse::BadRequest().body("from must not be after to"))
+                        }
+                    }
+                    let filter = cruds::EventFilter{
+                        q: query.q.filter(|q| !q.trim().is_empty()),
+                        from: query.from,
+                        to: query.to,
+                        status: query.status,
+                        has_open_teams: query.has_open_teams,
+                    };
+                    match repos.run(move |r| r.events.get_event_list(&filter, &page)).await {
+                        Ok(event_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(event_list)),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -202,15 +843,42 @@ async fn get_solo(req: HttpRequest, query: web::Query<EventIdQuery>) -> Result<H
     };
 }
 
-#[post("/api/teams")]
-async fn create_team(req: HttpRequest, body: web::Json<CreateTeamReqBody>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401),
+        (status = 403, description = "Not an organizer of the event"),
+    ),
+)]
+#[delete("/events/{event_id}")]
+async fn delete_event(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_delete_event(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_delete_event(req: HttpRequest, repos: &Repos, event_id: &String) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match cruds::create_team(&body.event_id, &user_data.login, &body.name, &body.desc) {
-                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let event_id = event_id.clone();
+                        move |r| r.events.is_event_organizer(&event_id, &user.id)
+                    }).await {
+                        Ok(true) => {},
+                        Ok(false) => return Ok(HttpResponse::Forbidden().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let event_id = event_id.clone();
+                        move |r| r.events.delete_event(&event_id)
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -220,16 +888,168 @@ async fn create_team(req: HttpRequest, body: web::Json<CreateTeamReqBody>) -> Re
     };
 }
 
-#[get("/api/teams")]
-async fn get_team(req: HttpRequest, query: web::Query<TeamIdQuery>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "events",
+    params(RecommendationQuery),
+    responses(
+        (status = 200, description = "Teams for the current user, or solos for `team_id` when its leader asks", body = [Recommendation<TeamDetail>]),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[get("/events/{event_id}/recommendations")]
+async fn get_recommendation(req: HttpRequest, path: web::Path<String>, query: web::Query<RecommendationQuery>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
-                Ok(_) => {
-                    match cruds::get_team_info_by_id(&query.team_id) {
-                        Ok(team) => return Ok(HttpResponse::Ok().content_type("text/html").json(team)),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    let limit = query.limit.unwrap_or(10).clamp(1, 50);
+                    let user = match db::block(move || cruds::get_user_info_by_name(&user_data.login).and_then(cruds::get_user_detail)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let teams = match db::block({
+                        let event_id = event_id.clone();
+                        move || cruds::get_event_teams_with_members(&event_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match &query.team_id {
+                        // Solos for the leader's team
+                        Some(team_id) => {
+                            let (team, members) = match teams.iter().find(|(t, _)| t.team.id.to_string() == *team_id) {
+                                Some(t) => t,
+                                None => return Ok(HttpResponse::NotFound().finish())
+                            };
+                            if team.team.reader_id != user.user.id {
+                                return Ok(HttpResponse::Forbidden().finish())
+                            }
+                            let solos = match db::block(move || cruds::get_event_solo_details(&event_id)).await {
+                                Ok(s) => s,
+                                Err(e) => return Ok(internal_error(e))
+                            };
+                            let mut result = recommend::solos_for_team(team, members, &solos);
+                            result.truncate(limit);
+                            return Ok(HttpResponse::Ok().content_type("text/html").json(result))
+                        },
+                        // Teams for the current user
+                        None => {
+                            let mut result = recommend::teams_for_user(&user, &teams);
+                            result.truncate(limit);
+                            return Ok(HttpResponse::Ok().content_type("text/html").json(result))
+                        }
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+// The current user, provided they organize the event
+#[allow(clippy::result_large_err)]
+async fn require_organizer(login: &String, event_id: &String) -> Result<User, HttpResponse> {
+    let user = match db::block({
+        let login = login.clone();
+        move || cruds::get_user_info_by_name(&login)
+    }).await {
+        Ok(u) => u,
+        Err(e) => return Err(internal_error(e))
+    };
+    match db::block({
+        let (event_id, user_id) = (event_id.clone(), user.id);
+        move || cruds::is_event_organizer(&event_id, &user_id)
+    }).await {
+        Ok(true) => return Ok(user),
+        Ok(false) => return Err(HttpResponse::Forbidden().finish()),
+        Err(e) => return Err(internal_error(e))
+    };
+}
+
+// Shared by the preview and confirm endpoints of automatic team formation
+#[allow(clippy::result_large_err)]
+async fn plan_auto_teams(login: &String, event_id: &String, body: &AutoTeamReqBody) -> Result<formation::Plan, HttpResponse> {
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(HttpResponse::UnprocessableEntity().json(errors))
+    }
+    require_organizer(login, event_id).await?;
+    let solos = match db::block({
+        let event_id = event_id.clone();
+        move || cruds::get_event_solo_details(&event_id)
+    }).await {
+        Ok(s) => s,
+        Err(e) => return Err(internal_error(e))
+    };
+    let together = body.together.clone().unwrap_or_default();
+    match formation::plan(&solos, body.team_size as usize, &together) {
+        Ok(plan) => return Ok(plan),
+        Err(e) => return Err(HttpResponse::BadRequest().body(e.to_string()))
+    };
+}
+
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = Plan),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[post("/events/{event_id}/auto_teams/preview")]
+async fn preview_auto_team(req: HttpRequest, path: web::Path<String>, body: web::Json<AutoTeamReqBody>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    match plan_auto_teams(&user_data.login, &path.into_inner(), &body).await {
+                        Ok(plan) => return Ok(HttpResponse::Ok().content_type("text/html").json(plan)),
+                        Err(res) => return Ok(res)
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 201, body = [Team]),
+        (status = 401),
+        (status = 403),
+        (status = 409, description = "Solos changed since the preview"),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[post("/events/{event_id}/auto_teams")]
+async fn create_auto_team(req: HttpRequest, path: web::Path<String>, body: web::Json<AutoTeamReqBody>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    let plan = match plan_auto_teams(&user_data.login, &event_id, &body).await {
+                        Ok(plan) => plan,
+                        Err(res) => return Ok(res)
+                    };
+                    // Only the plan the organizer previewed may be applied
+                    if body.plan_id != Some(plan.plan_id) {
+                        return Ok(HttpResponse::Conflict().body("Solos changed since the preview"))
                     }
+                    match db::block(move || cruds::create_planned_teams(&event_id, &plan.teams, &body.team_size)).await {
+                        Ok(team_list) => return Ok(HttpResponse::Created().content_type("text/html").json(team_list)),
+                        Err(e) => return Ok(error_response(e))
+                    };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
             }
@@ -238,16 +1058,45 @@ async fn get_team(req: HttpRequest, query: web::Query<TeamIdQuery>) -> Result<Ht
     };
 }
 
-#[get("/api/teams/event")]
-async fn get_team_by_event(req: HttpRequest, query: web::Query<EventIdQuery>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 201, body = CreatedWebhookSubscription),
+        (status = 401),
+        (status = 403),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[post("/events/{event_id}/webhooks")]
+async fn create_webhook(req: HttpRequest, path: web::Path<String>, body: web::Json<CreateWebhookReqBody>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
-                Ok(_) => {
-                    match cruds::get_wanna_join_teams_by_event_id(&query.event_id) {
-                        Ok(team_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(team_list)),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    let errors = check_outbound_url("url", &body.url).await;
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
                     }
+                    let secret = body.secret.clone().unwrap_or_else(webhook::generate_secret);
+                    let event_types = body.event_types.iter().map(|t| Some(t.clone())).collect();
+                    match db::block({
+                        let secret = secret.clone();
+                        move || cruds::create_webhook_subscription(&event_id, &body.url, &secret, &event_types)
+                    }).await {
+                        Ok(subscription) => {
+                            let created = CreatedWebhookSubscription{subscription, secret};
+                            return Ok(HttpResponse::Created().content_type("text/html").json(created))
+                        },
+                        Err(e) => return Ok(internal_error(e))
+                    };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
             }
@@ -256,15 +1105,27 @@ async fn get_team_by_event(req: HttpRequest, query: web::Query<EventIdQuery>) ->
     };
 }
 
-#[post("/api/joins")]
-async fn create_join(req: HttpRequest, body: web::Query<CreateJoinReqBody>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 200, body = [WebhookSubscription]),
+        (status = 401),
+        (status = 403),
+    ),
+)]
+#[get("/events/{event_id}/webhooks")]
+async fn get_webhook(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match cruds::create_join(&body.team_id, &user_data.login) {
-                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let event_id = path.into_inner();
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    match db::block(move || cruds::get_webhook_subscriptions_by_event_id(&event_id)).await {
+                        Ok(subscription_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(subscription_list)),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -274,15 +1135,33 @@ async fn create_join(req: HttpRequest, body: web::Query<CreateJoinReqBody>) -> R
     };
 }
 
-#[post("/api/requests")]
-async fn create_request(req: HttpRequest, body: web::Json<CreateRequestReqBody>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[delete("/events/{event_id}/webhooks/{webhook_id}")]
+async fn delete_webhook(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match cruds::create_request(&body.team_id, &body.user_id, &body.message) {
-                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let (event_id, webhook_id) = path.into_inner();
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    let subscription = match db::block(move || cruds::get_webhook_subscription(&event_id, &webhook_id)).await {
+                        Ok(Some(s)) => s,
+                        Ok(None) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block(move || cruds::delete_webhook_subscription(&subscription.id)).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -292,15 +1171,77 @@ async fn create_request(req: HttpRequest, body: web::Json<CreateRequestReqBody>)
     };
 }
 
-#[get("/api/requests")]
-async fn get_request(req: HttpRequest) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "webhooks",
+    params(PageQuery),
+    responses(
+        (status = 200, body = Page<WebhookDelivery>),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[get("/events/{event_id}/webhooks/{webhook_id}/deliveries")]
+async fn get_webhook_delivery(req: HttpRequest, path: web::Path<(String, String)>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
     match auth::parse_token(req) {
         Some(token) => {
             match auth::verification(token).await {
                 Ok(user_data) => {
-                    match cruds::get_requests_from_user_id(&user_data.login) {
-                        Ok(request_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(request_list)),
-                        Err(_) => return Ok(HttpResponse::InternalServerError().finish())
+                    let (event_id, webhook_id) = path.into_inner();
+                    // Newest first unless asked otherwise
+                    let page = match page.parse_with_order(&[SortKey::CreatedAt], Order::Desc) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    let subscription = match db::block(move || cruds::get_webhook_subscription(&event_id, &webhook_id)).await {
+                        Ok(Some(s)) => s,
+                        Ok(None) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block(move || cruds::get_webhook_deliveries(&subscription.id, &page)).await {
+                        Ok(delivery_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(delivery_list)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 202, description = "Queued again"),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[post("/events/{event_id}/webhooks/{webhook_id}/deliveries/{delivery_id}/redeliver")]
+async fn redeliver_webhook(req: HttpRequest, path: web::Path<(String, String, String)>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let (event_id, webhook_id, delivery_id) = path.into_inner();
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    let subscription = match db::block(move || cruds::get_webhook_subscription(&event_id, &webhook_id)).await {
+                        Ok(Some(s)) => s,
+                        Ok(None) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match db::block(move || cruds::redeliver_webhook(&subscription.id, &delivery_id)).await {
+                        Ok(true) => return Ok(HttpResponse::Accepted().finish()),
+                        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
                     };
                 },
                 Err(_) => return Ok(HttpResponse::Unauthorized().finish())
@@ -309,3 +1250,1118 @@ async fn get_request(req: HttpRequest) -> Result<HttpResponse, Error> {
         None => return Ok(HttpResponse::Unauthorized().finish())
     };
 }
+
+#[utoipa::path(
+    tag = "integrations",
+    responses(
+        (status = 200, body = EventIntegration),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[get("/events/{event_id}/integration")]
+async fn get_event_integration(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    match db::block(move || cruds::get_event_integration(&event_id)).await {
+                        Ok(Some(integration)) => return Ok(HttpResponse::Ok().content_type("text/html").json(integration)),
+                        Ok(None) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "integrations",
+    responses(
+        (status = 200, body = EventIntegration),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[put("/events/{event_id}/integration")]
+async fn set_event_integration(req: HttpRequest, path: web::Path<String>, body: web::Json<EventIntegrationReqBody>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    let errors = check_outbound_url("webhook_url", &body.webhook_url).await;
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    let event_uuid = match Uuid::parse_str(&event_id) {
+                        Ok(u) => u,
+                        Err(_) => return Ok(HttpResponse::BadRequest().body("Invalid event_id"))
+                    };
+                    match db::block(move || {
+                        let integration = NewEventIntegration{
+                            event_id: &event_uuid,
+                            platform: &body.platform,
+                            webhook_url: &body.webhook_url,
+                            notify_recruiting: body.notify_recruiting.unwrap_or(true),
+                            notify_full: body.notify_full.unwrap_or(true),
+                            notify_solos: body.notify_solos.unwrap_or(true),
+                        };
+                        cruds::set_event_integration(&integration)
+                    }).await {
+                        Ok(integration) => return Ok(HttpResponse::Ok().content_type("text/html").json(integration)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "integrations",
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403),
+    ),
+)]
+#[delete("/events/{event_id}/integration")]
+async fn delete_event_integration(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let event_id = path.into_inner();
+                    if let Err(res) = require_organizer(&user_data.login, &event_id).await {
+                        return Ok(res)
+                    }
+                    match db::block(move || cruds::delete_event_integration(&event_id)).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "solos",
+    responses(
+        (status = 201),
+        (status = 401),
+    ),
+)]
+#[post("/events/{event_id}/solos")]
+async fn create_solo(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_create_solo(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_create_solo(req: HttpRequest, repos: &Repos, event_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let event_id = event_id.clone();
+                        move |r| r.events.create_solo(&event_id, &user.id.to_string())
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "solos",
+    params(PageQuery),
+    responses(
+        (status = 201, body = Page<UserDetail>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/events/{event_id}/solos")]
+async fn get_solo(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_solo(req, &repos, &path.into_inner(), page).await;
+}
+
+async fn handle_get_solo(req: HttpRequest, repos: &Repos, event_id: &String, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(_) => {
+                    let page = match page.parse(&[SortKey::CreatedAt, SortKey::Name]) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    match repos.run({
+                        let event_id = event_id.clone();
+                        move |r| r.events.get_solos(&event_id, &page)
+                    }).await {
+                        Ok(user_list) => return Ok(HttpResponse::Created().content_type("text/html").json(user_list)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses(
+        (status = 201),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[post("/events/{event_id}/teams")]
+async fn create_team(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>, body: web::Json<CreateTeamReqBody>) -> Result<HttpResponse, Error> {
+    return handle_create_team(req, &repos, &path.into_inner(), &body).await;
+}
+
+async fn handle_create_team(req: HttpRequest, repos: &Repos, event_id: &String, body: &CreateTeamReqBody) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    if let Some(capacity) = body.capacity {
+                        if !(2..=TEAM_CAPACITY_MAX).contains(&capacity) {
+                            return Ok(HttpResponse::BadRequest().body(format!("capacity must be between 2 and {}", TEAM_CAPACITY_MAX)))
+                        }
+                    }
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let (event_id, name, desc, capacity) = (event_id.clone(), body.name.clone(), body.desc.clone(), body.capacity);
+                        move |r| r.teams.create_team(&event_id, &user.id.to_string(), &name, &desc, capacity.as_ref())
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses(
+        (status = 200, body = Team),
+        (status = 401),
+        (status = 404, description = "No such team"),
+    ),
+)]
+#[get("/teams/{team_id}")]
+async fn get_team(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_get_team(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_get_team(req: HttpRequest, repos: &Repos, team_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(_) => {
+                    match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id).and_then(|t| r.teams.get_team_detail(t))
+                    }).await {
+                        Ok(team) => return Ok(HttpResponse::Ok().content_type("text/html").json(team)),
+                        Err(e) => return Ok(error_response(e))
+                    }
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    params(PageQuery),
+    responses(
+        (status = 200, body = Page<TeamDetail>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/events/{event_id}/teams")]
+async fn get_team_by_event(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_team_by_event(req, &repos, &path.into_inner(), page).await;
+}
+
+async fn handle_get_team_by_event(req: HttpRequest, repos: &Repos, event_id: &String, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(_) => {
+                    let page = match page.parse(&[SortKey::CreatedAt, SortKey::Name]) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    match repos.run({
+                        let event_id = event_id.clone();
+                        move |r| r.teams.get_teams_by_event(&event_id, &page)
+                    }).await {
+                        Ok(team_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(team_list)),
+                        Err(e) => return Ok(internal_error(e))
+                    }
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[delete("/teams/{team_id}")]
+async fn delete_team(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_delete_team(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_delete_team(req: HttpRequest, repos: &Repos, team_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let team = match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    if team.reader_id != user.id {
+                        return Ok(HttpResponse::Forbidden().finish())
+                    }
+                    match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.delete_team(&team_id, &user.id)
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses(
+        (status = 200, body = TeamDetail),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+        (status = 422, body = FieldErrors),
+    ),
+)]
+#[put("/teams/{team_id}/roles")]
+async fn set_team_wanted_role(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>, body: web::Json<SetWantedRoleReqBody>) -> Result<HttpResponse, Error> {
+    return handle_set_team_wanted_role(req, &repos, &path.into_inner(), &body).await;
+}
+
+async fn handle_set_team_wanted_role(req: HttpRequest, repos: &Repos, team_id: &String, body: &SetWantedRoleReqBody) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let errors = body.validate();
+                    if !errors.is_empty() {
+                        return Ok(HttpResponse::UnprocessableEntity().json(errors))
+                    }
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let team = match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    if team.reader_id != user.id {
+                        return Ok(HttpResponse::Forbidden().finish())
+                    }
+                    let role = body.role.trim().to_lowercase();
+                    match repos.run({
+                        let (team_id, count) = (team_id.clone(), body.count);
+                        move |r| r.teams.set_wanted_role(&team_id, &role, &count)
+                    }).await {
+                        Ok(_) => {},
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    match repos.run(move |r| r.teams.get_team_detail(team)).await {
+                        Ok(team) => return Ok(HttpResponse::Ok().content_type("text/html").json(team)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+)]
+#[delete("/teams/{team_id}/roles/{role}")]
+async fn delete_team_wanted_role(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    return handle_delete_team_wanted_role(req, &repos, &path.0, &path.1).await;
+}
+
+async fn handle_delete_team_wanted_role(req: HttpRequest, repos: &Repos, team_id: &String, role: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let team = match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    if team.reader_id != user.id {
+                        return Ok(HttpResponse::Forbidden().finish())
+                    }
+                    let role = role.trim().to_lowercase();
+                    match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.delete_wanted_role(&team_id, &role)
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "joins",
+    responses(
+        (status = 201),
+        (status = 401),
+        (status = 404),
+        (status = 409, description = "The team is full"),
+    ),
+)]
+#[post("/teams/{team_id}/members")]
+async fn create_join(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_create_join(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_create_join(req: HttpRequest, repos: &Repos, team_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.create_join(&team_id, &user.id.to_string())
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
+                        Err(e) => return Ok(error_response(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "joins",
+    responses(
+        (status = 204),
+        (status = 401),
+    ),
+)]
+#[delete("/teams/{team_id}/members/me")]
+async fn delete_join(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_delete_join(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_delete_join(req: HttpRequest, repos: &Repos, team_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.delete_join(&team_id, &user.id.to_string())
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "requests",
+    responses(
+        (status = 201),
+        (status = 401),
+        (status = 404, description = "No such team"),
+        (status = 409, description = "Already requested, or already a member"),
+    ),
+)]
+#[post("/teams/{team_id}/requests")]
+async fn create_request(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>, body: web::Json<CreateRequestReqBody>) -> Result<HttpResponse, Error> {
+    return handle_create_request(req, &repos, &path.into_inner(), &body).await;
+}
+
+async fn handle_create_request(req: HttpRequest, repos: &Repos, team_id: &String, body: &CreateRequestReqBody) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let (team_id, message) = (team_id.clone(), body.message.clone());
+                        move |r| r.requests.create_request(&team_id, &user.id.to_string(), &message)
+                    }).await {
+                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
+                        Err(e) => return Ok(error_response(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "requests",
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 404, description = "No pending request"),
+        (status = 409, description = "The team is full"),
+    ),
+)]
+#[post("/teams/{team_id}/requests/{user_id}/accept")]
+async fn accept_request(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    return handle_accept_request(req, &repos, &path.0, &path.1).await;
+}
+
+async fn handle_accept_request(req: HttpRequest, repos: &Repos, team_id: &String, user_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let team = match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    if team.reader_id != user.id {
+                        return Ok(HttpResponse::Forbidden().finish())
+                    }
+                    match repos.run({
+                        let (team_id, user_id) = (team_id.clone(), user_id.clone());
+                        move |r| r.requests.accept_request(&team_id, &user_id)
+                    }).await {
+                        Ok(true) => return Ok(HttpResponse::Ok().content_type("text/html").body("0w0")),
+                        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(error_response(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "requests",
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 404, description = "No pending request"),
+    ),
+)]
+#[post("/teams/{team_id}/requests/{user_id}/decline")]
+async fn decline_request(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    return handle_decline_request(req, &repos, &path.0, &path.1).await;
+}
+
+async fn handle_decline_request(req: HttpRequest, repos: &Repos, team_id: &String, user_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let team = match repos.run({
+                        let team_id = team_id.clone();
+                        move |r| r.teams.get_team(&team_id)
+                    }).await {
+                        Ok(t) => t,
+                        Err(e) => return Ok(error_response(e))
+                    };
+                    if team.reader_id != user.id {
+                        return Ok(HttpResponse::Forbidden().finish())
+                    }
+                    match repos.run({
+                        let (team_id, user_id) = (team_id.clone(), user_id.clone());
+                        move |r| r.requests.decline_request(&team_id, &user_id)
+                    }).await {
+                        Ok(true) => return Ok(HttpResponse::Ok().content_type("text/html").body("0w0")),
+                        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "requests",
+    params(PageQuery),
+    responses(
+        (status = 200, body = Page<Request>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/requests")]
+async fn get_request(req: HttpRequest, repos: web::Data<Repos>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let page = match page.parse(&[SortKey::CreatedAt]) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run(move |r| r.requests.get_requests_by_user(&user.id.to_string(), &page)).await {
+                        Ok(request_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(request_list)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "notifications",
+    params(NotificationQuery, PageQuery),
+    responses(
+        (status = 200, body = Page<Notification>),
+        (status = 400),
+        (status = 401),
+    ),
+)]
+#[get("/notifications")]
+async fn get_notification(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<NotificationQuery>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    // Newest first unless asked otherwise
+                    let page = match page.parse_with_order(&[SortKey::CreatedAt], Order::Desc) {
+                        Ok(p) => p,
+                        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+                    };
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    let unread_only = query.unread.unwrap_or(false);
+                    match repos.run(move |r| r.notifications.get_notifications(&user.id, unread_only, &page)).await {
+                        Ok(notification_list) => return Ok(HttpResponse::Ok().content_type("text/html").json(notification_list)),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "notifications",
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 404),
+    ),
+)]
+#[post("/notifications/{notification_id}/read")]
+async fn read_notification(req: HttpRequest, repos: web::Data<Repos>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    return handle_read_notification(req, &repos, &path.into_inner()).await;
+}
+
+async fn handle_read_notification(req: HttpRequest, repos: &Repos, notification_id: &String) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run({
+                        let notification_id = notification_id.clone();
+                        move |r| r.notifications.mark_notification_read(&user.id, &notification_id)
+                    }).await {
+                        Ok(true) => return Ok(HttpResponse::NoContent().finish()),
+                        Ok(false) => return Ok(HttpResponse::NotFound().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "notifications",
+    responses(
+        (status = 204),
+        (status = 401),
+    ),
+)]
+#[post("/notifications/read_all")]
+async fn read_all_notification(req: HttpRequest, repos: web::Data<Repos>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    match repos.run(move |r| r.notifications.mark_all_notifications_read(&user.id)).await {
+                        Ok(_) => return Ok(HttpResponse::NoContent().finish()),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[utoipa::path(
+    tag = "notifications",
+    security(("bearer_auth" = []), ("access_token" = [])),
+    params(StreamQuery),
+    responses(
+        (status = 200, description = "Server-Sent Events; each `data:` line is a Notification", content_type = "text/event-stream", body = String),
+        (status = 401),
+    ),
+)]
+#[get("/notifications/stream")]
+async fn stream_notification(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<StreamQuery>) -> Result<HttpResponse, Error> {
+    match auth::parse_token(req).or_else(|| query.access_token.clone()) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(user_data) => {
+                    let user = match repos.run(move |r| r.users.get_user_by_name(&user_data.login)).await {
+                        Ok(u) => u,
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                    return Ok(HttpResponse::Ok()
+                        .content_type("text/event-stream")
+                        .insert_header(("Cache-Control", "no-cache"))
+                        .streaming(bus::sse_stream(user.id)))
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+// Deprecated aliases
+// The pre-v1 routes, kept under /api until clients have moved over. They
+// take ids from query strings or bodies and share the v1 handlers' logic;
+// configure marks every response with a Deprecation header.
+#[derive(Deserialize)]
+struct LegacyCreateTeamReqBody {
+    event_id: String,
+    #[serde(flatten)]
+    team: CreateTeamReqBody,
+}
+
+#[derive(Deserialize)]
+struct LegacyCreateRequestReqBody {
+    team_id: String,
+    #[serde(flatten)]
+    request: CreateRequestReqBody,
+}
+
+#[get("/users")]
+async fn get_user_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<UserIdQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_user(req, &repos, query.user_id.as_ref()).await;
+}
+
+#[delete("/events")]
+async fn delete_event_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<EventIdQuery>) -> Result<HttpResponse, Error> {
+    // Pre-v1 contract: any signed-in user may delete, answered with 201
+    match auth::parse_token(req) {
+        Some(token) => {
+            match auth::verification(token).await {
+                Ok(_) => {
+                    let event_id = query.event_id.clone();
+                    match repos.run(move |r| r.events.delete_event(&event_id)).await {
+                        Ok(_) => return Ok(HttpResponse::Created().content_type("text/html").body("0w0")),
+                        Err(e) => return Ok(internal_error(e))
+                    };
+                },
+                Err(_) => return Ok(HttpResponse::Unauthorized().finish())
+            }
+        },
+        None => return Ok(HttpResponse::Unauthorized().finish())
+    };
+}
+
+#[post("/solos")]
+async fn create_solo_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<EventIdQuery>) -> Result<HttpResponse, Error> {
+    return handle_create_solo(req, &repos, &query.event_id).await;
+}
+
+#[get("/solos")]
+async fn get_solo_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<EventIdQuery>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_solo(req, &repos, &query.event_id, page).await;
+}
+
+#[post("/teams")]
+async fn create_team_legacy(req: HttpRequest, repos: web::Data<Repos>, body: web::Json<LegacyCreateTeamReqBody>) -> Result<HttpResponse, Error> {
+    return handle_create_team(req, &repos, &body.event_id, &body.team).await;
+}
+
+#[get("/teams")]
+async fn get_team_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<TeamIdQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_team(req, &repos, &query.team_id).await;
+}
+
+#[get("/teams/event")]
+async fn get_team_by_event_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<EventIdQuery>, page: web::Query<PageQuery>) -> Result<HttpResponse, Error> {
+    return handle_get_team_by_event(req, &repos, &query.event_id, page).await;
+}
+
+#[post("/joins")]
+async fn create_join_legacy(req: HttpRequest, repos: web::Data<Repos>, query: web::Query<TeamIdQuery>) -> Result<HttpResponse, Error> {
+    return handle_create_join(req, &repos, &query.team_id).await;
+}
+
+#[post("/requests")]
+async fn create_request_legacy(req: HttpRequest, repos: web::Data<Repos>, body: web::Json<LegacyCreateRequestReqBody>) -> Result<HttpResponse, Error> {
+    return handle_create_request(req, &repos, &body.team_id, &body.request).await;
+}
+
+// Routes
+/// Every route, shared by the server and the integration tests
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let config = config::get();
+    cfg.service(index)
+        .service(healthz)
+        .service(readyz)
+        .service(version)
+        .service(get_metrics)
+        .service(web::scope("/api/v1")
+            .configure(|cfg| {
+                if config.features.api_docs {
+                    cfg.service(openapi_json)
+                        .service(swagger_ui);
+                }
+            })
+            .service(create_user)
+            .service(get_current_user)
+            .service(get_user)
+            .service(update_user)
+            .service(get_email_preference)
+            .service(set_email_preference)
+            .service(confirm_unsubscribe_email)
+            .service(unsubscribe_email)
+            .service(confirm_verify_email)
+            .service(verify_email)
+            .service(set_user_skill)
+            .service(delete_user_skill)
+            .service(create_skill)
+            .service(get_skill)
+            .service(create_event)
+            .service(get_event)
+            .service(delete_event)
+            .service(get_recommendation)
+            .service(preview_auto_team)
+            .service(create_auto_team)
+            .service(create_webhook)
+            .service(get_webhook)
+            .service(delete_webhook)
+            .service(get_webhook_delivery)
+            .service(redeliver_webhook)
+            .service(get_event_integration)
+            .service(set_event_integration)
+            .service(delete_event_integration)
+            .service(create_solo)
+            .service(get_solo)
+            .service(create_team)
+            .service(get_team_by_event)
+            .service(get_team)
+            .service(delete_team)
+            .service(set_team_wanted_role)
+            .service(delete_team_wanted_role)
+            .service(create_join)
+            .service(delete_join)
+            .service(create_request)
+            .service(accept_request)
+            .service(decline_request)
+            .service(get_request)
+            .service(get_notification)
+            .service(read_notification)
+            .service(read_all_notification)
+            .service(stream_notification)
+        )
+        // Pre-v1 routes, kept as aliases until clients have moved over
+        .service(web::scope("/api")
+            .wrap(DefaultHeaders::new()
+                .add(("Deprecation", "true"))
+                .add((header::LINK, "</api/v1/docs>; rel=\"deprecation\"")))
+            .service(create_user)
+            .service(get_user_legacy)
+            .service(create_event)
+            .service(get_event)
+            .service(delete_event_legacy)
+            .service(create_solo_legacy)
+            .service(get_solo_legacy)
+            .service(create_team_legacy)
+            .service(get_team_legacy)
+            .service(get_team_by_event_legacy)
+            .service(create_join_legacy)
+            .service(create_request_legacy)
+            .service(get_request)
+        );
+}
+
+// Docs
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "hotchpotch API"),
+    servers((url = "/api/v1")),
+    paths(
+        create_user,
+        get_current_user,
+        get_user,
+        update_user,
+        get_email_preference,
+        set_email_preference,
+        confirm_unsubscribe_email,
+        unsubscribe_email,
+        confirm_verify_email,
+        verify_email,
+        set_user_skill,
+        delete_user_skill,
+        create_skill,
+        get_skill,
+        create_event,
+        get_event,
+        delete_event,
+        get_recommendation,
+        preview_auto_team,
+        create_auto_team,
+        create_webhook,
+        get_webhook,
+        delete_webhook,
+        get_webhook_delivery,
+        redeliver_webhook,
+        get_event_integration,
+        set_event_integration,
+        delete_event_integration,
+        create_solo,
+        get_solo,
+        create_team,
+        get_team,
+        get_team_by_event,
+        delete_team,
+        set_team_wanted_role,
+        delete_team_wanted_role,
+        create_join,
+        delete_join,
+        create_request,
+        accept_request,
+        decline_request,
+        get_request,
+        get_notification,
+        read_notification,
+        read_all_notification,
+        stream_notification,
+    ),
+    modifiers(&SecurityAddon),
+    security(("bearer_auth" = [])),
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        // GitHub access token, as sent by the frontend
+        components.add_security_scheme("bearer_auth", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("access_token", SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::new("access_token"))));
+    }
+}
+
+#[get("/openapi.json")]
+async fn openapi_json() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(ApiDoc::openapi()))
+}
+
+// Swagger UI is loaded from a CDN so nothing has to be bundled at build time
+#[get("/docs")]
+async fn swagger_ui() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().content_type("text/html").body(SWAGGER_UI_HTML))
+}
+
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>hotchpotch API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({url: "/api/v1/openapi.json", dom_id: "#swagger-ui"});
+    };
+  </script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod router_tests {
+    use super::*;
+
+    #[test]
+    fn legacy_bodies_still_parse() {
+        let body: LegacyCreateTeamReqBody = serde_json::from_str(
+            r#"{"event_id": "e", "name": "Crabs", "desc": "", "capacity": 4}"#,
+        ).unwrap();
+        assert_eq!(body.event_id, "e");
+        assert_eq!(body.team.name, "Crabs");
+        assert_eq!(body.team.capacity, Some(4));
+    }
+}
@@ -1,5 +1,64 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    email_preferences (user_id) {
+        user_id -> Uuid,
+        #[max_length = 255]
+        email -> Varchar,
+        #[max_length = 10]
+        locale -> Varchar,
+        on_request -> Bool,
+        on_join -> Bool,
+        on_team -> Bool,
+        unsubscribe_token -> Uuid,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        verification_token -> Uuid,
+        verified_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    email_queue (id) {
+        id -> Uuid,
+        #[max_length = 255]
+        recipient -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        body -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    event_integrations (event_id) {
+        event_id -> Uuid,
+        #[max_length = 10]
+        platform -> Varchar,
+        #[max_length = 400]
+        webhook_url -> Varchar,
+        notify_recruiting -> Bool,
+        notify_full -> Bool,
+        notify_solos -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    event_organizers (event_id, user_id) {
+        event_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     events (id) {
         id -> Uuid,
@@ -8,6 +67,8 @@ diesel::table! {
         url -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        ended_at -> Nullable<Timestamp>,
     }
 }
 
@@ -17,6 +78,22 @@ diesel::table! {
         user_id -> Uuid,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        #[max_length = 50]
+        filled_role -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 30]
+        kind -> Varchar,
+        team_id -> Uuid,
+        actor_id -> Uuid,
+        read_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -28,6 +105,22 @@ diesel::table! {
         message -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        #[max_length = 20]
+        status -> Varchar,
+    }
+}
+
+diesel::table! {
+    skills (id) {
+        id -> Uuid,
+        #[max_length = 50]
+        name -> Varchar,
+        #[max_length = 20]
+        kind -> Varchar,
+        #[max_length = 50]
+        category -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -40,6 +133,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    team_wanted_roles (team_id, role) {
+        team_id -> Uuid,
+        #[max_length = 50]
+        role -> Varchar,
+        count -> Int4,
+        filled -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     teams (id) {
         id -> Uuid,
@@ -51,6 +156,17 @@ diesel::table! {
         desc -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        capacity -> Int4,
+    }
+}
+
+diesel::table! {
+    user_skills (user_id, skill_id) {
+        user_id -> Uuid,
+        skill_id -> Uuid,
+        level -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -65,23 +181,77 @@ diesel::table! {
         profile -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        #[max_length = 100]
+        display_name -> Nullable<Varchar>,
+        links -> Array<Nullable<Varchar>>,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        subscription_id -> Uuid,
+        #[max_length = 50]
+        event_type -> Varchar,
+        payload -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webhook_subscriptions (id) {
+        id -> Uuid,
+        event_id -> Uuid,
+        #[max_length = 400]
+        url -> Varchar,
+        #[max_length = 100]
+        secret -> Varchar,
+        event_types -> Array<Nullable<Varchar>>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
+diesel::joinable!(email_preferences -> users (user_id));
+diesel::joinable!(event_integrations -> events (event_id));
+diesel::joinable!(event_organizers -> events (event_id));
+diesel::joinable!(event_organizers -> users (user_id));
 diesel::joinable!(joins -> teams (team_id));
 diesel::joinable!(joins -> users (user_id));
+diesel::joinable!(notifications -> users (user_id));
 diesel::joinable!(requests -> teams (team_id));
 diesel::joinable!(requests -> users (user_id));
 diesel::joinable!(solos -> events (event_id));
 diesel::joinable!(solos -> users (user_id));
+diesel::joinable!(team_wanted_roles -> teams (team_id));
 diesel::joinable!(teams -> events (event_id));
 diesel::joinable!(teams -> users (reader_id));
+diesel::joinable!(user_skills -> skills (skill_id));
+diesel::joinable!(user_skills -> users (user_id));
+diesel::joinable!(webhook_deliveries -> webhook_subscriptions (subscription_id));
+diesel::joinable!(webhook_subscriptions -> events (event_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    email_preferences,
+    email_queue,
+    event_integrations,
+    event_organizers,
     events,
     joins,
+    notifications,
     requests,
+    skills,
     solos,
+    team_wanted_roles,
     teams,
+    user_skills,
     users,
+    webhook_deliveries,
+    webhook_subscriptions,
 );
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{anyhow, Context};
+use chrono::{Duration, NaiveDateTime, Utc};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::auth;
+use crate::cli::SeedArgs;
+use crate::cruds;
+use crate::models::{Skill, Team, User};
+use crate::pagination::{Order, PageParams, SortKey, MAX_LIMIT};
+
+// Seeded logins start with this so they are easy to tell from real ones
+const LOGIN_PREFIX: &str = "demo-";
+
+const FIRST_NAMES: [&str; 24] = [
+    "Aiko", "Ben", "Chloe", "Daiki", "Elena", "Farid", "Grace", "Haruto",
+    "Ines", "Jonas", "Kenta", "Lena", "Mateo", "Nadia", "Oliver", "Priya",
+    "Quentin", "Rina", "Sofia", "Takumi", "Uma", "Victor", "Wen", "Yuki",
+];
+const LAST_NAMES: [&str; 16] = [
+    "Tanaka", "Smith", "Garcia", "Kim", "Müller", "Rossi", "Sato", "Nguyen",
+    "Kowalski", "Haddad", "Suzuki", "Silva", "Novak", "Ito", "Dubois", "Patel",
+];
+const EVENT_NAMES: [&str; 8] = [
+    "Spring Hack", "Open Data Jam", "Game Jam", "AI Hackathon",
+    "Civic Tech Weekend", "HealthHack", "EdTech Sprint", "Climate Hack",
+];
+const TEAM_ADJECTIVES: [&str; 10] = [
+    "Night", "Byte", "Rusty", "Quantum", "Pixel", "Lazy", "Async", "Blue", "Tiny", "Stack",
+];
+const TEAM_NOUNS: [&str; 10] = [
+    "Owls", "Bandits", "Crabs", "Foxes", "Pandas", "Wizards", "Otters", "Rockets", "Giants", "Llamas",
+];
+// (name, kind, category); categories double as the roles teams look for
+const SKILLS: [(&str, &str, Option<&str>); 15] = [
+    ("React", "skill", Some("frontend")),
+    ("TypeScript", "skill", Some("frontend")),
+    ("Vue", "skill", Some("frontend")),
+    ("Rust", "skill", Some("backend")),
+    ("Go", "skill", Some("backend")),
+    ("Python", "skill", Some("backend")),
+    ("PostgreSQL", "skill", Some("backend")),
+    ("Figma", "skill", Some("design")),
+    ("Illustration", "skill", Some("design")),
+    ("PyTorch", "skill", Some("ml")),
+    ("Data analysis", "skill", Some("ml")),
+    ("Game development", "interest", None),
+    ("Open data", "interest", None),
+    ("Healthcare", "interest", None),
+    ("Education", "interest", None),
+];
+const ROLES: [&str; 4] = ["frontend", "backend", "design", "ml"];
+const REQUEST_MESSAGES: [&str; 4] = [
+    "Hi! I'd love to join, I can help with the backend.",
+    "Your idea sounds great, is there room for one more?",
+    "First hackathon for me, happy to help wherever needed.",
+    "",
+];
+
+/// A seeded user and the mock token that signs in as them
+#[derive(Serialize)]
+pub struct SeededUser {
+    pub login: String,
+    pub user_id: String,
+    pub token: String,
+}
+
+#[derive(Default, Debug)]
+pub struct Summary {
+    pub events: usize,
+    pub users: usize,
+    pub teams: usize,
+    pub solos: usize,
+    pub requests: usize,
+}
+
+/// Fill the database with demo data through the crud layer. The same seed
+/// gives the same data, apart from the IDs the database assigns and event
+/// dates, which follow today's date so there are always finished, ongoing
+/// and upcoming events.
+pub fn run(args: &SeedArgs) -> anyhow::Result<Summary> {
+    if args.users == 0 && args.events > 0 {
+        return Err(anyhow!("Events need at least one user to organize them"));
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(args.seed);
+    let logins = logins(&mut rng, args.users);
+    for (login, _) in &logins {
+        if cruds::get_user_info_by_name(login).is_ok() {
+            return Err(anyhow!("{} already exists; seed an empty database or pick another --seed", login));
+        }
+    }
+    let skills = ensure_skills()?;
+    let mut summary = Summary::default();
+    let mut users = vec![];
+    let mut seeded = vec![];
+    for (login, display_name) in &logins {
+        let user = seed_user(&mut rng, login, display_name, &skills)?;
+        seeded.push(SeededUser{login: login.clone(), user_id: user.id.to_string(), token: auth::mock_token(login)});
+        users.push(user);
+    }
+    summary.users = users.len();
+    let today = Utc::now().date_naive().and_hms_opt(9, 0, 0).unwrap();
+    for i in 0..args.events {
+        seed_event(&mut rng, i, today, &users, &mut summary)?;
+        summary.events += 1;
+    }
+    let json = serde_json::to_string_pretty(&seeded)?;
+    fs::write(&args.tokens, json)
+        .with_context(|| format!("Failed to write {}", args.tokens.display()))?;
+    Ok(summary)
+}
+
+// Unique logins with the display names they belong to
+fn logins(rng: &mut ChaCha8Rng, count: usize) -> Vec<(String, String)> {
+    (1..=count)
+        .map(|i| {
+            let first = FIRST_NAMES.choose(rng).unwrap();
+            let last = LAST_NAMES.choose(rng).unwrap();
+            (format!("{}{}{}", LOGIN_PREFIX, first.to_lowercase(), i), format!("{} {}", first, last))
+        })
+        .collect()
+}
+
+// The seed's skills by name, created where missing
+fn ensure_skills() -> anyhow::Result<HashMap<&'static str, Skill>> {
+    let mut existing = HashMap::new();
+    let mut page = PageParams{limit: MAX_LIMIT, offset: 0, sort: SortKey::Name, order: Order::Asc};
+    loop {
+        let skills = cruds::get_skill_list(&page)?;
+        let done = skills.next_cursor.is_none();
+        existing.extend(skills.items.into_iter().map(|s| (s.name.clone(), s)));
+        if done {
+            break;
+        }
+        page.offset += page.limit;
+    }
+    let mut skills = HashMap::new();
+    for (name, kind, category) in SKILLS {
+        let skill = match existing.remove(name) {
+            Some(s) => s,
+            None => cruds::create_skill(&name.to_string(), &kind.to_string(), category.map(|c| c.to_string()).as_ref())?,
+        };
+        skills.insert(name, skill);
+    }
+    Ok(skills)
+}
+
+fn seed_user(rng: &mut ChaCha8Rng, login: &String, display_name: &String, skills: &HashMap<&str, Skill>) -> anyhow::Result<User> {
+    let interest = SKILLS.iter().filter(|(_, kind, _)| *kind == "interest").collect::<Vec<_>>().choose(rng).unwrap().0;
+    let profile = format!("Hacking on {} things in my spare time.", interest.to_lowercase());
+    let user = cruds::create_user(login, &auth::mock_avatar_url(login), &profile)?;
+    let links = vec![Some(format!("https://github.com/{}", login))];
+    cruds::update_user_profile(&user.id.to_string(), None, Some(display_name), Some(&links))?;
+    let count = rng.gen_range(2..=4);
+    for (name, _, _) in SKILLS.choose_multiple(rng, count) {
+        let level = rng.gen_range(1..=5);
+        cruds::set_user_skill(&user.id.to_string(), &skills[name].id.to_string(), &level)?;
+    }
+    Ok(user)
+}
+
+fn seed_event(
+    rng: &mut ChaCha8Rng,
+    index: usize,
+    today: NaiveDateTime,
+    users: &[User],
+    summary: &mut Summary
+) -> anyhow::Result<()> {
+    // Finished, ongoing, then upcoming further and further away
+    let started_at = match index % 3 {
+        0 => today - Duration::days(30 + index as i64),
+        1 => today - Duration::days(1),
+        _ => today + Duration::days(14 * index as i64),
+    };
+    let ended_at = started_at + Duration::days(2);
+    let name = format!("{} {}", EVENT_NAMES[index % EVENT_NAMES.len()], index / EVENT_NAMES.len() + 1);
+    let desc = format!("A weekend of building things together. Seeded demo event #{}.", index + 1);
+    let url = format!("https://example.com/events/{}", index + 1);
+    let organizer = users.choose(rng).unwrap();
+    let event = cruds::create_event(&name, &desc, &url, Some(&started_at), Some(&ended_at), &organizer.id.to_string())?;
+    let event_id = event.id.to_string();
+
+    let mut participants: Vec<&User> = users.iter().collect();
+    participants.shuffle(rng);
+    participants.truncate(users.len() * rng.gen_range(40..=70) / 100);
+
+    let mut team_names: Vec<String> = TEAM_ADJECTIVES.iter()
+        .flat_map(|a| TEAM_NOUNS.iter().map(move |n| format!("{} {}", a, n)))
+        .collect();
+    team_names.shuffle(rng);
+    let mut teams: Vec<(Team, usize)> = vec![];
+    let mut rest = participants.into_iter();
+    for team_name in team_names.into_iter().take(rest.len() / 4) {
+        let leader = match rest.next() {
+            Some(u) => u,
+            None => break,
+        };
+        let capacity = rng.gen_range(3..=5);
+        let desc = format!("{} building something for {}.", team_name, name);
+        let team = cruds::create_team(&event_id, &leader.id.to_string(), &team_name, &desc, Some(&capacity))?;
+        let team_id = team.id.to_string();
+        if rng.gen_bool(0.6) {
+            let role = ROLES.choose(rng).unwrap().to_string();
+            cruds::set_team_wanted_role(&team_id, &role, &rng.gen_range(1..=2))?;
+        }
+        let mut size = 1;
+        for _ in 0..rng.gen_range(0..capacity) {
+            if let Some(member) = rest.next() {
+                cruds::create_join(&team_id, &member.id.to_string())?;
+                size += 1;
+            }
+        }
+        teams.push((team, size));
+    }
+    summary.teams += teams.len();
+
+    // Everyone left is looking for a team, and some of them ask to join one
+    for solo in rest {
+        let user_id = solo.id.to_string();
+        cruds::create_solo(&event_id, &user_id)?;
+        summary.solos += 1;
+        if !rng.gen_bool(0.5) {
+            continue;
+        }
+        let open: Vec<usize> = (0..teams.len())
+            .filter(|&i| (teams[i].1 as i32) < teams[i].0.capacity)
+            .collect();
+        let (team, size) = match open.choose(rng) {
+            Some(&i) => &mut teams[i],
+            None => continue,
+        };
+        let team_id = team.id.to_string();
+        let message = REQUEST_MESSAGES.choose(rng).unwrap().to_string();
+        cruds::create_request(&team_id, &user_id, &message)?;
+        summary.requests += 1;
+        match rng.gen_range(0..3) {
+            0 => {
+                cruds::accept_request(&team_id, &user_id)?;
+                *size += 1;
+                summary.solos -= 1;
+            },
+            1 => {
+                cruds::decline_request(&team_id, &user_id)?;
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod seed_tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_logins() {
+        let first = logins(&mut ChaCha8Rng::seed_from_u64(42), 40);
+        assert_eq!(first, logins(&mut ChaCha8Rng::seed_from_u64(42), 40));
+        assert_ne!(first, logins(&mut ChaCha8Rng::seed_from_u64(7), 40));
+        let unique: std::collections::HashSet<&String> = first.iter().map(|(login, _)| login).collect();
+        assert_eq!(unique.len(), 40);
+        assert!(first.iter().all(|(login, _)| login.starts_with(LOGIN_PREFIX)));
+    }
+}
@@ -0,0 +1,131 @@
+use std::future::{ready, Ready};
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+// Longest incoming request ID we pass on as is
+const REQUEST_ID_MAX_LENGTH: usize = 128;
+// Probes come every few seconds and would drown out real traffic
+const QUIET_PATHS: [&str; 2] = ["/healthz", "/readyz"];
+
+/// Install the global subscriber. RUST_LOG, when set, wins over the configured
+/// level. Records from the `log` crate are forwarded too.
+pub fn init(config: &Config) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(&config.log_level));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if config.log_format == "json" {
+        builder.json().flatten_event(true).with_current_span(true).with_span_list(false).init();
+    } else {
+        builder.init();
+    }
+}
+
+// The caller's ID if it is reasonable to echo back, otherwise a fresh one
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers().get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= REQUEST_ID_MAX_LENGTH)
+        .filter(|v| v.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)))
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Middleware running each request in a span carrying its ID, logging the
+/// outcome, and returning the ID in `X-Request-Id`
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware{service}))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let id = request_id(&req);
+        let quiet = QUIET_PATHS.contains(&req.path());
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            path = %req.path(),
+        );
+        let fut = span.in_scope(|| self.service.call(req));
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let status = res.status().as_u16();
+            let latency_ms = started.elapsed().as_millis() as u64;
+            if res.status().is_server_error() {
+                tracing::error!(status, latency_ms, "Request failed");
+            } else if !quiet {
+                tracing::info!(status, latency_ms, "Request completed");
+            }
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(res)
+        }.instrument(span))
+    }
+}
+
+#[cfg(test)]
+mod telemetry_tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn echoes_or_generates_request_ids() {
+        let app = init_service(
+            App::new()
+                .wrap(RequestTracing)
+                .route("/", web::get().to(HttpResponse::Ok))
+        ).await;
+        let req = TestRequest::get().uri("/").insert_header((REQUEST_ID_HEADER, "abc-123")).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+        // Anything that could forge log lines is replaced
+        let req = TestRequest::get().uri("/").insert_header((REQUEST_ID_HEADER, "a b")).to_request();
+        let res = call_service(&app, req).await;
+        let id = res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Column limits, kept in sync with the varchar sizes in schema.rs
+pub const USER_DISPLAY_NAME_MAX_LENGTH: usize = 100;
+pub const USER_PROFILE_MAX_LENGTH: usize = 400;
+pub const USER_LINK_MAX_LENGTH: usize = 400;
+pub const USER_LINKS_MAX_COUNT: usize = 5;
+pub const SKILL_NAME_MAX_LENGTH: usize = 50;
+pub const SKILL_CATEGORY_MAX_LENGTH: usize = 50;
+pub const SKILL_KINDS: [&str; 2] = ["skill", "interest"];
+pub const SKILL_LEVEL_MIN: i32 = 1;
+pub const SKILL_LEVEL_MAX: i32 = 5;
+pub const WANTED_ROLE_MAX_LENGTH: usize = 50;
+pub const TEAM_CAPACITY_MAX: i32 = 20;
+pub const EMAIL_MAX_LENGTH: usize = 255;
+pub const WEBHOOK_URL_MAX_LENGTH: usize = 400;
+pub const CHAT_WEBHOOK_URL_MAX_LENGTH: usize = 400;
+pub const WEBHOOK_SECRET_MIN_LENGTH: usize = 16;
+pub const WEBHOOK_SECRET_MAX_LENGTH: usize = 100;
+
+// Body of a 422 response, keyed by the offending request field
+#[derive(Serialize, Default, Debug, ToSchema)]
+pub struct FieldErrors {
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl FieldErrors {
+    pub fn new() -> FieldErrors {
+        FieldErrors::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn add(&mut self, field: &str, message: String) {
+        self.errors.entry(field.to_string()).or_default().push(message);
+    }
+
+    /// varchar(n) counts characters, not bytes
+    pub fn check_max_length(&mut self, field: &str, value: &str, max: usize) {
+        if value.chars().count() > max {
+            self.add(field, format!("must be at most {} characters", max));
+        }
+    }
+
+    pub fn check_not_blank(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, "must not be blank".to_string());
+        }
+    }
+
+    pub fn check_one_of(&mut self, field: &str, value: &str, allowed: &[&str]) {
+        if !allowed.contains(&value) {
+            self.add(field, format!("must be one of {}", allowed.join(", ")));
+        }
+    }
+
+    pub fn check_range(&mut self, field: &str, value: i32, min: i32, max: i32) {
+        if value < min || value > max {
+            self.add(field, format!("must be between {} and {}", min, max));
+        }
+    }
+
+    // Only catches obvious typos; the SMTP server has the final say
+    pub fn check_email(&mut self, field: &str, value: &str) {
+        match value.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && domain.contains('.') && !value.contains(char::is_whitespace) => {},
+            _ => self.add(field, "must be an email address".to_string()),
+        }
+    }
+
+    pub fn check_url(&mut self, field: &str, value: &str) {
+        if !(value.starts_with("https://") || value.starts_with("http://")) {
+            self.add(field, "must be an http(s) URL".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+
+    #[test]
+    fn collects_errors_per_field() {
+        let mut errors = FieldErrors::new();
+        errors.check_max_length("profile", &"あ".repeat(USER_PROFILE_MAX_LENGTH), USER_PROFILE_MAX_LENGTH);
+        assert!(errors.is_empty());
+        errors.check_max_length("profile", &"a".repeat(USER_PROFILE_MAX_LENGTH + 1), USER_PROFILE_MAX_LENGTH);
+        errors.check_url("links[0]", "ftp://example.com");
+        errors.check_not_blank("display_name", "  ");
+        assert_eq!(errors.errors.len(), 3);
+        assert_eq!(errors.errors["profile"], vec!["must be at most 400 characters".to_string()]);
+    }
+}
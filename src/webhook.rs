@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{cruds, outbound};
+use crate::models::{WebhookDelivery, WebhookSubscription};
+
+// Attempts before a delivery is given up on; it can still be redelivered by hand
+pub const MAX_ATTEMPTS: i32 = 8;
+const FIRST_RETRY_SECS: i32 = 10;
+const MAX_RETRY_SECS: i32 = 6 * 60 * 60;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 50;
+
+pub const SIGNATURE_HEADER: &str = "X-Hotchpotch-Signature";
+pub const EVENT_HEADER: &str = "X-Hotchpotch-Event";
+pub const DELIVERY_HEADER: &str = "X-Hotchpotch-Delivery";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WebhookEvent {
+    TeamCreated,
+    SoloCreated,
+    JoinCreated,
+    RequestCreated,
+    RequestAccepted,
+    RequestDeclined,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 6] = [
+        WebhookEvent::TeamCreated,
+        WebhookEvent::SoloCreated,
+        WebhookEvent::JoinCreated,
+        WebhookEvent::RequestCreated,
+        WebhookEvent::RequestAccepted,
+        WebhookEvent::RequestDeclined,
+    ];
+
+    // Value subscribers list in event_types and receive in `type`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TeamCreated => "team.created",
+            WebhookEvent::SoloCreated => "solo.created",
+            WebhookEvent::JoinCreated => "join.created",
+            WebhookEvent::RequestCreated => "request.created",
+            WebhookEvent::RequestAccepted => "request.accepted",
+            WebhookEvent::RequestDeclined => "request.declined",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        WebhookEvent::ALL.iter().map(|e| e.as_str()).collect()
+    }
+}
+
+pub fn subscribes_to(subscription: &WebhookSubscription, event: WebhookEvent) -> bool {
+    subscription.event_types.iter().any(|t| t.as_deref() == Some(event.as_str()))
+}
+
+/// JSON body of a delivery. `data` is the created row.
+pub fn payload(delivery_id: &Uuid, event: WebhookEvent, event_id: &Uuid, data: serde_json::Value) -> String {
+    json!({
+        "id": delivery_id,
+        "type": event.as_str(),
+        "event_id": event_id,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "data": data,
+    }).to_string()
+}
+
+/// Value of the signature header: `t=<unix time>,v1=<hex HMAC-SHA256>`.
+/// Receivers recompute the HMAC over `<unix time>.<body>` with their secret
+/// and should refuse old timestamps to stop replays.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Random secret handed out when the organizer doesn't pick one
+pub fn generate_secret() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Seconds to wait before the next attempt once `attempts` have failed
+pub fn backoff(attempts: i32) -> i32 {
+    let exp = (attempts.max(1) - 1).min(16) as u32;
+    (FIRST_RETRY_SECS * 2_i32.pow(exp)).min(MAX_RETRY_SECS)
+}
+
+// Returns the response status; anything but 2xx counts as a failure.
+// The URL is checked again on every attempt, since its host may resolve
+// somewhere else by now.
+fn send(subscription: &WebhookSubscription, delivery: &WebhookDelivery) -> (Option<i32>, anyhow::Result<()>) {
+    let client = match outbound::client(&subscription.url, REQUEST_TIMEOUT) {
+        Ok(client) => client,
+        Err(e) => return (None, Err(e).with_context(|| "Refused to deliver")),
+    };
+    let signature = sign(&subscription.secret, chrono::Utc::now().timestamp(), &delivery.payload);
+    let res = client.post(&subscription.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(SIGNATURE_HEADER, signature)
+        .body(delivery.payload.clone())
+        .send();
+    match res {
+        Ok(res) if res.status().is_success() => (Some(res.status().as_u16() as i32), Ok(())),
+        Ok(res) => (Some(res.status().as_u16() as i32), Err(anyhow::anyhow!("Receiver responded {}", res.status()))),
+        Err(e) => (None, Err(e).with_context(|| "Failed to reach receiver")),
+    }
+}
+
+/// Send every delivery that is due. Failures are rescheduled with backoff.
+pub fn deliver_pending() -> anyhow::Result<usize> {
+    let due = cruds::get_due_webhook_deliveries(BATCH_SIZE)?;
+    let mut delivered = 0;
+    for (delivery, subscription) in due {
+        let attempts = delivery.attempts + 1;
+        match send(&subscription, &delivery) {
+            (status, Ok(_)) => {
+                cruds::mark_webhook_delivered(&delivery.id, attempts, status)?;
+                delivered += 1;
+            },
+            (status, Err(e)) => {
+                cruds::mark_webhook_failed(&delivery.id, attempts, status, backoff(attempts), &format!("{:#}", e))?;
+            },
+        }
+    }
+    Ok(delivered)
+}
+
+/// Poll for due deliveries on a background thread, one worker per process
+pub fn spawn_worker() {
+    std::thread::spawn(|| loop {
+        if let Err(e) = deliver_pending() {
+            tracing::warn!(error = format!("{:#}", e), "Webhook delivery failed");
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1700000000, "{\"a\":1}"),
+            "t=1700000000,v1=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686",
+        );
+        assert_ne!(sign("secret", 1700000001, "{\"a\":1}"), sign("secret", 1700000000, "{\"a\":1}"));
+    }
+
+    #[test]
+    fn payload_carries_type_and_ids() {
+        let id = Uuid::from_u128(1);
+        let event_id = Uuid::from_u128(2);
+        let body: serde_json::Value = serde_json::from_str(&payload(&id, WebhookEvent::TeamCreated, &event_id, json!({"name": "Crabs"}))).unwrap();
+        assert_eq!(body["type"], "team.created");
+        assert_eq!(body["id"], id.to_string());
+        assert_eq!(body["event_id"], event_id.to_string());
+        assert_eq!(body["data"]["name"], "Crabs");
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(backoff(1), 10);
+        assert_eq!(backoff(3), 40);
+        assert_eq!(backoff(MAX_ATTEMPTS + 10), MAX_RETRY_SECS);
+    }
+}
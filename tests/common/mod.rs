@@ -0,0 +1,130 @@
+// Shared by the integration tests. Every test binary talks to one pooled
+// connection that sits inside a transaction which is never committed, and
+// every test runs inside a savepoint of it that is rolled back afterwards.
+// Point TEST_DATABASE_URL at a scratch database to keep the tests away from
+// DATABASE_URL; migrations are applied to it before the first test.
+#![allow(dead_code)]
+
+use std::env;
+use std::sync::{Mutex, MutexGuard, Once};
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::{test, web, App};
+use diesel::prelude::*;
+use serde::de::DeserializeOwned;
+
+use hotchpotch_web_backend::models::{Event, Team, User};
+use hotchpotch_web_backend::repo::Repos;
+use hotchpotch_web_backend::{auth, config, cruds, db, router};
+
+static INIT: Once = Once::new();
+// The pool has one connection, so tests take turns
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Held for the length of a test; dropping it undoes everything the test wrote
+pub struct TestDb {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        if let Ok(mut conn) = db::establish_connection() {
+            let _ = diesel::sql_query("ROLLBACK TO SAVEPOINT integration_test").execute(&mut conn);
+            let _ = diesel::sql_query("RELEASE SAVEPOINT integration_test").execute(&mut conn);
+        }
+    }
+}
+
+pub fn setup() -> TestDb {
+    INIT.call_once(|| {
+        dotenv::dotenv().ok();
+        if let Ok(url) = env::var("TEST_DATABASE_URL") {
+            env::set_var("DATABASE_URL", url);
+        }
+        // Sign in as anyone with `mock_<login>`, without asking GitHub
+        env::set_var("GITHUB_MOCK_TOKENS", "true");
+        let config = config::init().unwrap_or_else(|e| panic!("{:#}", e));
+        let mut conn = PgConnection::establish(&config.database.url)
+            .expect("Failed to connect the test database");
+        db::migrate(&mut conn).unwrap_or_else(|e| panic!("{:#}", e));
+        db::init_test_pool().unwrap_or_else(|e| panic!("{:#}", e));
+    });
+    let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut conn = db::establish_connection().unwrap();
+    diesel::sql_query("SAVEPOINT integration_test").execute(&mut conn).unwrap();
+    TestDb{_lock: lock}
+}
+
+pub async fn app() -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    test::init_service(App::new().app_data(web::Data::new(Repos::diesel())).configure(router::configure)).await
+}
+
+pub fn bearer(login: &str) -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", auth::mock_token(login)))
+}
+
+pub fn get(uri: &str, login: &str) -> test::TestRequest {
+    test::TestRequest::get().uri(uri).insert_header(bearer(login))
+}
+
+pub fn post(uri: &str, login: &str) -> test::TestRequest {
+    test::TestRequest::post().uri(uri).insert_header(bearer(login))
+}
+
+pub fn put(uri: &str, login: &str) -> test::TestRequest {
+    test::TestRequest::put().uri(uri).insert_header(bearer(login))
+}
+
+pub fn patch(uri: &str, login: &str) -> test::TestRequest {
+    test::TestRequest::patch().uri(uri).insert_header(bearer(login))
+}
+
+pub fn delete(uri: &str, login: &str) -> test::TestRequest {
+    test::TestRequest::delete().uri(uri).insert_header(bearer(login))
+}
+
+/// Send a request and return its status code
+pub async fn status<S, B>(app: &S, req: test::TestRequest) -> u16
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    test::call_service(app, req.to_request()).await.status().as_u16()
+}
+
+/// Send a request, check its status and parse the JSON body
+pub async fn json<S, B, T>(app: &S, req: test::TestRequest, expected: u16) -> T
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+    T: DeserializeOwned,
+{
+    let res = test::call_service(app, req.to_request()).await;
+    assert_eq!(res.status().as_u16(), expected);
+    let body = test::read_body(res).await;
+    serde_json::from_slice(&body)
+        .unwrap_or_else(|e| panic!("{}: {}", e, String::from_utf8_lossy(&body)))
+}
+
+/// Sign up through the API, as the frontend does on first login
+pub async fn sign_up<S, B>(app: &S, login: &str) -> User
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    assert_eq!(status(app, post("/api/v1/users", login)).await, 201);
+    cruds::get_user_info_by_name(&login.to_string()).unwrap()
+}
+
+pub fn create_event(organizer: &User, name: &str) -> Event {
+    let (name, desc, url) = (name.to_string(), String::new(), String::new());
+    cruds::create_event(&name, &desc, &url, None, None, &organizer.id.to_string()).unwrap()
+}
+
+pub fn create_team(event: &Event, leader: &User, name: &str, capacity: i32) -> Team {
+    let (name, desc) = (name.to_string(), String::new());
+    cruds::create_team(&event.id.to_string(), &leader.id.to_string(), &name, &desc, Some(&capacity)).unwrap()
+}
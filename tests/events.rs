@@ -0,0 +1,186 @@
+mod common;
+
+use actix_web::test;
+use serde_json::{json, Value};
+
+use hotchpotch_web_backend::{cruds, formation};
+
+use common::*;
+
+#[actix_web::test]
+async fn creates_lists_and_deletes_events() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let body = json!({"name": "it-Rust Jam", "desc": "Crabs", "url": "https://jam.example", "started_at": "2030-05-01T09:00:00", "ended_at": "2030-05-02T18:00:00"});
+    assert_eq!(status(&app, post("/api/v1/events", "it-octo").set_json(body)).await, 201);
+    let body = json!({"name": "it-Backwards", "desc": "", "url": "", "started_at": "2030-05-02T09:00:00", "ended_at": "2030-05-01T09:00:00"});
+    assert_eq!(status(&app, post("/api/v1/events", "it-octo").set_json(body)).await, 400);
+    let req = test::TestRequest::post().uri("/api/v1/events").set_json(json!({"name": "it-x", "desc": "", "url": ""}));
+    assert_eq!(status(&app, req).await, 401);
+
+    let events: Value = json(&app, get("/api/v1/events?q=it-Rust", "it-octo"), 200).await;
+    assert_eq!(events["total"], 1);
+    let event = &events["items"][0];
+    assert_eq!(event["name"], "it-Rust Jam");
+    let event_id = event["id"].as_str().unwrap().to_string();
+    assert!(cruds::is_event_organizer(&event_id, &octo.id).unwrap());
+
+    let events: Value = json(&app, get("/api/v1/events?q=it-Rust&status=upcoming&from=2030-04-01", "it-octo"), 200).await;
+    assert_eq!(events["total"], 1);
+    let events: Value = json(&app, get("/api/v1/events?q=it-Rust&status=finished", "it-octo"), 200).await;
+    assert_eq!(events["total"], 0);
+    assert_eq!(status(&app, get("/api/v1/events?from=2030-05-02&to=2030-05-01", "it-octo")).await, 400);
+    assert_eq!(status(&app, get("/api/v1/events?sort=started_at", "it-octo")).await, 400);
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/events")).await, 401);
+
+    sign_up(&app, "it-mona").await;
+    assert_eq!(status(&app, delete(&format!("/api/v1/events/{}", event_id), "it-mona")).await, 403);
+    assert_eq!(status(&app, delete(&format!("/api/v1/events/{}", event_id), "it-octo")).await, 204);
+    let events: Value = json(&app, get("/api/v1/events?q=it-Rust", "it-octo"), 200).await;
+    assert_eq!(events["total"], 0);
+}
+
+#[actix_web::test]
+async fn recommends_teams_and_solos() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let hubot = sign_up(&app, "it-hubot").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 4);
+    cruds::create_solo(&event.id.to_string(), &hubot.id.to_string()).unwrap();
+    let uri = format!("/api/v1/events/{}/recommendations", event.id);
+
+    let teams: Value = json(&app, get(&uri, "it-hubot"), 200).await;
+    assert_eq!(teams[0]["candidate"]["id"], team.id.to_string());
+    let solos: Value = json(&app, get(&format!("{}?team_id={}", uri, team.id), "it-octo"), 200).await;
+    assert_eq!(solos[0]["candidate"]["id"], hubot.id.to_string());
+    assert_eq!(status(&app, get(&format!("{}?team_id={}", uri, team.id), "it-mona")).await, 403);
+    assert_eq!(status(&app, get(&format!("{}?team_id={}", uri, uuid::Uuid::new_v4()), "it-octo")).await, 404);
+    assert_eq!(status(&app, test::TestRequest::get().uri(&uri)).await, 401);
+}
+
+#[actix_web::test]
+async fn forms_teams_from_solos() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let event_id = event.id.to_string();
+    for login in ["it-a", "it-b", "it-c", "it-d"] {
+        let user = sign_up(&app, login).await;
+        cruds::create_solo(&event_id, &user.id.to_string()).unwrap();
+    }
+    let preview_uri = format!("/api/v1/events/{}/auto_teams/preview", event.id);
+    let uri = format!("/api/v1/events/{}/auto_teams", event.id);
+
+    let plan: Value = json(&app, post(&preview_uri, "it-octo").set_json(json!({"team_size": 2})), 200).await;
+    assert_eq!(plan["teams"].as_array().unwrap().len(), 2);
+    assert_eq!(plan["unassigned"], json!([]));
+    let errors: Value = json(&app, post(&preview_uri, "it-octo").set_json(json!({"team_size": 1})), 422).await;
+    assert!(errors["errors"]["team_size"].is_array());
+    assert_eq!(status(&app, post(&preview_uri, "it-mona").set_json(json!({"team_size": 2}))).await, 403);
+    let a = cruds::get_user_info_by_name(&"it-a".to_string()).unwrap();
+    let b = cruds::get_user_info_by_name(&"it-b".to_string()).unwrap();
+    let c = cruds::get_user_info_by_name(&"it-c".to_string()).unwrap();
+    let body = json!({"team_size": 2, "together": [[a.id, b.id, c.id]]});
+    assert_eq!(status(&app, post(&preview_uri, "it-octo").set_json(body)).await, 400);
+
+    let body = json!({"team_size": 2, "plan_id": uuid::Uuid::new_v4()});
+    assert_eq!(status(&app, post(&uri, "it-octo").set_json(body)).await, 409);
+    let stale = formation::plan(&cruds::get_event_solo_details(&event_id).unwrap(), 2, &[]).unwrap();
+    let body = json!({"team_size": 2, "plan_id": plan["plan_id"]});
+    let teams: Value = json(&app, post(&uri, "it-octo").set_json(body), 201).await;
+    assert_eq!(teams.as_array().unwrap().len(), 2);
+    let solos: Value = json(&app, get(&format!("/api/v1/events/{}/solos", event.id), "it-octo"), 201).await;
+    assert_eq!(solos["total"], 0);
+    // Its members are no longer solo, so the plan can't be applied again
+    let e = cruds::create_planned_teams(&event_id, &stale.teams, &2).unwrap_err();
+    assert!(matches!(e.downcast_ref(), Some(cruds::CrudError::Conflict(_))));
+}
+
+#[actix_web::test]
+async fn manages_webhooks() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let uri = format!("/api/v1/events/{}/webhooks", event.id);
+
+    let body = json!({"url": "ftp://hooks.example", "secret": "short", "event_types": ["team.deleted"]});
+    let errors: Value = json(&app, post(&uri, "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["url"].is_array());
+    assert!(errors["errors"]["secret"].is_array());
+    assert!(errors["errors"]["event_types[0]"].is_array());
+    // Receivers inside the server's own network are refused
+    for url in ["http://127.0.0.1:9000/in", "http://localhost/in", "http://[::1]/in", "http://169.254.169.254/latest"] {
+        let errors: Value = json(&app, post(&uri, "it-octo").set_json(json!({"url": url, "event_types": ["team.created"]})), 422).await;
+        assert!(errors["errors"]["url"].is_array());
+    }
+    let body = json!({"url": "https://203.0.113.10/in", "event_types": ["team.created"]});
+    assert_eq!(status(&app, post(&uri, "it-mona").set_json(body.clone())).await, 403);
+    let created: Value = json(&app, post(&uri, "it-octo").set_json(body), 201).await;
+    assert!(!created["secret"].as_str().unwrap().is_empty());
+    let webhook_id = created["id"].as_str().unwrap().to_string();
+
+    let webhooks: Value = json(&app, get(&uri, "it-octo"), 200).await;
+    assert_eq!(webhooks.as_array().unwrap().len(), 1);
+    assert!(webhooks[0].get("secret").is_none());
+    assert_eq!(status(&app, get(&uri, "it-mona")).await, 403);
+
+    // A new team is queued for delivery
+    create_team(&event, &octo, "it-Crabs", 4);
+    let deliveries_uri = format!("{}/{}/deliveries", uri, webhook_id);
+    let deliveries: Value = json(&app, get(&deliveries_uri, "it-octo"), 200).await;
+    assert_eq!(deliveries["total"], 1);
+    assert_eq!(deliveries["items"][0]["event_type"], "team.created");
+    assert_eq!(status(&app, get(&format!("{}/{}/deliveries", uri, uuid::Uuid::new_v4()), "it-octo")).await, 404);
+    assert_eq!(status(&app, get(&format!("{}?sort=name", deliveries_uri), "it-octo")).await, 400);
+
+    let delivery_id = deliveries["items"][0]["id"].as_str().unwrap();
+    let redeliver_uri = format!("{}/{}/redeliver", deliveries_uri, delivery_id);
+    assert_eq!(status(&app, post(&redeliver_uri, "it-octo")).await, 202);
+    assert_eq!(status(&app, post(&redeliver_uri, "it-mona")).await, 403);
+    let redeliver_uri = format!("{}/{}/redeliver", deliveries_uri, uuid::Uuid::new_v4());
+    assert_eq!(status(&app, post(&redeliver_uri, "it-octo")).await, 404);
+
+    assert_eq!(status(&app, delete(&format!("{}/{}", uri, webhook_id), "it-mona")).await, 403);
+    assert_eq!(status(&app, delete(&format!("{}/{}", uri, webhook_id), "it-octo")).await, 204);
+    assert_eq!(status(&app, delete(&format!("{}/{}", uri, webhook_id), "it-octo")).await, 404);
+}
+
+#[actix_web::test]
+async fn manages_the_chat_integration() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let uri = format!("/api/v1/events/{}/integration", event.id);
+
+    assert_eq!(status(&app, get(&uri, "it-octo")).await, 404);
+    let body = json!({"platform": "irc", "webhook_url": "nope"});
+    let errors: Value = json(&app, put(&uri, "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["platform"].is_array());
+    assert!(errors["errors"]["webhook_url"].is_array());
+    let body = json!({"platform": "slack", "webhook_url": "http://10.0.0.5/T0"});
+    let errors: Value = json(&app, put(&uri, "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["webhook_url"].is_array());
+    let body = json!({"platform": "slack", "webhook_url": "https://203.0.113.10/T0", "notify_solos": false});
+    assert_eq!(status(&app, put(&uri, "it-mona").set_json(body.clone())).await, 403);
+    let integration: Value = json(&app, put(&uri, "it-octo").set_json(body), 200).await;
+    assert_eq!(integration["platform"], "slack");
+    assert_eq!(integration["notify_solos"], false);
+    let integration: Value = json(&app, get(&uri, "it-octo"), 200).await;
+    assert_eq!(integration["notify_full"], true);
+    assert_eq!(status(&app, get(&uri, "it-mona")).await, 403);
+
+    assert_eq!(status(&app, delete(&uri, "it-mona")).await, 403);
+    assert_eq!(status(&app, delete(&uri, "it-octo")).await, 204);
+    assert_eq!(status(&app, get(&uri, "it-octo")).await, 404);
+    assert_eq!(status(&app, test::TestRequest::get().uri(&uri)).await, 401);
+}
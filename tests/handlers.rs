@@ -0,0 +1,280 @@
+// Handler tests against the in-memory repositories; they need no database.
+// Only routes that go through Repos are exercised here, the rest are
+// covered by the integration tests.
+mod common;
+
+use std::cell::RefCell;
+use std::env;
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::time::Duration;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::{test, web, App};
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use hotchpotch_web_backend::cruds::EventFilter;
+use hotchpotch_web_backend::models::{Event, UserDetail};
+use hotchpotch_web_backend::pagination::{Page, PageParams};
+use hotchpotch_web_backend::repo::{EventRepo, Repos};
+use hotchpotch_web_backend::{config, router};
+
+use common::{delete, get, json, patch, post, put, status};
+
+static INIT: Once = Once::new();
+
+async fn memory_app() -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    app_with(Repos::memory()).await
+}
+
+async fn app_with(repos: Repos) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    INIT.call_once(|| {
+        env::set_var("GITHUB_MOCK_TOKENS", "true");
+        // Required by the config, but never connected to
+        if env::var("DATABASE_URL").is_err() {
+            env::set_var("DATABASE_URL", "postgres://localhost/unused");
+        }
+        config::init().unwrap_or_else(|e| panic!("{:#}", e));
+    });
+    test::init_service(App::new().app_data(web::Data::new(repos)).configure(router::configure)).await
+}
+
+// Signs octo and hubot up; octo organizes the Jam and leads the Crabs
+async fn seeded<S, B>(app: &S) -> (String, String)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    for login in ["octo", "hubot", "mona"] {
+        assert_eq!(status(app, post("/api/v1/users", login)).await, 201);
+    }
+    let body = json!({"name": "Jam", "desc": "", "url": ""});
+    assert_eq!(status(app, post("/api/v1/events", "octo").set_json(body)).await, 201);
+    let events: Value = json(app, get("/api/v1/events", "octo"), 200).await;
+    let event_id = events["items"][0]["id"].as_str().unwrap().to_string();
+    let body = json!({"name": "Crabs", "desc": "", "capacity": 3});
+    assert_eq!(status(app, post(&format!("/api/v1/events/{}/teams", event_id), "octo").set_json(body)).await, 201);
+    let teams: Value = json(app, get(&format!("/api/v1/events/{}/teams", event_id), "octo"), 200).await;
+    let team_id = teams["items"][0]["id"].as_str().unwrap().to_string();
+    (event_id, team_id)
+}
+
+#[actix_web::test]
+async fn reads_and_updates_the_current_user() {
+    let app = memory_app().await;
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/users/me")).await, 401);
+    // Signed in, but never signed up
+    assert_eq!(status(&app, get("/api/v1/users/me", "octo")).await, 500);
+    assert_eq!(status(&app, post("/api/v1/users", "octo")).await, 201);
+    let me: Value = json(&app, get("/api/v1/users/me", "octo"), 200).await;
+    assert_eq!(me["name"], "octo");
+    assert_eq!(me["unread_notifications"], 0);
+
+    let body = json!({"display_name": "Octo Cat", "links": ["https://octo.example"]});
+    let user: Value = json(&app, patch("/api/v1/users/me", "octo").set_json(body), 200).await;
+    assert_eq!(user["display_name"], "Octo Cat");
+    let errors: Value = json(&app, patch("/api/v1/users/me", "octo").set_json(json!({"display_name": " "})), 422).await;
+    assert!(errors["errors"]["display_name"].is_array());
+    let other: Value = json(&app, get(&format!("/api/v1/users/{}", me["id"].as_str().unwrap()), "octo"), 200).await;
+    assert!(other.get("unread_notifications").is_none());
+    assert_eq!(status(&app, get("/api/v1/users/not-a-user", "octo")).await, 404);
+    assert_eq!(status(&app, get("/api/v1/teams/not-a-team", "octo")).await, 404);
+}
+
+#[actix_web::test]
+async fn leaders_answer_requests() {
+    let app = memory_app().await;
+    let (event_id, team_id) = seeded(&app).await;
+    assert_eq!(status(&app, post(&format!("/api/v1/events/{}/solos", event_id), "hubot")).await, 201);
+    let hubot: Value = json(&app, get("/api/v1/users/me", "hubot"), 200).await;
+    let hubot_id = hubot["id"].as_str().unwrap();
+    let uri = format!("/api/v1/teams/{}/requests", team_id);
+    let body = json!({"message": "Room for one more?"});
+    assert_eq!(status(&app, post(&uri, "hubot").set_json(body.clone())).await, 201);
+    assert_eq!(status(&app, post(&uri, "hubot").set_json(body)).await, 409);
+
+    let accept = format!("{}/{}/accept", uri, hubot_id);
+    assert_eq!(status(&app, post(&accept, "mona")).await, 403);
+    assert_eq!(status(&app, post(&accept, "octo")).await, 200);
+    assert_eq!(status(&app, post(&accept, "octo")).await, 404);
+    let solos: Value = json(&app, get(&format!("/api/v1/events/{}/solos", event_id), "octo"), 201).await;
+    assert_eq!(solos["total"], 0);
+    let requests: Value = json(&app, get("/api/v1/requests", "hubot"), 200).await;
+    assert_eq!(requests["items"][0]["status"], "accepted");
+
+    let notifications: Value = json(&app, get("/api/v1/notifications", "hubot"), 200).await;
+    assert_eq!(notifications["items"][0]["kind"], "request_accepted");
+    let uri = format!("/api/v1/notifications/{}/read", notifications["items"][0]["id"].as_str().unwrap());
+    assert_eq!(status(&app, post(&uri, "octo")).await, 404);
+    assert_eq!(status(&app, post(&uri, "hubot")).await, 204);
+    let unread: Value = json(&app, get("/api/v1/notifications?unread=true", "hubot"), 200).await;
+    assert_eq!(unread["total"], 0);
+
+    // Members who leave may ask again
+    let uri = format!("/api/v1/teams/{}/requests", team_id);
+    assert_eq!(status(&app, post(&uri, "hubot").set_json(json!({"message": ""}))).await, 409);
+    assert_eq!(status(&app, delete(&format!("/api/v1/teams/{}/members/me", team_id), "hubot")).await, 204);
+    assert_eq!(status(&app, post(&uri, "hubot").set_json(json!({"message": "Back again"}))).await, 201);
+    let requests: Value = json(&app, get("/api/v1/requests", "hubot"), 200).await;
+    assert_eq!(requests["items"][0]["status"], "pending");
+}
+
+#[actix_web::test]
+async fn only_leaders_manage_teams() {
+    let app = memory_app().await;
+    let (_, team_id) = seeded(&app).await;
+    let roles = format!("/api/v1/teams/{}/roles", team_id);
+    let team: Value = json(&app, put(&roles, "octo").set_json(json!({"role": " Backend ", "count": 2})), 200).await;
+    assert_eq!(team["wanted_roles"][0]["role"], "backend");
+    assert_eq!(status(&app, put(&roles, "mona").set_json(json!({"role": "design", "count": 1}))).await, 403);
+
+    let members = format!("/api/v1/teams/{}/members", team_id);
+    assert_eq!(status(&app, post(&members, "mona")).await, 201);
+    assert_eq!(status(&app, post(&members, "hubot")).await, 201);
+    // octo, mona and hubot fill the Crabs
+    assert_eq!(status(&app, post("/api/v1/users", "lisa")).await, 201);
+    assert_eq!(status(&app, post(&members, "lisa")).await, 409);
+    let uri = format!("/api/v1/teams/{}", team_id);
+    assert_eq!(status(&app, delete(&uri, "mona")).await, 403);
+    assert_eq!(status(&app, delete(&uri, "octo")).await, 204);
+    assert_eq!(status(&app, delete(&uri, "octo")).await, 404);
+    assert_eq!(status(&app, delete("/api/v1/teams/not-a-team", "octo")).await, 404);
+    let notifications: Value = json(&app, get("/api/v1/notifications", "mona"), 200).await;
+    assert_eq!(notifications["items"][0]["kind"], "team_disbanded");
+}
+
+#[actix_web::test]
+async fn legacy_routes_share_the_repositories() {
+    let app = memory_app().await;
+    let (event_id, team_id) = seeded(&app).await;
+    let teams: Value = json(&app, get(&format!("/api/teams/event?event_id={}", event_id), "octo"), 200).await;
+    assert_eq!(teams["items"][0]["id"], team_id);
+    assert_eq!(status(&app, delete(&format!("/api/events?event_id={}", event_id), "mona")).await, 201);
+    let events: Value = json(&app, get("/api/v1/events", "octo"), 200).await;
+    assert_eq!(events["total"], 0);
+}
+
+// Held shut by the test; a query waiting on it stands for a slow database
+#[derive(Default)]
+struct Gate {
+    open: Mutex<bool>,
+    opened: Condvar,
+}
+
+impl Gate {
+    fn open(&self) {
+        *self.open.lock().unwrap() = true;
+        self.opened.notify_all();
+    }
+
+    // The timeout only keeps a broken test from hanging
+    fn wait(&self) -> bool {
+        let open = self.open.lock().unwrap();
+        let (open, _) = self.opened.wait_timeout_while(open, Duration::from_secs(10), |open| !*open).unwrap();
+        *open
+    }
+}
+
+// Events that can't be listed until the gate opens
+struct SlowEvents(Arc<dyn EventRepo>, Arc<Gate>);
+
+impl EventRepo for SlowEvents {
+    fn create_event(
+        &self,
+        name: &String,
+        desc: &String,
+        url: &String,
+        started_at: Option<&NaiveDateTime>,
+        ended_at: Option<&NaiveDateTime>,
+        organizer_id: &String
+    ) -> anyhow::Result<Event> {
+        self.0.create_event(name, desc, url, started_at, ended_at, organizer_id)
+    }
+
+    fn get_event_list(&self, filter: &EventFilter, page: &PageParams) -> anyhow::Result<Page<Event>> {
+        if !self.1.wait() {
+            return Err(anyhow!("The gate was never opened"));
+        }
+        self.0.get_event_list(filter, page)
+    }
+
+    fn is_event_organizer(&self, event_id: &String, user_id: &Uuid) -> anyhow::Result<bool> {
+        self.0.is_event_organizer(event_id, user_id)
+    }
+
+    fn delete_event(&self, event_id: &String) -> anyhow::Result<()> {
+        self.0.delete_event(event_id)
+    }
+
+    fn create_solo(&self, event_id: &String, user_id: &String) -> anyhow::Result<()> {
+        self.0.create_solo(event_id, user_id)
+    }
+
+    fn get_solos(&self, event_id: &String, page: &PageParams) -> anyhow::Result<Page<UserDetail>> {
+        self.0.get_solos(event_id, page)
+    }
+}
+
+// The test runtime has a single worker thread, so a query run on it would
+// hold up every request behind it. The slow queries are only let through
+// once every fast request has been answered.
+#[actix_web::test]
+async fn slow_queries_do_not_stall_other_requests() {
+    let gate = Arc::new(Gate::default());
+    let repos = Repos::memory();
+    let repos = Repos{events: Arc::new(SlowEvents(repos.events.clone(), gate.clone())), ..repos};
+    let app = app_with(repos).await;
+    assert_eq!(status(&app, post("/api/v1/users", "octo")).await, 201);
+
+    let answered = RefCell::new(Vec::new());
+    let slow = join_all((0..4).map(|_| async {
+        let status = status(&app, get("/api/v1/events", "octo")).await;
+        answered.borrow_mut().push("slow");
+        status
+    }));
+    let fast = async {
+        let statuses = join_all((0..20).map(|_| async {
+            let status = status(&app, get("/api/v1/users/me", "octo")).await;
+            answered.borrow_mut().push("fast");
+            status
+        })).await;
+        gate.open();
+        statuses
+    };
+    let (slow, fast) = futures_util::join!(slow, fast);
+
+    assert!(fast.iter().all(|s| *s == 200));
+    assert!(slow.iter().all(|s| *s == 200));
+    let mut expected = vec!["fast"; 20];
+    expected.extend(["slow"; 4]);
+    assert_eq!(answered.into_inner(), expected);
+}
+
+// Every documented operation reaches a handler: unsigned, they answer 401
+// or reject their input, never the 404 or 405 of an unregistered route
+#[actix_web::test]
+async fn openapi_matches_registered_routes() {
+    let app = memory_app().await;
+    let spec = serde_json::to_value(router::ApiDoc::openapi()).unwrap();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        let uri = path
+            .split('/')
+            .map(|segment| if segment.starts_with('{') { Uuid::nil().to_string() } else { segment.to_string() })
+            .collect::<Vec<_>>()
+            .join("/");
+        // Other keys of a path item, like parameters, aren't operations
+        for method in item.as_object().unwrap().keys().filter(|k| ["get", "post", "put", "patch", "delete"].contains(&k.as_str())) {
+            let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+            let req = test::TestRequest::default().method(method.clone()).uri(&format!("/api/v1{}", uri));
+            let status = status(&app, req).await;
+            assert!(status != 404 && status != 405, "{} {} answered {}", method, path, status);
+        }
+    }
+}
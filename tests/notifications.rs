@@ -0,0 +1,95 @@
+mod common;
+
+use actix_web::body::MessageBody;
+use actix_web::test;
+use futures_util::future::poll_fn;
+use serde_json::{json, Value};
+
+use hotchpotch_web_backend::models::{Team, User};
+
+use common::*;
+
+// it-octo leads a team that it-hubot and it-mona have asked to join
+async fn requested<S, B>(app: &S) -> (User, User, Team)
+where
+    S: actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let octo = sign_up(app, "it-octo").await;
+    let hubot = sign_up(app, "it-hubot").await;
+    let mona = sign_up(app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 4);
+    for user in [&hubot, &mona] {
+        let body = json!({"message": ""});
+        assert_eq!(status(app, post(&format!("/api/v1/teams/{}/requests", team.id), &user.name).set_json(body)).await, 201);
+    }
+    (octo, hubot, team)
+}
+
+#[actix_web::test]
+async fn lists_the_requests_a_user_sent() {
+    let _db = setup();
+    let app = app().await;
+    let (_, hubot, team) = requested(&app).await;
+    let requests: Value = json(&app, get("/api/v1/requests", "it-hubot"), 200).await;
+    assert_eq!(requests["total"], 1);
+    assert_eq!(requests["items"][0]["team_id"], team.id.to_string());
+    assert_eq!(requests["items"][0]["user_id"], hubot.id.to_string());
+    assert_eq!(requests["items"][0]["status"], "pending");
+    assert_eq!(status(&app, get("/api/v1/requests?sort=name", "it-hubot")).await, 400);
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/requests")).await, 401);
+}
+
+#[actix_web::test]
+async fn reads_notifications() {
+    let _db = setup();
+    let app = app().await;
+    let (_, hubot, _) = requested(&app).await;
+    let notifications: Value = json(&app, get("/api/v1/notifications", "it-octo"), 200).await;
+    assert_eq!(notifications["total"], 2);
+    assert_eq!(notifications["items"][0]["kind"], "request_created");
+    let me: Value = json(&app, get("/api/v1/users/me", "it-octo"), 200).await;
+    assert_eq!(me["unread_notifications"], 2);
+
+    let from_hubot = notifications["items"].as_array().unwrap().iter()
+        .find(|n| n["actor_id"] == hubot.id.to_string())
+        .unwrap();
+    let uri = format!("/api/v1/notifications/{}/read", from_hubot["id"].as_str().unwrap());
+    assert_eq!(status(&app, post(&uri, "it-hubot")).await, 404);
+    assert_eq!(status(&app, post(&uri, "it-octo")).await, 204);
+    let unread: Value = json(&app, get("/api/v1/notifications?unread=true", "it-octo"), 200).await;
+    assert_eq!(unread["total"], 1);
+    let uri = format!("/api/v1/notifications/{}/read", uuid::Uuid::new_v4());
+    assert_eq!(status(&app, post(&uri, "it-octo")).await, 404);
+
+    assert_eq!(status(&app, post("/api/v1/notifications/read_all", "it-octo")).await, 204);
+    let unread: Value = json(&app, get("/api/v1/notifications?unread=true", "it-octo"), 200).await;
+    assert_eq!(unread["total"], 0);
+    assert_eq!(status(&app, get("/api/v1/notifications?limit=0", "it-octo")).await, 400);
+    assert_eq!(status(&app, test::TestRequest::post().uri("/api/v1/notifications/read_all")).await, 401);
+}
+
+#[actix_web::test]
+async fn streams_new_notifications() {
+    let _db = setup();
+    let app = app().await;
+    let (_, hubot, team) = requested(&app).await;
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/notifications/stream")).await, 401);
+    let uri = format!("/api/v1/notifications/stream?access_token={}", hotchpotch_web_backend::auth::mock_token("it-hubot"));
+    let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.headers().get("Content-Type").unwrap(), "text/event-stream");
+
+    let uri = format!("/api/v1/teams/{}/requests/{}/accept", team.id, hubot.id);
+    assert_eq!(status(&app, post(&uri, "it-octo")).await, 200);
+    let mut body = Box::pin(res.into_body());
+    let chunk = match poll_fn(|cx| body.as_mut().poll_next(cx)).await {
+        Some(Ok(chunk)) => chunk,
+        _ => panic!("The stream ended without a notification"),
+    };
+    let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+    let notice: Value = serde_json::from_str(chunk.strip_prefix("data: ").unwrap().trim()).unwrap();
+    assert_eq!(notice["kind"], "request_accepted");
+    assert_eq!(notice["team_id"], team.id.to_string());
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use actix_web::test;
+use serde_json::Value;
+
+use common::*;
+
+#[actix_web::test]
+async fn probes_answer_without_a_token() {
+    let _db = setup();
+    let app = app().await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(test::read_body(res).await, "0w0");
+
+    let health: Value = json(&app, test::TestRequest::get().uri("/healthz"), 200).await;
+    assert_eq!(health["status"], "ok");
+    let readiness: Value = json(&app, test::TestRequest::get().uri("/readyz"), 200).await;
+    assert_eq!(readiness["checks"]["database"]["status"], "ok");
+    assert_eq!(readiness["checks"]["migrations"]["status"], "ok");
+    let version: Value = json(&app, test::TestRequest::get().uri("/version"), 200).await;
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(status(&app, test::TestRequest::get().uri("/metrics")).await, 200);
+}
+
+#[actix_web::test]
+async fn serves_the_api_docs() {
+    let _db = setup();
+    let app = app().await;
+    let spec: Value = json(&app, test::TestRequest::get().uri("/api/v1/openapi.json"), 200).await;
+    assert!(spec["paths"]["/events/{event_id}/teams"].is_object());
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/docs")).await, 200);
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/nowhere")).await, 404);
+}
+
+#[actix_web::test]
+async fn legacy_routes_are_marked_deprecated() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let res = test::call_service(&app, get(&format!("/api/users?user_id={}", octo.id), "it-octo").to_request()).await;
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.headers().get("Deprecation").unwrap(), "true");
+    let user: Value = test::read_body_json(res).await;
+    assert_eq!(user["name"], "it-octo");
+
+    let res = test::call_service(&app, get("/api/v1/users/me", "it-octo").to_request()).await;
+    assert!(res.headers().get("Deprecation").is_none());
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use actix_web::test;
+use serde_json::{json, Value};
+
+use hotchpotch_web_backend::cruds;
+
+use common::*;
+
+#[actix_web::test]
+async fn creates_and_lists_teams_and_solos() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let hubot = sign_up(&app, "it-hubot").await;
+    let event = create_event(&octo, "it-Jam");
+    let teams_uri = format!("/api/v1/events/{}/teams", event.id);
+    let solos_uri = format!("/api/v1/events/{}/solos", event.id);
+
+    let body = json!({"name": "it-Crabs", "desc": "We like Rust", "capacity": 3});
+    assert_eq!(status(&app, post(&teams_uri, "it-octo").set_json(body)).await, 201);
+    let body = json!({"name": "it-Huge", "desc": "", "capacity": 1000});
+    assert_eq!(status(&app, post(&teams_uri, "it-octo").set_json(body)).await, 400);
+    let req = test::TestRequest::post().uri(&teams_uri).set_json(json!({"name": "it-x", "desc": ""}));
+    assert_eq!(status(&app, req).await, 401);
+
+    let teams: Value = json(&app, get(&teams_uri, "it-hubot"), 200).await;
+    assert_eq!(teams["total"], 1);
+    assert_eq!(teams["items"][0]["reader_id"], octo.id.to_string());
+    assert_eq!(status(&app, get(&format!("{}?order=sideways", teams_uri), "it-hubot")).await, 400);
+    let team_id = teams["items"][0]["id"].as_str().unwrap();
+    let team: Value = json(&app, get(&format!("/api/v1/teams/{}", team_id), "it-hubot"), 200).await;
+    assert_eq!(team["name"], "it-Crabs");
+    assert_eq!(team["capacity"], 3);
+    assert_eq!(status(&app, get(&format!("/api/v1/teams/{}", uuid::Uuid::new_v4()), "it-hubot")).await, 404);
+    assert_eq!(status(&app, get("/api/v1/teams/not-a-team", "it-hubot")).await, 404);
+    assert_eq!(status(&app, test::TestRequest::get().uri(&format!("/api/v1/teams/{}", team_id))).await, 401);
+
+    assert_eq!(status(&app, post(&solos_uri, "it-hubot")).await, 201);
+    let solos: Value = json(&app, get(&solos_uri, "it-octo"), 201).await;
+    assert_eq!(solos["total"], 1);
+    assert_eq!(solos["items"][0]["id"], hubot.id.to_string());
+    assert_eq!(status(&app, get(&format!("{}?limit=1000", solos_uri), "it-octo")).await, 400);
+    assert_eq!(status(&app, test::TestRequest::post().uri(&solos_uri)).await, 401);
+}
+
+#[actix_web::test]
+async fn leaders_manage_wanted_roles() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    sign_up(&app, "it-mona").await;
+    sign_up(&app, "it-hubot").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 4);
+    let uri = format!("/api/v1/teams/{}/roles", team.id);
+
+    let team_detail: Value = json(&app, put(&uri, "it-octo").set_json(json!({"role": " Backend ", "count": 2})), 200).await;
+    assert_eq!(team_detail["wanted_roles"][0]["role"], "backend");
+    assert_eq!(team_detail["wanted_roles"][0]["count"], 2);
+    assert_eq!(team_detail["wanted_roles"][0]["filled"], 0);
+    let errors: Value = json(&app, put(&uri, "it-octo").set_json(json!({"role": "", "count": 0})), 422).await;
+    assert!(errors["errors"]["role"].is_array());
+    assert!(errors["errors"]["count"].is_array());
+    assert_eq!(status(&app, put(&uri, "it-mona").set_json(json!({"role": "design", "count": 1}))).await, 403);
+    let missing = format!("/api/v1/teams/{}/roles", uuid::Uuid::new_v4());
+    assert_eq!(status(&app, put(&missing, "it-octo").set_json(json!({"role": "design", "count": 1}))).await, 404);
+
+    // Members with a backend skill fill the role, and free it when they leave
+    let body = json!({"name": "it-Zig", "kind": "skill", "category": "backend"});
+    let skill: Value = json(&app, post("/api/v1/skills", "it-octo").set_json(body), 201).await;
+    let members = format!("/api/v1/teams/{}/members", team.id);
+    for login in ["it-mona", "it-hubot"] {
+        let body = json!({"skill_id": skill["id"], "level": 3});
+        assert_eq!(status(&app, put("/api/v1/users/me/skills", login).set_json(body)).await, 200);
+        assert_eq!(status(&app, post(&members, login)).await, 201);
+    }
+    let errors: Value = json(&app, put(&uri, "it-octo").set_json(json!({"role": "backend", "count": 1})), 422).await;
+    assert!(errors["errors"]["count"].is_array());
+    assert_eq!(status(&app, delete(&format!("{}/me", members), "it-mona")).await, 204);
+    let team_detail: Value = json(&app, put(&uri, "it-octo").set_json(json!({"role": "backend", "count": 1})), 200).await;
+    assert_eq!(team_detail["wanted_roles"][0]["filled"], 1);
+
+    assert_eq!(status(&app, delete(&format!("{}/backend", uri), "it-mona")).await, 403);
+    assert_eq!(status(&app, delete(&format!("{}/BACKEND", uri), "it-octo")).await, 204);
+    let team_detail: Value = json(&app, get(&format!("/api/v1/teams/{}", team.id), "it-octo"), 200).await;
+    assert_eq!(team_detail["wanted_roles"], json!([]));
+    assert_eq!(status(&app, delete(&format!("{}/backend", missing), "it-octo")).await, 404);
+}
+
+#[actix_web::test]
+async fn joins_and_leaves_teams() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let hubot = sign_up(&app, "it-hubot").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 2);
+    let uri = format!("/api/v1/teams/{}/members", team.id);
+
+    assert_eq!(status(&app, post(&uri, "it-hubot")).await, 201);
+    // The leader and hubot fill it
+    assert_eq!(status(&app, post(&uri, "it-mona")).await, 409);
+    let teams = cruds::get_event_teams_with_members(&event.id.to_string()).unwrap();
+    assert!(teams[0].1.iter().any(|m| m.user.id == hubot.id));
+    assert_eq!(status(&app, delete(&format!("{}/me", uri), "it-hubot")).await, 204);
+    let teams = cruds::get_event_teams_with_members(&event.id.to_string()).unwrap();
+    assert!(teams[0].1.iter().all(|m| m.user.id != hubot.id));
+    assert_eq!(status(&app, post(&uri, "it-mona")).await, 201);
+    assert_eq!(status(&app, test::TestRequest::post().uri(&uri)).await, 401);
+    assert_eq!(status(&app, test::TestRequest::delete().uri(&format!("{}/me", uri))).await, 401);
+}
+
+#[actix_web::test]
+async fn leaders_answer_requests() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let hubot = sign_up(&app, "it-hubot").await;
+    let mona = sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 2);
+    let uri = format!("/api/v1/teams/{}/requests", team.id);
+    for user in [&hubot, &mona] {
+        cruds::create_solo(&event.id.to_string(), &user.id.to_string()).unwrap();
+        // The applicant comes from the token, whatever the body says
+        let body = json!({"user_id": octo.id, "message": "Room for one more?"});
+        assert_eq!(status(&app, post(&uri, &user.name).set_json(body)).await, 201);
+    }
+    assert_eq!(status(&app, post(&uri, "it-mona").set_json(json!({"message": "Again"}))).await, 409);
+    let missing = format!("/api/v1/teams/{}/requests", uuid::Uuid::new_v4());
+    assert_eq!(status(&app, post(&missing, "it-mona").set_json(json!({"message": ""}))).await, 404);
+    let req = test::TestRequest::post().uri(&uri).set_json(json!({"message": ""}));
+    assert_eq!(status(&app, req).await, 401);
+
+    let accept = format!("{}/{}/accept", uri, hubot.id);
+    assert_eq!(status(&app, post(&accept, "it-mona")).await, 403);
+    assert_eq!(status(&app, post(&accept, "it-octo")).await, 200);
+    assert_eq!(status(&app, post(&accept, "it-octo")).await, 404);
+    let solos = cruds::get_event_solo_details(&event.id.to_string()).unwrap();
+    assert!(solos.iter().all(|s| s.user.id != hubot.id));
+
+    // Full now; mona's request stays pending
+    assert_eq!(status(&app, post(&format!("{}/{}/accept", uri, mona.id), "it-octo")).await, 409);
+    let decline = format!("{}/{}/decline", uri, mona.id);
+    assert_eq!(status(&app, post(&decline, "it-hubot")).await, 403);
+    assert_eq!(status(&app, post(&decline, "it-octo")).await, 200);
+    assert_eq!(status(&app, post(&decline, "it-octo")).await, 404);
+    // Declined applicants may ask again; members may not until they leave
+    assert_eq!(status(&app, post(&uri, "it-mona").set_json(json!({"message": "Still keen"}))).await, 201);
+    assert_eq!(status(&app, post(&decline, "it-octo")).await, 200);
+    assert_eq!(status(&app, post(&uri, "it-hubot").set_json(json!({"message": ""}))).await, 409);
+    assert_eq!(status(&app, delete(&format!("/api/v1/teams/{}/members/me", team.id), "it-hubot")).await, 204);
+    assert_eq!(status(&app, post(&uri, "it-hubot").set_json(json!({"message": "Back again"}))).await, 201);
+    assert_eq!(status(&app, post(&accept, "it-octo")).await, 200);
+    let missing = format!("/api/v1/teams/{}/requests/{}/accept", uuid::Uuid::new_v4(), mona.id);
+    assert_eq!(status(&app, post(&missing, "it-octo")).await, 404);
+}
+
+#[actix_web::test]
+async fn only_leaders_delete_teams() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    sign_up(&app, "it-mona").await;
+    let event = create_event(&octo, "it-Jam");
+    let team = create_team(&event, &octo, "it-Crabs", 4);
+    let uri = format!("/api/v1/teams/{}", team.id);
+
+    assert_eq!(status(&app, delete(&uri, "it-mona")).await, 403);
+    assert_eq!(status(&app, delete(&uri, "it-octo")).await, 204);
+    assert_eq!(status(&app, delete(&uri, "it-octo")).await, 404);
+    assert_eq!(status(&app, delete("/api/v1/teams/not-a-team", "it-octo")).await, 404);
+    assert_eq!(status(&app, test::TestRequest::delete().uri(&uri)).await, 401);
+}
+
+#[actix_web::test]
+async fn legacy_team_routes_take_ids_from_the_body_and_query() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    let event = create_event(&octo, "it-Jam");
+    let body = json!({"event_id": event.id, "name": "it-Crabs", "desc": ""});
+    assert_eq!(status(&app, post("/api/teams", "it-octo").set_json(body)).await, 201);
+    let teams: Value = json(&app, get(&format!("/api/teams/event?event_id={}", event.id), "it-octo"), 200).await;
+    let team_id = teams["items"][0]["id"].as_str().unwrap();
+    let team: Value = json(&app, get(&format!("/api/teams?team_id={}", team_id), "it-octo"), 200).await;
+    assert_eq!(team["name"], "it-Crabs");
+    // Routes added with v1 have no pre-v1 alias
+    let body = json!({"team_id": team_id, "role": "design", "count": 1});
+    assert_eq!(status(&app, put("/api/teams/roles", "it-octo").set_json(body)).await, 404);
+    assert_eq!(status(&app, delete(&format!("/api/teams?team_id={}", team_id), "it-octo")).await, 404);
+}
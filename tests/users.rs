@@ -0,0 +1,150 @@
+mod common;
+
+use actix_web::test;
+use serde_json::{json, Value};
+
+use hotchpotch_web_backend::cruds;
+
+use common::*;
+
+#[actix_web::test]
+async fn signs_up_and_reads_users() {
+    let _db = setup();
+    let app = app().await;
+    assert_eq!(status(&app, test::TestRequest::post().uri("/api/v1/users")).await, 401);
+    let octo = sign_up(&app, "it-octo").await;
+    assert_eq!(octo.icon_url.as_deref(), Some(hotchpotch_web_backend::auth::mock_avatar_url("it-octo").as_str()));
+
+    let me: Value = json(&app, get("/api/v1/users/me", "it-octo"), 200).await;
+    assert_eq!(me["id"], octo.id.to_string());
+    assert_eq!(me["unread_notifications"], 0);
+    assert_eq!(me["skills"], json!([]));
+
+    sign_up(&app, "it-hubot").await;
+    let other: Value = json(&app, get(&format!("/api/v1/users/{}", octo.id), "it-hubot"), 200).await;
+    assert_eq!(other["name"], "it-octo");
+    assert!(other.get("unread_notifications").is_none());
+    assert_eq!(status(&app, get(&format!("/api/v1/users/{}", uuid::Uuid::new_v4()), "it-hubot")).await, 404);
+    assert_eq!(status(&app, get("/api/v1/users/not-a-user", "it-hubot")).await, 404);
+}
+
+#[actix_web::test]
+async fn rejects_missing_and_unknown_tokens() {
+    let _db = setup();
+    let app = app().await;
+    assert_eq!(status(&app, test::TestRequest::get().uri("/api/v1/users/me")).await, 401);
+    let req = test::TestRequest::get().uri("/api/v1/users/me").insert_header(("Authorization", "Bearer mock_"));
+    assert_eq!(status(&app, req).await, 401);
+    let req = test::TestRequest::get().uri("/api/v1/users/me").insert_header(("Authorization", "Basic b2N0bw=="));
+    assert_eq!(status(&app, req).await, 401);
+    // Signed in, but never signed up
+    assert_eq!(status(&app, get("/api/v1/users/me", "it-nobody")).await, 500);
+}
+
+#[actix_web::test]
+async fn updates_the_profile() {
+    let _db = setup();
+    let app = app().await;
+    sign_up(&app, "it-octo").await;
+    let body = json!({"display_name": "Octo Cat", "profile": "Rustacean", "links": ["https://octo.example"]});
+    let user: Value = json(&app, patch("/api/v1/users/me", "it-octo").set_json(body), 200).await;
+    assert_eq!(user["display_name"], "Octo Cat");
+    assert_eq!(user["profile"], "Rustacean");
+    assert_eq!(user["links"], json!(["https://octo.example"]));
+
+    let body = json!({"display_name": " ", "links": ["not a url"]});
+    let errors: Value = json(&app, patch("/api/v1/users/me", "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["display_name"].is_array());
+    assert!(errors["errors"]["links[0]"].is_array());
+    let req = test::TestRequest::patch().uri("/api/v1/users/me").set_json(json!({}));
+    assert_eq!(status(&app, req).await, 401);
+}
+
+#[actix_web::test]
+async fn sets_up_and_unsubscribes_email() {
+    let _db = setup();
+    let app = app().await;
+    let octo = sign_up(&app, "it-octo").await;
+    assert_eq!(status(&app, get("/api/v1/users/me/email", "it-octo")).await, 404);
+
+    let body = json!({"email": "octo", "locale": "xx"});
+    let errors: Value = json(&app, put("/api/v1/users/me/email", "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["email"].is_array());
+    assert!(errors["errors"]["locale"].is_array());
+
+    let body = json!({"email": "octo@example.com", "on_join": false});
+    let preference: Value = json(&app, put("/api/v1/users/me/email", "it-octo").set_json(body), 200).await;
+    assert_eq!(preference["email"], "octo@example.com");
+    assert_eq!(preference["on_request"], true);
+    assert_eq!(preference["on_join"], false);
+    assert!(preference.get("unsubscribe_token").is_none());
+    let preference: Value = json(&app, get("/api/v1/users/me/email", "it-octo"), 200).await;
+    assert_eq!(preference["email"], "octo@example.com");
+    assert_eq!(preference["verified_at"], Value::Null);
+
+    // Nothing is mailed until the address is verified
+    let verification_token = cruds::get_email_preference(&octo.id).unwrap().unwrap().verification_token;
+    let verify_uri = format!("/api/v1/email/verify?token={}", verification_token);
+    assert_eq!(status(&app, test::TestRequest::get().uri(&verify_uri)).await, 200);
+    assert!(cruds::get_email_preference(&octo.id).unwrap().unwrap().verified_at.is_none());
+    assert_eq!(status(&app, test::TestRequest::post().uri(&verify_uri)).await, 200);
+    let preference: Value = json(&app, get("/api/v1/users/me/email", "it-octo"), 200).await;
+    assert!(preference["verified_at"].is_string());
+    let body = json!({"email": "octo@example.com", "on_join": true});
+    let preference: Value = json(&app, put("/api/v1/users/me/email", "it-octo").set_json(body), 200).await;
+    assert!(preference["verified_at"].is_string());
+    let body = json!({"email": "someone@example.com", "on_join": false});
+    let preference: Value = json(&app, put("/api/v1/users/me/email", "it-octo").set_json(body), 200).await;
+    assert_eq!(preference["verified_at"], Value::Null);
+    assert_eq!(status(&app, test::TestRequest::post().uri(&verify_uri)).await, 404);
+
+    // Opening the link only shows the page; the button sends the POST
+    let token = cruds::get_email_preference(&octo.id).unwrap().unwrap().unsubscribe_token;
+    let uri = format!("/api/v1/email/unsubscribe?token={}", token);
+    assert_eq!(status(&app, test::TestRequest::get().uri(&uri)).await, 200);
+    let preference: Value = json(&app, get("/api/v1/users/me/email", "it-octo"), 200).await;
+    assert_eq!(preference["on_request"], true);
+    assert_eq!(status(&app, test::TestRequest::post().uri(&uri)).await, 200);
+    let preference: Value = json(&app, get("/api/v1/users/me/email", "it-octo"), 200).await;
+    assert_eq!((&preference["on_request"], &preference["on_join"], &preference["on_team"]), (&json!(false), &json!(false), &json!(false)));
+    let req = test::TestRequest::post().uri(&format!("/api/v1/email/unsubscribe?token={}", uuid::Uuid::new_v4()));
+    assert_eq!(status(&app, req).await, 404);
+}
+
+#[actix_web::test]
+async fn manages_skills() {
+    let _db = setup();
+    let app = app().await;
+    sign_up(&app, "it-octo").await;
+    let body = json!({"name": "Zig", "kind": "skill", "category": "backend"});
+    let skill: Value = json(&app, post("/api/v1/skills", "it-octo").set_json(body), 201).await;
+    assert_eq!(skill["name"], "Zig");
+    let body = json!({"name": "Zig", "kind": "interest"});
+    assert_eq!(status(&app, post("/api/v1/skills", "it-octo").set_json(body)).await, 409);
+    let body = json!({"name": "", "kind": "hobby"});
+    let errors: Value = json(&app, post("/api/v1/skills", "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["name"].is_array());
+    assert!(errors["errors"]["kind"].is_array());
+    let req = test::TestRequest::post().uri("/api/v1/skills").set_json(json!({"name": "Zig", "kind": "skill"}));
+    assert_eq!(status(&app, req).await, 401);
+
+    let skills: Value = json(&app, get("/api/v1/skills?sort=name&limit=100", "it-octo"), 200).await;
+    assert!(skills["items"].as_array().unwrap().iter().any(|s| s["id"] == skill["id"]));
+    assert_eq!(status(&app, get("/api/v1/skills?sort=level", "it-octo")).await, 400);
+    assert_eq!(status(&app, get("/api/v1/skills?limit=0", "it-octo")).await, 400);
+
+    let skill_id = skill["id"].as_str().unwrap();
+    let body = json!({"skill_id": skill_id, "level": 9});
+    let errors: Value = json(&app, put("/api/v1/users/me/skills", "it-octo").set_json(body), 422).await;
+    assert!(errors["errors"]["level"].is_array());
+    let body = json!({"skill_id": "00000000-0000-0000-0000-000000000000", "level": 4});
+    assert_eq!(status(&app, put("/api/v1/users/me/skills", "it-octo").set_json(body)).await, 404);
+    let body = json!({"skill_id": skill_id, "level": 4});
+    let me: Value = json(&app, put("/api/v1/users/me/skills", "it-octo").set_json(body), 200).await;
+    assert_eq!(me["skills"].as_array().unwrap().len(), 1);
+    assert_eq!(me["skills"][0]["level"], 4);
+
+    assert_eq!(status(&app, delete(&format!("/api/v1/users/me/skills/{}", skill_id), "it-octo")).await, 204);
+    let me: Value = json(&app, get("/api/v1/users/me", "it-octo"), 200).await;
+    assert_eq!(me["skills"], json!([]));
+}